@@ -0,0 +1,975 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    io::Error,
+    path::{Path, PathBuf},
+};
+
+use crypto::{digest::Digest, sha1::Sha1};
+
+use crate::{
+    base, binary,
+    data::{DataType, DateErr, Stages, Ugit},
+    line_diff::{self, Hunk, LineKind},
+    merge::{self, MergeOptions, MergeResult},
+};
+
+/// 补丁中一个文件的修改
+#[derive(Clone, Debug, Default)]
+pub struct FilePatch {
+    /// 修改前的路径，新文件为 None
+    pub old_path: Option<PathBuf>,
+    /// 修改后的路径，删除的文件为 None
+    pub new_path: Option<PathBuf>,
+    /// 复制时原来的文件保留
+    pub copy: bool,
+    /// `index` 行里修改前后的对象名，可能是缩写
+    pub old_oid: Option<String>,
+    pub new_oid: Option<String>,
+    /// 文件权限，如 `100644`、`100755`
+    pub old_mode: Option<String>,
+    pub new_mode: Option<String>,
+    pub hunks: Vec<Hunk>,
+    pub binary: Option<BinaryPatch>,
+}
+
+impl FilePatch {
+    /// 出错时提示的路径
+    pub fn path(&self) -> &Path {
+        match (&self.old_path, &self.new_path) {
+            (Some(path), _) | (None, Some(path)) => path,
+            (None, None) => Path::new(""),
+        }
+    }
+
+    /// 反向的补丁，`apply --reverse` 用它撤销修改
+    pub fn reverse(&self) -> FilePatch {
+        let hunks = self
+            .hunks
+            .iter()
+            .map(|hunk| Hunk {
+                old_start: hunk.new_start,
+                old_len: hunk.new_len,
+                new_start: hunk.old_start,
+                new_len: hunk.old_len,
+                lines: hunk
+                    .lines
+                    .iter()
+                    .map(|(kind, line)| {
+                        let kind = match kind {
+                            LineKind::Added => LineKind::Removed,
+                            LineKind::Removed => LineKind::Added,
+                            LineKind::Context => LineKind::Context,
+                        };
+                        (kind, line.clone())
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        //没有反向数据的二进制补丁不能撤销
+        let binary = self.binary.as_ref().map(|binary| match binary {
+            BinaryPatch::Data {
+                forward,
+                reverse: Some(reverse),
+            } => BinaryPatch::Data {
+                forward: reverse.clone(),
+                reverse: Some(forward.clone()),
+            },
+            _ => BinaryPatch::Differ,
+        });
+
+        FilePatch {
+            old_path: self.new_path.clone(),
+            new_path: self.old_path.clone(),
+            copy: self.copy,
+            old_oid: self.new_oid.clone(),
+            new_oid: self.old_oid.clone(),
+            old_mode: self.new_mode.clone(),
+            new_mode: self.old_mode.clone(),
+            hunks,
+            binary,
+        }
+    }
+}
+
+/// 二进制文件的修改
+#[derive(Clone, Debug)]
+pub enum BinaryPatch {
+    /// 只有 `Binary files ... differ`，没有可以应用的内容
+    Differ,
+    /// `GIT binary patch`，反向的数据用来检查修改前的内容
+    Data {
+        forward: BinaryData,
+        reverse: Option<BinaryData>,
+    },
+}
+
+/// 二进制补丁的一段数据
+#[derive(Clone, Debug)]
+pub enum BinaryData {
+    /// 完整的内容
+    Literal(Vec<u8>),
+    /// 相对另一边内容的 delta
+    Delta(Vec<u8>),
+}
+
+impl BinaryData {
+    fn apply(&self, base: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            BinaryData::Literal(content) => Ok(content.clone()),
+            BinaryData::Delta(delta) => binary::apply_delta(base, delta),
+        }
+    }
+}
+
+/// `apply` 修改哪里以及怎么应用
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ApplyOptions {
+    /// 只修改索引，不碰工作区
+    pub cached: bool,
+    /// 同时修改索引和工作区，要求两者的内容一致
+    pub index: bool,
+    /// 只检查能不能应用，什么都不修改
+    pub check: bool,
+    pub reverse: bool,
+    /// 不能直接应用时，用 `index` 行里的原始对象做三方合并
+    pub three_way: bool,
+    /// hunk 前后至少要匹配的上下文行数，为 None 时所有上下文都要匹配
+    pub min_context: Option<usize>,
+}
+
+/// 没有在原位置或没有用全部上下文应用的 hunk
+#[derive(Clone, Debug)]
+pub struct HunkReport {
+    pub path: PathBuf,
+    /// 第几个 hunk，从 1 开始
+    pub number: usize,
+    /// 实际应用的位置，从 1 开始
+    pub line: usize,
+    pub offset: isize,
+    /// 忽略了前后多少行上下文
+    pub fuzz: usize,
+}
+
+impl fmt::Display for HunkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hunk #{} succeeded at {}", self.number, self.line)?;
+        if self.fuzz > 0 {
+            write!(f, " with fuzz {}", self.fuzz)?;
+        }
+        if self.offset != 0 {
+            let plural = if self.offset.abs() == 1 { "" } else { "s" };
+            write!(f, " (offset {} line{plural})", self.offset)?;
+        }
+        write!(f, ".")
+    }
+}
+
+/// 应用补丁的结果
+#[derive(Debug, Default)]
+pub struct ApplyResult {
+    pub hunks: Vec<HunkReport>,
+    /// 三方合并后还有冲突的文件
+    pub conflicts: Vec<PathBuf>,
+}
+
+/// 解析 unified diff，支持 `diff --git` 的扩展头和普通的 `---`/`+++` 格式
+pub fn parse_patch(text: &str) -> Result<Vec<FilePatch>, String> {
+    let lines = line_diff::split_lines(text);
+    let mut patches = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let traditional = line.starts_with("--- ")
+            && lines
+                .get(i + 1)
+                .is_some_and(|next| next.starts_with("+++ "));
+        if line.starts_with("diff --git ") || traditional {
+            let (patch, next) = parse_file(&lines, i)?;
+            patches.push(patch);
+            i = next;
+        } else {
+            i += 1;
+        }
+    }
+
+    Ok(patches)
+}
+
+/// 从 `start` 开始解析一个文件的修改，返回它和下一行的位置
+fn parse_file(lines: &[&str], start: usize) -> Result<(FilePatch, usize), String> {
+    let mut patch = FilePatch::default();
+    let mut i = start;
+    let (mut is_new, mut is_delete) = (false, false);
+
+    if let Some(names) = lines[i].strip_prefix("diff --git ") {
+        let (old, new) = git_diff_paths(names.trim_end_matches('\n'))
+            .ok_or_else(|| format!("invalid diff header: {}", names.trim_end()))?;
+        patch.old_path = Some(old);
+        patch.new_path = Some(new);
+        i += 1;
+
+        while let Some(line) = lines.get(i) {
+            let line = line.trim_end_matches('\n');
+            let header = |prefix: &str| line.strip_prefix(prefix).map(PathBuf::from);
+            let mode = |prefix: &str| line.strip_prefix(prefix).map(str::to_string);
+            if let Some(new_mode) = mode("new file mode ") {
+                patch.new_mode = Some(new_mode);
+                is_new = true;
+            } else if let Some(old_mode) = mode("deleted file mode ") {
+                patch.old_mode = Some(old_mode);
+                is_delete = true;
+            } else if let Some(old_mode) = mode("old mode ") {
+                patch.old_mode = Some(old_mode);
+            } else if let Some(new_mode) = mode("new mode ") {
+                patch.new_mode = Some(new_mode);
+            } else if let Some(path) = header("rename from ") {
+                patch.old_path = Some(path);
+            } else if let Some(path) = header("rename to ") {
+                patch.new_path = Some(path);
+            } else if let Some(path) = header("copy from ") {
+                patch.old_path = Some(path);
+                patch.copy = true;
+            } else if let Some(path) = header("copy to ") {
+                patch.new_path = Some(path);
+            } else if let Some(index) = line.strip_prefix("index ") {
+                let mut parts = index.split_whitespace();
+                if let Some((old, new)) = parts.next().and_then(|range| range.split_once("..")) {
+                    patch.old_oid = Some(old.to_string());
+                    patch.new_oid = Some(new.to_string());
+                }
+                //权限没有变化时写在 index 行的最后
+                if let Some(mode) = parts.next() {
+                    patch.old_mode.get_or_insert_with(|| mode.to_string());
+                    patch.new_mode.get_or_insert_with(|| mode.to_string());
+                }
+            } else if !(line.starts_with("similarity index ")
+                || line.starts_with("dissimilarity index "))
+            {
+                break;
+            }
+            i += 1;
+        }
+    }
+
+    if lines.get(i).is_some_and(|line| line.starts_with("--- ")) {
+        let old = file_name(&lines[i][4..]);
+        let new = lines
+            .get(i + 1)
+            .and_then(|line| line.strip_prefix("+++ "))
+            .map(file_name)
+            .ok_or_else(|| format!("missing +++ line after {}", lines[i].trim_end()))?;
+        is_new |= old.is_none();
+        is_delete |= new.is_none();
+        patch.old_path = old.or(patch.old_path);
+        patch.new_path = new.or(patch.new_path);
+        i += 2;
+
+        while lines.get(i).is_some_and(|line| line.starts_with("@@ ")) {
+            let (hunk, next) = parse_hunk(lines, i)?;
+            patch.hunks.push(hunk);
+            i = next;
+        }
+    } else if lines
+        .get(i)
+        .is_some_and(|line| line.starts_with("Binary files "))
+    {
+        patch.binary = Some(BinaryPatch::Differ);
+        i += 1;
+    } else if lines
+        .get(i)
+        .is_some_and(|line| *line == "GIT binary patch\n")
+    {
+        let (binary, next) = parse_binary(lines, i + 1)?;
+        patch.binary = Some(binary);
+        i = next;
+    }
+
+    if is_new {
+        patch.old_path = None;
+    }
+    if is_delete {
+        patch.new_path = None;
+    }
+    if patch.old_path.is_none() && patch.new_path.is_none() {
+        return Err(String::from("patch with only garbage"));
+    }
+
+    Ok((patch, i))
+}
+
+/// `a/foo b/foo`，两边相同时路径里可以有 ` b/`
+fn git_diff_paths(names: &str) -> Option<(PathBuf, PathBuf)> {
+    let names = names.strip_prefix("a/")?;
+    let half = names.len().checked_sub(3)? / 2;
+    if let (Some(old), Some(new)) = (names.get(..half), names.get(half..)) {
+        if new.strip_prefix(" b/") == Some(old) {
+            return Some((PathBuf::from(old), PathBuf::from(old)));
+        }
+    }
+
+    let (old, new) = names.split_once(" b/")?;
+    Some((PathBuf::from(old), PathBuf::from(new)))
+}
+
+/// `---`/`+++` 后面的路径，去掉 `a/`、`b/` 前缀和时间戳，`/dev/null` 为 None
+fn file_name(name: &str) -> Option<PathBuf> {
+    let name = name.trim_end_matches('\n');
+    let name = name.split_once('\t').map_or(name, |(name, _)| name);
+    if name == "/dev/null" {
+        return None;
+    }
+
+    let name = name
+        .strip_prefix("a/")
+        .or_else(|| name.strip_prefix("b/"))
+        .unwrap_or(name);
+    Some(PathBuf::from(name))
+}
+
+/// `@@ -1,3 +1,4 @@` 和后面的行，按头里的行数读取
+fn parse_hunk(lines: &[&str], start: usize) -> Result<(Hunk, usize), String> {
+    let invalid = || format!("corrupt patch at line {}", start + 1);
+    let header = lines[start].trim_end_matches('\n');
+    let mut ranges = header.split(' ').skip(1);
+    let old = ranges.next().and_then(|range| range.strip_prefix('-'));
+    let new = ranges.next().and_then(|range| range.strip_prefix('+'));
+    let (old_start, old_len) = old.and_then(parse_range).ok_or_else(invalid)?;
+    let (new_start, new_len) = new.and_then(parse_range).ok_or_else(invalid)?;
+
+    let mut hunk = Hunk {
+        old_start,
+        old_len,
+        new_start,
+        new_len,
+        lines: vec![],
+    };
+    let (mut old_left, mut new_left) = (old_len, new_len);
+    let mut i = start + 1;
+    while old_left > 0 || new_left > 0 {
+        let line = *lines.get(i).ok_or_else(invalid)?;
+        let (kind, content) = match line.split_at_checked(1) {
+            Some((" ", content)) => (LineKind::Context, content),
+            Some(("-", content)) => (LineKind::Removed, content),
+            Some(("+", content)) => (LineKind::Added, content),
+            //有的邮件客户端会删掉空的上下文行行首的空格
+            _ if line == "\n" => (LineKind::Context, line),
+            Some(("\\", _)) => {
+                strip_newline(&mut hunk);
+                i += 1;
+                continue;
+            }
+            _ => return Err(invalid()),
+        };
+
+        match kind {
+            LineKind::Context if old_left > 0 && new_left > 0 => {
+                old_left -= 1;
+                new_left -= 1;
+            }
+            LineKind::Removed if old_left > 0 => old_left -= 1,
+            LineKind::Added if new_left > 0 => new_left -= 1,
+            _ => return Err(invalid()),
+        }
+        hunk.lines.push((kind, content.to_string()));
+        i += 1;
+    }
+
+    if lines.get(i).is_some_and(|line| line.starts_with('\\')) {
+        strip_newline(&mut hunk);
+        i += 1;
+    }
+
+    Ok((hunk, i))
+}
+
+/// `\ No newline at end of file` 说明上一行没有换行符
+fn strip_newline(hunk: &mut Hunk) {
+    if let Some((_, line)) = hunk.lines.last_mut() {
+        if line.ends_with('\n') {
+            line.pop();
+        }
+    }
+}
+
+/// `3,4` 或 `3`，行数为 0 时起始行就是插入的位置，否则从 1 开始
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let (start, len) = match range.split_once(',') {
+        Some((start, len)) => (start.parse::<usize>().ok()?, len.parse().ok()?),
+        None => (range.parse().ok()?, 1),
+    };
+
+    match len {
+        0 => Some((start, 0)),
+        _ => Some((start.checked_sub(1)?, len)),
+    }
+}
+
+/// `GIT binary patch` 之后的正向和反向数据
+fn parse_binary(lines: &[&str], start: usize) -> Result<(BinaryPatch, usize), String> {
+    let mut i = start;
+    let mut blocks = vec![];
+    while blocks.len() < 2 {
+        let header = match lines.get(i) {
+            Some(header) => header.trim_end_matches('\n'),
+            None => break,
+        };
+        let (kind, size) = match header.split_once(' ') {
+            Some((kind @ ("literal" | "delta"), size)) => (kind, size),
+            _ => break,
+        };
+        let size = size
+            .parse()
+            .map_err(|_| format!("corrupt binary patch header: {header}"))?;
+
+        let data_start = i + 1;
+        i = data_start;
+        while lines.get(i).is_some_and(|line| line.trim_end().len() > 1) {
+            i += 1;
+        }
+        let data = lines[data_start..i]
+            .iter()
+            .map(|line| line.trim_end())
+            .collect::<Vec<_>>();
+        let data = binary::decode_patch_data(&data, size)?;
+        blocks.push(match kind {
+            "literal" => BinaryData::Literal(data),
+            _ => BinaryData::Delta(data),
+        });
+        //每段数据后面有一个空行
+        i += 1;
+    }
+
+    let mut blocks = blocks.into_iter();
+    let forward = blocks
+        .next()
+        .ok_or_else(|| String::from("binary patch without data"))?;
+    Ok((
+        BinaryPatch::Data {
+            forward,
+            reverse: blocks.next(),
+        },
+        i,
+    ))
+}
+
+/// 内容对应的对象名，不写入对象库
+fn blob_oid(content: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.input(content);
+    hasher.result_str()
+}
+
+/// hunk 实际应用的位置
+#[derive(Clone, Copy, Debug)]
+struct Placement {
+    /// 在修改前的内容中的起始行，从 0 开始
+    start: usize,
+    offset: isize,
+    fuzz: usize,
+}
+
+/// 把 hunk 依次应用到内容上，找不到原位置时上下挪动，再按 `min_context` 去掉多余的上下文，失败时返回出错的 hunk 的下标
+fn apply_text(
+    content: &str,
+    hunks: &[Hunk],
+    min_context: Option<usize>,
+) -> Result<(String, Vec<Placement>), usize> {
+    let old = line_diff::split_lines(content);
+    let mut result = String::new();
+    let mut placements = vec![];
+    let mut cursor = 0;
+    let mut offset = 0isize;
+    for (n, hunk) in hunks.iter().enumerate() {
+        let is_context = |(kind, _): &&(LineKind, String)| *kind == LineKind::Context;
+        let leading = hunk.lines.iter().take_while(is_context).count();
+        let trailing = match leading == hunk.lines.len() {
+            true => 0,
+            false => hunk.lines.iter().rev().take_while(is_context).count(),
+        };
+        let (max_lead, max_trail) = match min_context {
+            Some(min) => (leading.saturating_sub(min), trailing.saturating_sub(min)),
+            None => (0, 0),
+        };
+
+        let mut found = None;
+        for fuzz in 0..=max_lead.max(max_trail) {
+            let (lead, trail) = (fuzz.min(max_lead), fuzz.min(max_trail));
+            let lines = &hunk.lines[lead..hunk.lines.len() - trail];
+            let preimage = lines
+                .iter()
+                .filter(|(kind, _)| *kind != LineKind::Added)
+                .map(|(_, line)| line.as_str())
+                .collect::<Vec<_>>();
+            let expected = (hunk.old_start as isize + lead as isize + offset).max(cursor as isize);
+            if let Some(pos) = find_lines(&old, &preimage, expected as usize, cursor) {
+                found = Some((pos, lead, fuzz, lines, preimage.len()));
+                break;
+            }
+        }
+        let (pos, lead, fuzz, lines, len) = found.ok_or(n)?;
+
+        let start = pos - lead.min(pos);
+        offset = start as isize - hunk.old_start as isize;
+        placements.push(Placement {
+            start,
+            offset,
+            fuzz,
+        });
+
+        for line in &old[cursor..pos] {
+            result.push_str(line);
+        }
+        for (kind, line) in lines {
+            if *kind != LineKind::Removed {
+                result.push_str(line);
+            }
+        }
+        cursor = pos + len;
+    }
+
+    for line in &old[cursor..] {
+        result.push_str(line);
+    }
+
+    Ok((result, placements))
+}
+
+/// 从 `expected` 开始向两边找和 `lines` 相同的位置，不早于 `min`
+fn find_lines(old: &[&str], lines: &[&str], expected: usize, min: usize) -> Option<usize> {
+    let last = old.len().checked_sub(lines.len())?;
+    if min > last {
+        return None;
+    }
+
+    let expected = expected.clamp(min, last);
+    let matches = |pos: usize| old[pos..pos + lines.len()] == *lines;
+    for distance in 0..=last - min {
+        if expected >= min + distance && matches(expected - distance) {
+            return Some(expected - distance);
+        }
+        if expected + distance <= last && matches(expected + distance) {
+            return Some(expected + distance);
+        }
+    }
+
+    None
+}
+
+impl Ugit {
+    /// 应用补丁，有一个文件失败时什么都不修改，`index` 时同时改索引，`cached` 时只改索引
+    pub fn apply_patches(
+        &self,
+        patches: &[FilePatch],
+        options: &ApplyOptions,
+    ) -> Result<ApplyResult, DateErr> {
+        let reversed;
+        let patches = match options.reverse {
+            true => {
+                reversed = patches.iter().map(FilePatch::reverse).collect::<Vec<_>>();
+                &reversed
+            }
+            false => patches,
+        };
+
+        let mut index = self.get_index()?;
+        //前面的补丁修改过的文件，None 表示已经删除
+        let mut updates: HashMap<PathBuf, Option<Vec<u8>>> = HashMap::new();
+        let mut order = vec![];
+        let mut modes = vec![];
+        let mut conflicts = HashMap::new();
+        let mut result = ApplyResult::default();
+
+        for patch in patches {
+            let preimage = match &patch.old_path {
+                Some(path) => self.read_preimage(path, &index, &updates, options)?,
+                None => {
+                    let path = patch.path();
+                    self.check_absent(path, &index, &updates, options)?;
+                    vec![]
+                }
+            };
+
+            let content = match &patch.binary {
+                Some(binary) => apply_binary(patch, binary, &preimage)?,
+                None => {
+                    let text = String::from_utf8_lossy(&preimage);
+                    match apply_text(&text, &patch.hunks, options.min_context) {
+                        Ok((text, placements)) => {
+                            for (i, placement) in placements.iter().enumerate() {
+                                if placement.offset != 0 || placement.fuzz > 0 {
+                                    result.hunks.push(HunkReport {
+                                        path: patch.path().to_path_buf(),
+                                        number: i + 1,
+                                        line: placement.start + 1,
+                                        offset: placement.offset,
+                                        fuzz: placement.fuzz,
+                                    });
+                                }
+                            }
+                            text.into_bytes()
+                        }
+                        Err(n) => {
+                            let path = patch.path().display();
+                            let line = patch.hunks[n].old_start + 1;
+                            eprintln!("error: patch failed: {path}:{line}");
+                            if !options.three_way {
+                                return Err(DateErr::Err(format!("{path}: patch does not apply")));
+                            }
+
+                            eprintln!("Falling back to three-way merge...");
+                            let (content, stages) = self.three_way(patch, &preimage)?;
+                            match stages {
+                                Some(stages) => {
+                                    eprintln!("Applied patch to '{path}' with conflicts.");
+                                    let new_path = patch.new_path.clone().unwrap_or_default();
+                                    result.conflicts.push(new_path.clone());
+                                    conflicts.insert(new_path, stages);
+                                }
+                                None => eprintln!("Applied patch to '{path}' cleanly."),
+                            }
+                            content
+                        }
+                    }
+                }
+            };
+
+            if let Some(old) = &patch.old_path {
+                if !patch.copy && patch.new_path.as_ref() != Some(old) {
+                    updates.insert(old.clone(), None);
+                    order.push(old.clone());
+                }
+            }
+            match &patch.new_path {
+                Some(new) => {
+                    updates.insert(new.clone(), Some(content));
+                    order.push(new.clone());
+                    if let Some(mode) = patch
+                        .new_mode
+                        .as_ref()
+                        .filter(|_| patch.new_mode != patch.old_mode)
+                    {
+                        modes.push((new.clone(), mode.clone()));
+                    }
+                }
+                None if !content.is_empty() => {
+                    return Err(DateErr::Err(format!(
+                        "{}: removal patch leaves file contents",
+                        patch.path().display()
+                    )))
+                }
+                None => {}
+            }
+        }
+
+        if options.check {
+            return Ok(result);
+        }
+
+        //先删除再写入，文件和目录互相替换时才不会冲突
+        let mut seen = HashSet::new();
+        order.retain(|path| seen.insert(path.clone()));
+        order.sort_by_key(|path| updates[path].is_some());
+        for path in &order {
+            let key = path.to_string_lossy().to_string();
+            match &updates[path] {
+                None => {
+                    if !options.cached {
+                        base::remove_working_file(path)?;
+                    }
+                    index.remove(&key);
+                }
+                Some(content) => {
+                    if !options.cached {
+                        if let Some(parent) = path.parent() {
+                            fs::create_dir_all(parent)?;
+                        }
+                        fs::write(path, content)?;
+                    }
+                    if options.cached || options.index {
+                        index.insert(key, self.hash(content, DataType::Blob)?);
+                    }
+                }
+            }
+        }
+        if !options.cached {
+            for (path, mode) in &modes {
+                set_mode(path, mode)?;
+            }
+        }
+
+        if options.cached || options.index {
+            self.write_index(&index)?;
+            if !conflicts.is_empty() {
+                let mut recorded = self.get_conflicts()?;
+                for (path, stages) in conflicts {
+                    recorded.insert(path.to_string_lossy().to_string(), stages);
+                }
+                self.write_conflicts(&recorded)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// 在补丁基于的原始内容上应用，再和当前内容合并，有冲突时返回各方的版本
+    fn three_way(
+        &self,
+        patch: &FilePatch,
+        preimage: &[u8],
+    ) -> Result<(Vec<u8>, Option<Stages>), DateErr> {
+        let lacks = || {
+            DateErr::Err(String::from(
+                "repository lacks the necessary blob to perform 3-way merge.",
+            ))
+        };
+        let base_oid = patch
+            .old_oid
+            .as_deref()
+            .filter(|_| patch.old_path.is_some())
+            .and_then(|oid| self.find_object(oid))
+            .ok_or_else(lacks)?;
+        let base = self.get_object(&base_oid, DataType::Blob)?;
+        let (theirs, _) = apply_text(&base, &patch.hunks, None).map_err(|_| lacks())?;
+        let ours = String::from_utf8_lossy(preimage);
+
+        let options = MergeOptions {
+            quiet: true,
+            ..MergeOptions::default()
+        };
+        match merge::merge3(&base, &ours, &theirs, &options) {
+            MergeResult::Clean(content) => Ok((content.into_bytes(), None)),
+            MergeResult::Conflicted { content, .. } => {
+                let stages = Stages {
+                    base: Some(base_oid),
+                    ours: Some(self.hash(preimage, DataType::Blob)?),
+                    theirs: Some(self.hash(theirs.as_bytes(), DataType::Blob)?),
+                };
+                Ok((content.into_bytes(), Some(stages)))
+            }
+        }
+    }
+
+    /// 按前缀找对象，补丁里的对象名通常是缩写，有多个匹配时不确定是哪一个
+    fn find_object(&self, prefix: &str) -> Option<String> {
+        if prefix.is_empty() || prefix.bytes().all(|b| b == b'0') {
+            return None;
+        }
+
+        let mut found = fs::read_dir(Path::new(self.git_dir()).join("objects"))
+            .ok()?
+            .filter_map(Result::ok)
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with(prefix));
+        let oid = found.next()?;
+        found.next().is_none().then_some(oid)
+    }
+
+    /// 补丁修改前的内容，优先用前面的补丁修改后的结果
+    fn read_preimage(
+        &self,
+        path: &Path,
+        index: &HashMap<String, String>,
+        updates: &HashMap<PathBuf, Option<Vec<u8>>>,
+        options: &ApplyOptions,
+    ) -> Result<Vec<u8>, DateErr> {
+        let missing =
+            |place: &str| DateErr::Err(format!("{}: does not exist in {place}", path.display()));
+        match updates.get(path) {
+            Some(Some(content)) => return Ok(content.clone()),
+            Some(None) => return Err(missing("index")),
+            None => {}
+        }
+
+        let staged = index.get(path.to_string_lossy().as_ref());
+        if options.cached {
+            let oid = staged.ok_or_else(|| missing("index"))?;
+            return self.get_object_bytes(oid, DataType::Blob);
+        }
+
+        let content = fs::read(path)
+            .map_err(|_| DateErr::Err(format!("{}: No such file or directory", path.display())))?;
+        if options.index {
+            let oid = staged.ok_or_else(|| missing("index"))?;
+            if *oid != blob_oid(&content) {
+                return Err(DateErr::Err(format!(
+                    "{}: does not match index",
+                    path.display()
+                )));
+            }
+        }
+
+        Ok(content)
+    }
+
+    /// 新文件不能已经存在
+    fn check_absent(
+        &self,
+        path: &Path,
+        index: &HashMap<String, String>,
+        updates: &HashMap<PathBuf, Option<Vec<u8>>>,
+        options: &ApplyOptions,
+    ) -> Result<(), DateErr> {
+        let exists =
+            |place: &str| DateErr::Err(format!("{}: already exists in {place}", path.display()));
+        match updates.get(path) {
+            Some(Some(_)) => return Err(exists("working directory")),
+            Some(None) => return Ok(()),
+            None => {}
+        }
+
+        if (options.cached || options.index) && index.contains_key(path.to_string_lossy().as_ref())
+        {
+            return Err(exists("index"));
+        }
+        if !options.cached && path.exists() {
+            return Err(exists("working directory"));
+        }
+        Ok(())
+    }
+}
+
+/// 应用二进制补丁，有反向数据时确认补丁是基于当前内容生成的
+fn apply_binary(
+    patch: &FilePatch,
+    binary: &BinaryPatch,
+    preimage: &[u8],
+) -> Result<Vec<u8>, DateErr> {
+    let path = patch.path().display();
+    let (forward, reverse) = match binary {
+        BinaryPatch::Differ => {
+            return Err(DateErr::Err(format!(
+                "cannot apply binary patch to '{path}' without full index line"
+            )))
+        }
+        BinaryPatch::Data { forward, reverse } => (forward, reverse),
+    };
+
+    let mismatch = || {
+        DateErr::Err(format!(
+            "the patch applies to '{path}', which does not match the current contents."
+        ))
+    };
+    let content = forward.apply(preimage).map_err(|_| mismatch())?;
+    if let Some(reverse) = reverse {
+        if reverse.apply(&content).ok().as_deref() != Some(preimage) {
+            return Err(mismatch());
+        }
+    }
+
+    Ok(content)
+}
+
+/// 索引不记录权限，可执行位只体现在工作区
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: &str) -> Result<(), Error> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = fs::metadata(path)?.permissions();
+    let bits = match mode {
+        "100755" => permissions.mode() | 0o111,
+        _ => permissions.mode() & !0o111,
+    };
+    permissions.set_mode(bits);
+    fs::set_permissions(path, permissions)
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: &str) -> Result<(), Error> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "\
+diff --git a/f b/f
+index 1111111..2222222 100644
+--- a/f
++++ b/f
+@@ -2,3 +2,3 @@
+ b
+-c
++C
+ d
+@@ -7,3 +7,3 @@
+ g
+-h
++H
+ i
+";
+
+    fn hunks(patch: &str) -> Vec<Hunk> {
+        let mut patches = parse_patch(patch).unwrap();
+        assert_eq!(patches.len(), 1);
+        patches.remove(0).hunks
+    }
+
+    fn apply(content: &str, patch: &str, min_context: Option<usize>) -> Result<String, usize> {
+        apply_text(content, &hunks(patch), min_context).map(|(result, _)| result)
+    }
+
+    #[test]
+    fn parse_git_diff() {
+        let patch = parse_patch(PATCH).unwrap().remove(0);
+        assert_eq!(patch.path(), Path::new("f"));
+        assert_eq!(patch.old_oid.as_deref(), Some("1111111"));
+        assert_eq!(patch.new_mode.as_deref(), Some("100644"));
+        assert_eq!(patch.hunks.len(), 2);
+        assert_eq!((patch.hunks[1].old_start, patch.hunks[1].old_len), (6, 3));
+        assert!(parse_patch("--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n a\n").is_err());
+    }
+
+    #[test]
+    fn apply_in_place_and_reverse() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+        let new = "a\nb\nC\nd\ne\nf\ng\nH\ni\n";
+        assert_eq!(apply(old, PATCH, None).unwrap(), new);
+
+        let reversed = parse_patch(PATCH).unwrap()[0].reverse();
+        let (result, _) = apply_text(new, &reversed.hunks, None).unwrap();
+        assert_eq!(result, old);
+    }
+
+    #[test]
+    fn apply_with_offset() {
+        let old = "x\nx\na\nb\nc\nd\ne\nf\ng\nh\ni\n";
+        let (result, placements) = apply_text(old, &hunks(PATCH), None).unwrap();
+        assert_eq!(result, "x\nx\na\nb\nC\nd\ne\nf\ng\nH\ni\n");
+        assert_eq!(
+            placements.iter().map(|p| p.offset).collect::<Vec<_>>(),
+            [2, 2]
+        );
+    }
+
+    #[test]
+    fn apply_with_fuzz() {
+        let old = "a\nB\nc\nd\ne\nf\ng\nh\ni\n";
+        assert_eq!(apply(old, PATCH, None), Err(0));
+        let (result, placements) = apply_text(old, &hunks(PATCH), Some(0)).unwrap();
+        assert_eq!(result, "a\nB\nC\nd\ne\nf\ng\nH\ni\n");
+        assert_eq!(placements[0].fuzz, 1);
+        assert_eq!(placements[1].fuzz, 0);
+    }
+
+    #[test]
+    fn conflicting_hunks() {
+        assert_eq!(apply("a\nb\nc\nd\ne\nf\ng\nX\ni\n", PATCH, None), Err(1));
+        assert_eq!(apply("", PATCH, Some(0)), Err(0));
+    }
+
+    #[test]
+    fn empty_input_and_missing_newline() {
+        let create =
+            "--- /dev/null\n+++ b/f\n@@ -0,0 +1,2 @@\n+a\n+b\n\\ No newline at end of file\n";
+        let created = parse_patch(create).unwrap().remove(0);
+        assert_eq!(created.old_path, None);
+        assert_eq!(apply("", create, None).unwrap(), "a\nb");
+
+        let newline =
+            "--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+b\n";
+        assert_eq!(apply("a\nb", newline, None).unwrap(), "a\nb\n");
+        assert_eq!(apply("a\nb\n", newline, None), Err(0));
+    }
+}
@@ -1,19 +1,74 @@
 use std::{
     collections::{HashMap, HashSet, LinkedList},
-    env,
+    env, fmt,
     fs::{self},
     io::Error,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 
-use crate::data::{self, DataType, DateErr, RefValue, Ugit};
+use crate::{
+    data::{self, DataType, DateErr, RefValue, Ugit},
+    date,
+    diff::{self, MergedTree},
+    merge::{ConflictStyle, Favor, MergeOptions},
+    pathspec::Pathspec,
+    strategy::{MergeHead, Strategy, StrategyOption},
+};
 
+#[derive(Clone, Debug, Default)]
 pub struct Commit {
     pub tree: Option<String>,
     pub parents: Vec<String>,
+    /// 早期的提交没有记录作者和提交者
+    pub author: Option<Signature>,
+    pub committer: Option<Signature>,
     pub message: Option<String>,
 }
 
+/// 提交的作者或者提交者
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    /// unix 时间戳
+    pub time: i64,
+    /// 时区，相对 UTC 的分钟数
+    pub offset: i32,
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} <{}> {} {}",
+            self.name,
+            self.email,
+            self.time,
+            date::format_offset(self.offset)
+        )
+    }
+}
+
+impl FromStr for Signature {
+    type Err = String;
+
+    /// `名字 <邮箱> 时间戳 时区`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid signature: {s:?}");
+        let (name, rest) = s.split_once('<').ok_or_else(invalid)?;
+        let (email, date) = rest.split_once('>').ok_or_else(invalid)?;
+        let (time, offset) = date::parse_date(date).ok_or_else(invalid)?;
+
+        Ok(Signature {
+            name: name.trim().to_string(),
+            email: email.trim().to_string(),
+            time,
+            offset,
+        })
+    }
+}
+
 #[derive(Debug)]
 pub enum Node {
     Dir(HashMap<String, Node>),
@@ -31,7 +86,7 @@ impl Ugit {
 
         //简单粗暴，直接遍历
         let refs_to_try: [&str; 4] = [
-            &name,
+            name,
             &format!("refs/{name}"),
             &format!("refs/tags/{name}"),
             &format!("refs/heads/{name}"),
@@ -39,7 +94,7 @@ impl Ugit {
 
         for name in refs_to_try {
             if let Some(val) = self
-                .get_ref(name, false)
+                .get_ref(name, true)
                 .filter(|ref_val| !ref_val.value.is_empty())
             {
                 return val.value;
@@ -101,7 +156,7 @@ impl Ugit {
     }
 
     pub fn read_tree(&self, oid: &str, update_working: bool) {
-        match self.get_tree(oid, &PathBuf::from("./")) {
+        match self.get_tree_in_base(oid) {
             Some(map) => {
                 match self.get_index() {
                     Ok(mut index) => {
@@ -128,6 +183,15 @@ impl Ugit {
     }
 
     pub fn commit(&self, message: &str) -> Result<String, DateErr> {
+        self.commit_with_author(message, self.signature("AUTHOR"))
+    }
+
+    /// 同 `commit`，但作者由调用者指定，`am` 用它保留补丁原来的作者
+    pub fn commit_with_author(&self, message: &str, author: Signature) -> Result<String, DateErr> {
+        if !self.check_unmerged("Committing") {
+            return Err(DateErr::Err(String::from("unmerged files")));
+        }
+
         let oid = self.write_tree()?;
 
         let mut commit = format!("tree {oid}\n");
@@ -136,26 +200,33 @@ impl Ugit {
             .filter(|head| !head.value.is_empty())
         {
             commit.push_str(&format!("parent {}\n", head.value));
-        } else {
-            commit.push('\n');
         }
 
         if let Some(head) = self
             .get_ref_recursive(data::MERGE_HEAD)
             .filter(|head| !head.value.is_empty())
         {
-            commit.push_str(&format!("parent {}\n", head.value));
+            //章鱼合并时每行一个要合并的提交
+            for parent in head.value.lines() {
+                commit.push_str(&format!("parent {parent}\n"));
+            }
             if let Err(err) = self.delete_ref(data::MERGE_HEAD, true) {
                 println!("commit, delete merge head error, err:{:?}", err);
             }
-        } else {
-            commit.push('\n');
+            if let Err(err) = self.delete_ref(data::MERGE_TREE, false) {
+                println!("commit, delete merge tree error, err:{:?}", err);
+            }
         }
-        commit.push_str(&format!("\n{message}\n"));
+        commit.push_str(&format!("author {author}\n"));
+        commit.push_str(&format!("committer {}\n", self.signature("COMMITTER")));
+        commit.push_str(&format!("\n{}\n", message.trim_end()));
 
         match self.hash(commit.as_bytes(), DataType::Commit) {
             Ok(oid) => {
                 self.update_ref(data::HEAD, RefValue::direct(oid.clone()), true);
+                if let Err(err) = self.write_merge_msg("") {
+                    println!("commit, delete merge message error, err:{:?}", err);
+                }
                 Ok(oid)
             }
             err @ Err(_) => err,
@@ -166,29 +237,22 @@ impl Ugit {
         let oid = oid.as_ref();
         match self.get_object(oid, DataType::Commit) {
             Ok(content) => {
-                const TREE_PREFIX: &str = "tree ";
-                const PARENT_PREFIX: &str = "parent ";
-                //前两行
-                //tree
-                //parent
-                //空格
-                //剩下的都是内容
-                let mut lines = content.lines();
-                let tree = lines
-                    .next()
-                    .and_then(|s| s.strip_prefix(TREE_PREFIX))
-                    .map(str::to_string);
-                let mut parents = vec![];
-                while let Some(s) = lines.next().and_then(|s| s.strip_prefix(PARENT_PREFIX)) {
-                    parents.push(s.to_string());
+                //头部和信息之间是一个空行，早期的提交格式在信息前还有多余的空行
+                let (header, message) = content.split_once("\n\n").unwrap_or((&content, ""));
+
+                let mut commit = Commit::default();
+                for line in header.lines() {
+                    match line.split_once(' ') {
+                        Some(("tree", tree)) => commit.tree = Some(tree.to_string()),
+                        Some(("parent", parent)) => commit.parents.push(parent.to_string()),
+                        Some(("author", author)) => commit.author = author.parse().ok(),
+                        Some(("committer", committer)) => commit.committer = committer.parse().ok(),
+                        _ => {}
+                    }
                 }
-                let message = lines.collect::<String>();
+                commit.message = Some(message.trim_start_matches('\n').trim_end().to_string());
 
-                Some(Commit {
-                    tree,
-                    parents,
-                    message: Some(message),
-                })
+                Some(commit)
             }
             Err(e) => {
                 eprintln!("get_commit err, oid:{:?}, err:{:?}", oid, e);
@@ -197,41 +261,272 @@ impl Ugit {
         }
     }
 
-    pub fn checkout<T: AsRef<str>>(&self, name: T) {
+    /// 作者或提交者，`role` 是 `AUTHOR` 或 `COMMITTER`，依次取 `RGIT_<role>_*` 环境变量、配置和登录名
+    pub fn signature(&self, role: &str) -> Signature {
+        let var = |name: &str| env::var(format!("RGIT_{role}_{name}")).ok();
+        let user = env::var("USER").unwrap_or_else(|_| String::from("unknown"));
+
+        let name = var("NAME")
+            .or_else(|| self.get_config("user.name"))
+            .unwrap_or_else(|| user.clone());
+        let email = var("EMAIL")
+            .or_else(|| self.get_config("user.email"))
+            .unwrap_or_else(|| format!("{user}@localhost"));
+        let (time, offset) = var("DATE")
+            .and_then(|date| date::parse_date(&date))
+            .unwrap_or((date::now(), 0));
+
+        Signature {
+            name,
+            email,
+            time,
+            offset,
+        }
+    }
+
+    pub fn checkout<T: AsRef<str>>(&self, name: T, force: bool) {
         let name = name.as_ref();
-        let oid = self.get_oid(name);
-        match self.get_commit(&oid) {
-            Some(Commit {
-                tree: Some(tree_id),
-                ..
-            }) => {
-                self.read_tree(&tree_id, true);
-
-                let ref_value = if self.is_branch(name) {
-                    RefValue {
-                        symbolic: true,
-                        value: format!("refs/heads/{name}"),
-                    }
-                } else {
-                    RefValue::direct(oid)
-                };
+        let target = self
+            .resolve_commit(name)
+            .ok()
+            .and_then(|oid| Some((self.get_commit_tree(&oid)?, oid)));
+        let (target, oid) = match target {
+            Some(target) => target,
+            None => {
+                eprintln!("checkout not exists commit, oid:{}", name);
+                return;
+            }
+        };
+
+        if !force && !self.check_unmerged("Checkout") {
+            return;
+        }
+
+        let head = self.get_head_tree();
+        if !force && !self.check_local_changes(&head, &target, false, "checkout") {
+            return;
+        }
+
+        if let Err(err) = self.switch_tree(&head, &target, force) {
+            eprintln!("checkout failed, err:{:?}", err);
+            return;
+        }
+
+        if force {
+            self.resolve_conflicts(|_| true);
+        }
+
+        let ref_value = if self.is_branch(name) {
+            RefValue {
+                symbolic: true,
+                value: format!("refs/heads/{name}"),
+            }
+        } else {
+            RefValue::direct(oid)
+        };
+
+        self.update_ref(data::HEAD, ref_value, false)
+    }
+
+    /// 读取提交对应的整棵树
+    pub fn get_commit_tree(&self, oid: &str) -> Option<HashMap<PathBuf, String>> {
+        self.get_commit(oid)
+            .and_then(|commit| commit.tree)
+            .and_then(|tree| self.get_tree_in_base(&tree))
+    }
+
+    /// HEAD 指向提交的树，还没有提交时为空
+    pub fn get_head_tree(&self) -> HashMap<PathBuf, String> {
+        self.get_ref_if_not_empty(data::HEAD)
+            .and_then(|head| self.get_commit_tree(&head.value))
+            .unwrap_or_default()
+    }
+
+    /// 找出从 `head` 切换到 `target` 会覆盖的本地修改和未跟踪文件，`discard` 时所有本地修改都算
+    pub fn find_local_changes(
+        &self,
+        head: &HashMap<PathBuf, String>,
+        target: &HashMap<PathBuf, String>,
+        discard: bool,
+    ) -> LocalChanges {
+        let index = self.get_index_tree();
+        let working = self.get_working_tree();
+
+        let mut paths = HashSet::new();
+        for tree in [head, target, &index, &working] {
+            paths.extend(tree.keys());
+        }
+
+        let mut changes = LocalChanges::default();
+        for path in paths {
+            let (h, t) = (head.get(path), target.get(path));
+            let (i, w) = (index.get(path), working.get(path));
 
-                self.update_ref(data::HEAD, ref_value, false)
+            //目标里是文件的路径，原来的目录和里面的文件都要删掉
+            let in_the_way = path.ancestors().skip(1).any(|dir| target.contains_key(dir));
+
+            if i.is_none() && h.is_none() {
+                if w.is_some() && (t.is_some() && w != t || in_the_way) {
+                    changes.untracked.push(path.clone());
+                }
+                continue;
+            }
+
+            let modified = i != h || w != i;
+            let already_target = i == t && w == t;
+            if modified && !already_target && (discard || h != t || in_the_way) {
+                changes.modified.push(path.clone());
             }
-            _ => eprintln!("checkout not exists commit, oid:{}", name),
         }
+
+        changes.modified.sort();
+        changes.untracked.sort();
+        changes
+    }
+
+    /// 打印会被覆盖的文件，没有时返回 true
+    pub fn check_local_changes(
+        &self,
+        head: &HashMap<PathBuf, String>,
+        target: &HashMap<PathBuf, String>,
+        discard: bool,
+        action: &str,
+    ) -> bool {
+        let changes = self.find_local_changes(head, target, discard);
+        if changes.is_empty() {
+            return true;
+        }
+
+        changes.report(action);
+        false
+    }
+
+    /// 还有没解决的冲突时打印出来并返回 false
+    pub fn check_unmerged(&self, action: &str) -> bool {
+        let mut unmerged = match self.get_conflicts() {
+            Ok(conflicts) => conflicts.into_keys().collect::<Vec<_>>(),
+            Err(err) => {
+                eprintln!("get_conflicts error:{:?}", err);
+                return false;
+            }
+        };
+
+        if unmerged.is_empty() {
+            return true;
+        }
+
+        unmerged.sort();
+        eprintln!("error: {action} is not possible because you have unmerged files.");
+        for path in unmerged {
+            eprintln!("\t{path}");
+        }
+        eprintln!("hint: Fix them up in the work tree, and then use 'rgit add <file>'");
+        eprintln!("hint: as appropriate to mark resolution.");
+        false
+    }
+
+    /// 把满足条件的路径标记为已解决，返回这些路径
+    pub fn resolve_conflicts(&self, resolved: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+        let mut conflicts = match self.get_conflicts() {
+            Ok(conflicts) => conflicts,
+            Err(err) => {
+                eprintln!("get_conflicts error:{:?}", err);
+                return vec![];
+            }
+        };
+
+        let paths = conflicts
+            .keys()
+            .map(PathBuf::from)
+            .filter(|path| resolved(path))
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            return paths;
+        }
+
+        for path in &paths {
+            conflicts.remove(path.to_string_lossy().as_ref());
+        }
+        if let Err(err) = self.write_conflicts(&conflicts) {
+            eprintln!("write_conflicts error:{:?}", err);
+        }
+
+        paths
+    }
+
+    /// 把索引和工作区从 `head` 切到 `target`，两边相同的路径保留本地修改，`force` 时全部重置
+    pub fn switch_tree(
+        &self,
+        head: &HashMap<PathBuf, String>,
+        target: &HashMap<PathBuf, String>,
+        force: bool,
+    ) -> Result<(), DateErr> {
+        let mut index = self.get_index()?;
+        let index_tree = if force {
+            index.clear();
+            self.get_index_tree()
+        } else {
+            HashMap::new()
+        };
+
+        let mut paths = HashSet::new();
+        paths.extend(head.keys());
+        paths.extend(target.keys());
+        paths.extend(index_tree.keys());
+
+        //先删除再写入，文件和目录互相替换时才不会冲突
+        let (writes, removes): (Vec<_>, Vec<_>) = paths
+            .into_iter()
+            .partition(|path| target.contains_key(*path));
+        let removes = removes
+            .into_iter()
+            .filter(|path| force || head.contains_key(*path))
+            .collect::<HashSet<_>>();
+        let writes = writes
+            .into_iter()
+            .filter(|path| force || head.get(*path) != Some(&target[*path]))
+            .collect::<Vec<_>>();
+
+        //挡路的目录里还有不会删除的文件时，只有 force 才覆盖，在改动任何东西之前检查
+        if !force {
+            for path in &writes {
+                if path.is_dir() && has_other_files(path, &removes)? {
+                    return Err(DateErr::Err(format!(
+                        "untracked working tree files in '{}' would be overwritten",
+                        path.display()
+                    )));
+                }
+            }
+        }
+
+        for path in &removes {
+            index.remove(path.to_string_lossy().as_ref());
+            remove_working_file(path)?;
+        }
+
+        for path in writes {
+            let oid = &target[path];
+            if path.is_dir() {
+                fs::remove_dir_all(path)?;
+            }
+            self.checkout_file(path, oid)?;
+            index.insert(path.to_string_lossy().to_string(), oid.clone());
+        }
+
+        self.write_index(&index)?;
+        Ok(())
     }
 
     pub fn create_tag(&self, oid: &str, tag: &str) {
         self.update_ref(
-            format!("refs/tags{tag}"),
+            format!("refs/tags/{tag}"),
             RefValue::direct(oid.to_string()),
             true,
         )
     }
 
     fn is_branch(&self, branch: &str) -> bool {
-        self.get_ref(&format!("refs/heads/{branch}"), true)
+        self.get_ref_if_not_empty(&format!("refs/heads/{branch}"))
             .is_some()
     }
 
@@ -285,10 +580,7 @@ impl Ugit {
     }
 
     pub fn get_branch_name(&self) -> Option<String> {
-        let ref_value = match self.get_ref(data::HEAD, false) {
-            Some(ref_value) => ref_value,
-            None => return None,
-        };
+        let ref_value = self.get_ref(data::HEAD, false)?;
 
         if !ref_value.symbolic {
             return None;
@@ -300,7 +592,57 @@ impl Ugit {
             .map(str::to_string)
     }
 
-    pub fn reset(&self, oid: String) {
+    pub fn reset(&self, oid: String, mode: ResetMode, force: bool) {
+        let target = match self.get_commit_tree(&oid) {
+            Some(target) => target,
+            None => {
+                eprintln!("reset not exists commit, oid:{}", oid);
+                return;
+            }
+        };
+
+        let head = self.get_head_tree();
+        let result = match mode {
+            ResetMode::Soft => Ok(()),
+            ResetMode::Mixed => self
+                .write_index(&tree_to_index(&target))
+                .map_err(DateErr::Io),
+            ResetMode::Hard => {
+                if !force && !self.check_local_changes(&head, &target, true, "reset") {
+                    return;
+                }
+                self.switch_tree(&head, &target, true)
+            }
+            ResetMode::Keep => {
+                if !self.check_local_changes(&head, &target, false, "reset") {
+                    return;
+                }
+                self.switch_tree(&head, &target, false)
+            }
+        };
+
+        if let Err(err) = result {
+            eprintln!("reset failed, err:{:?}", err);
+            return;
+        }
+
+        if let Some(orig_head) = self.get_ref_if_not_empty(data::HEAD) {
+            self.update_ref(data::ORIG_HEAD, orig_head, false);
+        }
+
+        if !matches!(mode, ResetMode::Soft) {
+            if let Err(err) = self.delete_ref(data::MERGE_HEAD, false) {
+                eprintln!("reset, delete merge head error, err:{:?}", err);
+            }
+            if let Err(err) = self.delete_ref(data::MERGE_TREE, false) {
+                eprintln!("reset, delete merge tree error, err:{:?}", err);
+            }
+            if let Err(err) = self.write_merge_msg("") {
+                eprintln!("reset, delete merge message error, err:{:?}", err);
+            }
+            self.resolve_conflicts(|_| true);
+        }
+
         self.update_ref(
             data::HEAD,
             RefValue {
@@ -311,6 +653,41 @@ impl Ugit {
         );
     }
 
+    /// 把指定路径在索引中的内容恢复成提交里的版本，不移动 HEAD
+    pub fn reset_paths(&self, oid: &str, paths: &[String]) {
+        let target = match self.get_commit_tree(oid) {
+            Some(target) => target,
+            None if oid == data::HEAD => HashMap::new(),
+            None => {
+                eprintln!("reset not exists commit, oid:{}", oid);
+                return;
+            }
+        };
+
+        let mut index = match self.get_index() {
+            Ok(index) => index,
+            Err(err) => {
+                eprintln!("get_index error:{:?}", err);
+                return;
+            }
+        };
+
+        let specs = paths.iter().map(normalize_path).collect::<Vec<_>>();
+        let in_specs = |path: &Path| specs.iter().any(|spec| path.starts_with(spec));
+
+        index.retain(|path, _| !in_specs(Path::new(path)));
+        for (path, oid) in &target {
+            if in_specs(path) {
+                index.insert(path.to_string_lossy().to_string(), oid.clone());
+            }
+        }
+
+        if let Err(err) = self.write_index(&index) {
+            eprintln!("reset, update index error:{:?}", err);
+        }
+        self.resolve_conflicts(in_specs);
+    }
+
     pub fn get_working_tree(&self) -> HashMap<PathBuf, String> {
         let base = PathBuf::from(".");
         let read_dir = match PathBuf::from(".").read_dir() {
@@ -351,51 +728,33 @@ impl Ugit {
         entires
     }
 
-    fn read_tree_merged(
+    /// 把合并结果写进索引和工作区，有会被覆盖的本地修改时返回 false
+    fn write_merged(
         &self,
-        t_base: Option<String>,
-        t_head: &str,
-        t_other: &str,
-        update_working: bool,
-    ) -> Result<(), DateErr> {
-        let t_base_tree = match t_base.and_then(|t_base| self.get_tree_in_base(&t_base)) {
-            Some(tree) => tree,
-            None => HashMap::new(),
-        };
-
-        //TODO check is there un commit changes?
-        let t_head_tree = match self.get_tree_in_base(t_head) {
-            Some(tree) => tree,
-            None => return Err(DateErr::TreeNotExists(String::from(t_head))),
-        };
-
-        let t_other_tree = match self.get_tree_in_base(t_other) {
-            Some(tree) => tree,
-            None => return Err(DateErr::TreeNotExists(String::from(t_other))),
-        };
-
-        let merge_tress = match self.merge_tress(&t_base_tree, &t_head_tree, &t_other_tree) {
-            Ok(merged_tree) => merged_tree,
-            Err(err) => return Err(err),
-        };
-
-        let mut index = self.get_index()?;
-
-        index.clear();
-        for (path, oid) in merge_tress {
-            index.insert(path.to_string_lossy().to_string(), oid);
+        t_head_tree: &HashMap<PathBuf, String>,
+        merged: &MergedTree,
+        force: bool,
+    ) -> Result<bool, DateErr> {
+        if !force && !self.check_local_changes(t_head_tree, &merged.tree, false, "merge") {
+            return Ok(false);
         }
 
-        self.write_index(&index)?;
+        self.switch_tree(t_head_tree, &merged.tree, force)?;
+        self.write_conflicts(
+            &merged
+                .conflicts
+                .iter()
+                .map(|(path, stages)| (path.to_string_lossy().to_string(), stages.clone()))
+                .collect(),
+        )?;
+        Ok(true)
+    }
 
-        if update_working {
-            self.checkout_index(&index)?;
+    pub fn merge(&self, names: &[String], flags: &MergeFlags) {
+        if !self.check_unmerged("Merging") {
+            return;
         }
 
-        Ok(())
-    }
-
-    pub fn merge(&self, other: &str) {
         let head = match self
             .get_ref_recursive(data::HEAD)
             .filter(|refvalue| !refvalue.value.is_empty())
@@ -408,69 +767,329 @@ impl Ugit {
             }
         };
 
-        let other = self.get_oid(other);
-        let merge_base = self.get_merge_base(&head, &other);
+        let mut others = vec![];
+        for name in names {
+            let oid = match self.resolve_commit(name) {
+                Ok(oid) => oid,
+                Err(_) => {
+                    eprintln!("merge failed, commit not exists:{:?}", name);
+                    return;
+                }
+            };
+            if !self.is_ancestor_of(&head, &oid) {
+                others.push(MergeHead {
+                    name: name.clone(),
+                    oid,
+                });
+            }
+        }
 
-        let c_other = match self.get_commit(&other).and_then(|commit| commit.tree) {
-            Some(c_other) => c_other,
-            None => {
-                eprintln!("merge failed, commit not exists:{:?}", other);
+        if others.is_empty() {
+            println!("Already up to date.");
+            return;
+        }
+
+        let fast_forward =
+            matches!(others.as_slice(), [other] if self.is_ancestor_of(&other.oid, &head));
+        if !fast_forward && flags.fast_forward == FastForward::Only {
+            eprintln!("fatal: Not possible to fast-forward, aborting.");
+            return;
+        }
+
+        let mut options = MergeOptions {
+            style: flags.style,
+            ours_label: String::from(data::HEAD),
+            base_label: String::from("merged common ancestors"),
+            theirs_label: others[0].name.clone(),
+            ..Default::default()
+        };
+        for option in &flags.strategy_options {
+            match option {
+                StrategyOption::Ours => options.favor = Some(Favor::Ours),
+                StrategyOption::Theirs => options.favor = Some(Favor::Theirs),
+                StrategyOption::IgnoreSpaceChange => options.whitespace.ignore_space_change = true,
+                StrategyOption::IgnoreAllSpace => options.whitespace.ignore_all_space = true,
+                StrategyOption::IgnoreSpaceAtEol => options.whitespace.ignore_space_at_eol = true,
+                StrategyOption::Subtree(_) => {}
+            }
+        }
+
+        let head_tree = self.get_head_tree();
+        let oids = others
+            .iter()
+            .map(|other| other.oid.clone())
+            .collect::<Vec<_>>();
+
+        if fast_forward && flags.fast_forward != FastForward::Never {
+            let other = &others[0].oid;
+            let other_tree = match self.get_commit_tree(other) {
+                Some(other_tree) => other_tree,
+                None => {
+                    eprintln!("merge failed, tree not exists");
+                    return;
+                }
+            };
+
+            if !flags.force && !self.check_local_changes(&head_tree, &other_tree, false, "merge") {
                 return;
             }
-        };
 
-        if merge_base
-            .as_ref()
-            .filter(|merge_base| *merge_base == &head)
-            .is_some()
-        {
-            self.read_tree(&c_other, true);
+            if let Err(err) = self.switch_tree(&head_tree, &other_tree, flags.force) {
+                eprintln!("merge failed err:{:?}", err);
+                return;
+            }
+            //拒绝合并时不能改动 ORIG_HEAD，所以等工作区切换完再记录
+            self.update_ref(data::ORIG_HEAD, RefValue::direct(head.clone()), false);
+
+            if flags.squash {
+                self.write_squash_msg(&head, &oids);
+                println!("Squash commit -- not updating HEAD");
+                return;
+            }
             self.update_ref(data::HEAD, RefValue::direct(other.to_string()), true);
             println!("Fast-forward merge, no need to commit");
             return;
         }
 
-        let c_head = match self.get_commit(&head).and_then(|commit| commit.tree) {
-            Some(c_head) => c_head,
-            None => {
-                eprintln!("merge failed, commit not exists:{:?}", data::HEAD);
+        let strategy = flags.strategy.unwrap_or(match others.len() {
+            1 => Strategy::Recursive,
+            _ => Strategy::Octopus,
+        });
+        let merged = match strategy
+            .build(&flags.strategy_options)
+            .merge(self, &head, &others, &options)
+        {
+            Ok(merged) => merged,
+            Err(err) => {
+                eprintln!("merge failed err:{:?}", err);
                 return;
             }
         };
 
-        let merge_base = merge_base
-            .and_then(|str| self.get_commit(str))
-            .and_then(|base_commit| base_commit.tree);
+        match self.write_merged(&head_tree, &merged, flags.force) {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(err) => {
+                eprintln!("merge failed err:{:?}", err);
+                return;
+            }
+        }
+        self.update_ref(data::ORIG_HEAD, RefValue::direct(head.clone()), false);
 
-        self.update_ref(data::MERGE_HEAD, RefValue::direct(other.to_string()), true);
+        if flags.squash {
+            self.write_squash_msg(&head, &oids);
+        } else {
+            self.update_ref(data::MERGE_HEAD, RefValue::direct(oids.join("\n")), true);
+            match self.write_tree_of(&tree_to_index(&merged.tree)) {
+                Ok(tree) => self.update_ref(data::MERGE_TREE, RefValue::direct(tree), false),
+                Err(err) => eprintln!("merge, write merge tree error, err:{:?}", err),
+            }
+            if let Err(err) = self.write_merge_msg(&self.merge_message(&others)) {
+                eprintln!("merge, write merge message error, err:{:?}", err);
+            }
+        }
 
-        if let Err(err) = self.read_tree_merged(merge_base, &c_head, &c_other, true) {
-            eprintln!("merge failed err:{:?}", err);
+        if !merged.conflicts.is_empty() {
+            println!("Automatic merge failed; fix conflicts and then commit the result.");
+        } else if flags.squash {
+            println!("Squash commit -- not updating HEAD");
         } else {
-            self.update_ref(
-                data::MERGE_HEAD,
-                RefValue {
-                    symbolic: false,
-                    value: other.to_string(),
-                },
-                true,
-            );
             println!("Merged in working tree\nPlease commit");
         }
     }
 
+    /// 默认的合并提交信息，如 `Merge branches 'a' and 'b'`
+    fn merge_message(&self, others: &[MergeHead]) -> String {
+        let (branches, commits): (Vec<_>, Vec<_>) =
+            others.iter().partition(|other| self.is_branch(&other.name));
+
+        let describe = |kind: &str, heads: Vec<&MergeHead>| {
+            let names = heads
+                .iter()
+                .map(|head| format!("'{}'", head.name))
+                .collect::<Vec<_>>();
+            match names.as_slice() {
+                [] => None,
+                [name] => Some(format!("{kind} {name}")),
+                [init @ .., last] => Some(format!("{kind}es {} and {last}", init.join(", "))),
+            }
+        };
+
+        let parts = [describe("branch", branches), describe("commit", commits)]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        format!("Merge {}\n", parts.join(", "))
+    }
+
+    /// `merge --squash` 不记录 MERGE_HEAD，提交信息列出被合并进来的提交
+    fn write_squash_msg(&self, head: &str, others: &[String]) {
+        let merged = self
+            .iter_commits_and_parents(vec![head.to_string()])
+            .into_iter()
+            .collect::<HashSet<_>>();
+
+        let mut message = String::from("Squashed commit of the following:\n");
+        for oid in self.iter_commits_and_parents(others.to_vec()) {
+            if merged.contains(&oid) {
+                continue;
+            }
+            if let Some(commit) = self.get_commit(&oid) {
+                message.push_str(&format!("\ncommit {oid}\n\n"));
+                for line in commit.message.unwrap_or_default().lines() {
+                    message.push_str(&format!("    {line}\n"));
+                }
+            }
+        }
+
+        if let Err(err) = self.write_merge_msg(&message) {
+            eprintln!("merge, write merge message error, err:{:?}", err);
+        }
+    }
+
+    /// 放弃正在进行的合并，只把合并改过的路径恢复成合并前的样子
+    pub fn merge_abort(&self) {
+        if self.get_ref_if_not_empty(data::MERGE_HEAD).is_none() {
+            eprintln!("fatal: There is no merge to abort (MERGE_HEAD missing).");
+            return;
+        }
+
+        let orig = match self.get_ref_if_not_empty(data::ORIG_HEAD) {
+            Some(orig_head) => self.get_commit_tree(&orig_head.value).unwrap_or_default(),
+            None => {
+                eprintln!("merge --abort failed, ORIG_HEAD missing");
+                return;
+            }
+        };
+        let merged = match self.get_ref_if_not_empty(data::MERGE_TREE) {
+            Some(tree) => self.get_tree_in_base(&tree.value).unwrap_or_default(),
+            None => {
+                eprintln!("merge --abort failed, MERGE_TREE missing");
+                return;
+            }
+        };
+
+        //冲突的文件本来就是合并写的，其余改过的路径有自己的修改时不能丢掉
+        let conflicts = self.get_conflicts().unwrap_or_default();
+        let mut changes = self.find_local_changes(&merged, &orig, false);
+        changes
+            .modified
+            .retain(|path| !conflicts.contains_key(path.to_string_lossy().as_ref()));
+        if !changes.is_empty() {
+            changes.report("merge --abort");
+            return;
+        }
+
+        if let Err(err) = self.switch_tree(&merged, &orig, false) {
+            eprintln!("merge --abort failed, err:{:?}", err);
+            return;
+        }
+
+        for ref_str in [data::MERGE_HEAD, data::MERGE_TREE] {
+            if let Err(err) = self.delete_ref(ref_str, false) {
+                eprintln!("merge --abort, delete {ref_str} error, err:{:?}", err);
+            }
+        }
+        if let Err(err) = self.write_merge_msg("") {
+            eprintln!("merge --abort, delete merge message error, err:{:?}", err);
+        }
+        self.resolve_conflicts(|_| true);
+    }
+
+    /// 解决冲突后用准备好的信息提交合并
+    pub fn merge_continue(&self) -> Result<String, DateErr> {
+        if self.get_ref_if_not_empty(data::MERGE_HEAD).is_none() {
+            return Err(DateErr::Err(String::from(
+                "There is no merge in progress (MERGE_HEAD missing).",
+            )));
+        }
+
+        let message = self.get_merge_msg().unwrap_or_default();
+        self.commit(&message)
+    }
+
     pub fn get_merge_base(&self, oid1: &str, oid2: &str) -> Option<String> {
-        let parents1: HashSet<String> =
-            HashSet::from_iter(self.iter_commits_and_parents(vec![oid1.to_string()]));
+        self.get_merge_bases(oid1, &[oid2.to_string()])
+            .into_iter()
+            .next()
+    }
+
+    /// `one` 和假想的 `others` 合并提交之间所有最好的共同祖先
+    pub fn get_merge_bases(&self, one: &str, others: &[String]) -> Vec<String> {
+        let reachable = self
+            .iter_commits_and_parents(others.to_vec())
+            .into_iter()
+            .collect::<HashSet<_>>();
+
+        let common = self
+            .iter_commits_and_parents(vec![one.to_string()])
+            .into_iter()
+            .filter(|oid| reachable.contains(oid))
+            .collect();
+        self.best_common_ancestors(common)
+    }
+
+    /// 所有提交共同的最好的祖先
+    pub fn get_octopus_merge_bases(&self, oids: &[String]) -> Vec<String> {
+        let mut oids = oids.iter();
+        let mut common = match oids.next() {
+            Some(first) => self.iter_commits_and_parents(vec![first.clone()]),
+            None => return vec![],
+        };
+
+        for oid in oids {
+            let reachable = self
+                .iter_commits_and_parents(vec![oid.clone()])
+                .into_iter()
+                .collect::<HashSet<_>>();
+            common.retain(|oid| reachable.contains(oid));
+        }
+        self.best_common_ancestors(common)
+    }
+
+    /// 共同祖先的祖先也都是共同祖先，去掉能从其它共同祖先的父提交到达的那些
+    fn best_common_ancestors(&self, common: Vec<String>) -> Vec<String> {
+        let parents = common
+            .iter()
+            .filter_map(|oid| self.get_commit(oid))
+            .flat_map(|commit| commit.parents)
+            .collect::<Vec<_>>();
+        let redundant = self
+            .iter_commits_and_parents(parents)
+            .into_iter()
+            .collect::<HashSet<_>>();
+
+        common
+            .into_iter()
+            .filter(|oid| !redundant.contains(oid))
+            .collect()
+    }
 
-        self.iter_commits_and_parents(vec![oid2.to_string()])
+    /// `commit` 从 `refname` 分出来的位置，按 reflog 从新到旧找第一个是 `commit` 祖先的值
+    pub fn get_fork_point(&self, refname: &str, commit: &str) -> Option<String> {
+        let refname = [
+            refname.to_string(),
+            format!("refs/{refname}"),
+            format!("refs/heads/{refname}"),
+            format!("refs/remotes/{refname}"),
+        ]
+        .into_iter()
+        .find(|name| self.get_ref_if_not_empty(name).is_some())?;
+
+        let mut candidates = vec![self.get_ref_if_not_empty(&refname)?.value];
+        candidates.extend(self.get_reflog(&refname).into_iter().map(|entry| entry.new));
+
+        let ancestors = self
+            .iter_commits_and_parents(vec![commit.to_string()])
             .into_iter()
-            .find(|oid| parents1.contains(oid))
+            .collect::<HashSet<_>>();
+        candidates
+            .into_iter()
+            .find(|candidate| ancestors.contains(candidate))
     }
 
-    fn build_index_tree_recursive(&self) -> Result<HashMap<String, Node>, Error> {
+    fn build_index_tree_recursive(index: &HashMap<String, String>) -> HashMap<String, Node> {
         let mut index_as_tree = Node::Dir(HashMap::new());
-        let index = self.get_index()?;
 
         for (path, oid) in index {
             let path = PathBuf::from(path);
@@ -504,12 +1123,12 @@ impl Ugit {
             }
 
             if let Node::Dir(map) = current_path {
-                map.insert(file_name.to_string(), Node::File(oid));
+                map.insert(file_name.to_string(), Node::File(oid.clone()));
             }
         }
 
         match index_as_tree {
-            Node::Dir(map) => Ok(map),
+            Node::Dir(map) => map,
             _ => unreachable!(),
         }
     }
@@ -541,73 +1160,15 @@ impl Ugit {
     }
 
     pub fn write_tree(&self) -> Result<String, DateErr> {
-        let tree_dict = match self.build_index_tree_recursive() {
-            Ok(tree_dict) => tree_dict,
-            Err(err) => return Err(DateErr::Io(err)),
-        };
-        self.write_tree_recursive(&tree_dict)
-    }
-
-    pub fn iter_objects_in_commits(&self, oids: Vec<String>) -> HashSet<String> {
-        self.iter_objects_in_commits_fetch(oids, &|_| {})
-    }
-
-    pub fn iter_objects_in_commits_fetch(
-        &self,
-        oids: Vec<String>,
-        fetch: &impl Fn(&str),
-    ) -> HashSet<String> {
-        let mut visited = HashSet::new();
-
-        for oid in self.iter_commits_and_parents_with_fectch(oids, fetch) {
-            fetch(&oid);
-            if let Some(tree) = self.get_commit(&oid).and_then(|commit| commit.tree) {
-                if !visited.contains(tree.as_str()) {
-                    self.iter_objects_in_tree_with_fetch(&tree, &mut visited, fetch);
-                }
-            }
-
-            visited.insert(oid);
-        }
-
-        visited
+        self.write_tree_of(&self.get_index()?)
     }
 
-    fn iter_objects_in_tree_with_fetch(
-        &self,
-        oid: &str,
-        visited: &mut HashSet<String>,
-        fetch: &impl Fn(&str),
-    ) {
-        visited.insert(oid.to_string());
-
-        fetch(oid);
-
-        match self.iter_tree_entires(oid) {
-            Ok(entries) => {
-                for (data_type, oid, _) in entries {
-                    if visited.contains(&oid) {
-                        continue;
-                    }
-
-                    match data_type {
-                        DataType::Tree => {
-                            self.iter_objects_in_tree_with_fetch(oid.as_str(), visited, fetch);
-                        }
-                        _ => {
-                            visited.insert(oid.clone());
-                            fetch(&oid);
-                        }
-                    }
-                }
-            }
-            Err(err) => {
-                eprintln!("iter tree entries err:{:?}, oid:{:?}", err, oid);
-            }
-        }
+    /// 把索引形式的文件列表写成树对象
+    pub fn write_tree_of(&self, index: &HashMap<String, String>) -> Result<String, DateErr> {
+        self.write_tree_recursive(&Self::build_index_tree_recursive(index))
     }
 
-    fn iter_tree_entires<T: AsRef<str>>(
+    pub(crate) fn iter_tree_entires<T: AsRef<str>>(
         &self,
         oid: T,
     ) -> Result<Vec<(DataType, String, String)>, DateErr> {
@@ -641,68 +1202,205 @@ impl Ugit {
             .contains(&maybe_ancesotr.to_string())
     }
 
-    fn add_directory(&self, dir: &str, map: &mut HashMap<String, String>) {
-        let read_dir: fs::ReadDir = match PathBuf::from(dir).read_dir() {
-            Ok(read_dir) => read_dir,
+    pub fn add(&self, filenames: &[String], options: &AddOptions) {
+        if filenames.is_empty() && !options.update && !options.all {
+            println!("Nothing specified, nothing added.");
+            return;
+        }
+
+        let mut index = match self.get_index() {
+            Ok(index) => index,
             Err(err) => {
-                eprintln!("add_directory:{:?} read_dir_err:{:?}", dir, err);
+                eprintln!("get_index error:{:?}", err);
                 return;
             }
         };
 
-        let mut dirs = LinkedList::new();
-        dirs.push_back(read_dir);
-        while let Some(read_dir) = dirs.pop_front() {
-            for path in read_dir.filter_map(Result::ok) {
-                let path = path.path();
-                if is_ignored(&path) {
-                    continue;
+        let pathspec = Pathspec::new(filenames);
+        let index_tree = self.get_index_tree();
+        let working_tree = self.get_working_tree();
+
+        for pattern in pathspec.includes() {
+            let spec = Pathspec::new(&[pattern]);
+            if !index_tree
+                .keys()
+                .chain(working_tree.keys())
+                .any(|path| spec.matches(path))
+            {
+                eprintln!("fatal: pathspec '{pattern}' did not match any files");
+                return;
+            }
+        }
+
+        let mut changes = diff::iter_changed_files(&index_tree, &working_tree)
+            .into_iter()
+            .filter(|(path, _)| pathspec.matches(path))
+            .collect::<Vec<_>>();
+        changes.sort();
+
+        for (path, action) in changes {
+            let key = path.to_string_lossy().to_string();
+            match working_tree.get(&path) {
+                Some(oid) => {
+                    if options.update && action == "new file" {
+                        continue;
+                    }
+                    if options.dry_run {
+                        println!("add '{key}'");
+                    }
+                    index.insert(key, oid.clone());
+                }
+                None => {
+                    if options.dry_run {
+                        println!("remove '{key}'");
+                    }
+                    index.remove(&key);
                 }
+            }
+        }
 
-                if path.is_file() {
-                    self.add_file(&path.to_string_lossy(), map);
-                } else if let Ok(dir) = path.read_dir() {
-                    dirs.push_back(dir);
+        //冲突文件即使内容没变，add 之后也算解决了
+        let mut conflicts = self
+            .get_conflicts()
+            .unwrap_or_default()
+            .into_keys()
+            .map(PathBuf::from)
+            .filter(|path| pathspec.matches(path))
+            .collect::<Vec<_>>();
+        conflicts.sort();
+        for path in &conflicts {
+            let key = path.to_string_lossy().to_string();
+            match working_tree.get(path) {
+                Some(oid) => {
+                    index.insert(key, oid.clone());
+                }
+                None => {
+                    index.remove(&key);
                 }
             }
         }
+
+        if options.dry_run {
+            return;
+        }
+
+        if let Err(err) = self.write_index(&index) {
+            eprintln!("add file, update index error:{:?}", err);
+        }
+        self.resolve_conflicts(|path| pathspec.matches(path));
     }
 
-    fn add_file(&self, filename: &str, map: &mut HashMap<String, String>) {
-        let cur_dir = match env::current_dir() {
-            Ok(cur_dir) => cur_dir,
+    pub fn rm(&self, paths: &[String], cached: bool, recursive: bool, force: bool) {
+        let mut index = match self.get_index() {
+            Ok(index) => index,
             Err(err) => {
-                eprintln!("add file, get cur_dir error:{:?}", err);
+                eprintln!("get_index error:{:?}", err);
                 return;
             }
         };
 
-        let filename = match PathBuf::from(filename).canonicalize() {
-            Ok(path) => match path.strip_prefix(cur_dir) {
-                Ok(filename) => filename.to_path_buf(),
-                Err(err) => {
-                    eprintln!("get relative path error:{:?}", err);
-                    return;
-                }
-            },
-            Err(err) => {
-                eprintln!("add_file, canonicalize error:{:?}", err);
+        let mut matched = vec![];
+        for spec in paths.iter().map(normalize_path) {
+            let mut found = index
+                .keys()
+                .map(PathBuf::from)
+                .filter(|path| path.starts_with(&spec))
+                .collect::<Vec<_>>();
+
+            if found.is_empty() {
+                eprintln!(
+                    "fatal: pathspec '{}' did not match any files",
+                    spec.display()
+                );
                 return;
             }
-        };
 
-        match self.hash_object(&filename) {
-            Ok(oid) => {
-                let filename = filename.to_string_lossy();
-                map.insert(filename.to_string(), oid);
+            if !recursive && found.iter().any(|path| path != &spec) {
+                eprintln!(
+                    "fatal: not removing '{}' recursively without -r",
+                    spec.display()
+                );
+                return;
             }
-            Err(data_type) => {
-                eprintln!("add file:{:?} error:{:?}", filename, data_type);
+
+            matched.append(&mut found);
+        }
+        matched.sort();
+        matched.dedup();
+
+        if !force {
+            let head = self.get_head_tree();
+            let conflicts = self.get_conflicts().unwrap_or_default();
+            let mut staged = vec![];
+            let mut modified = vec![];
+            let mut both = vec![];
+            for path in &matched {
+                //删除冲突文件也是一种解决方式，不需要检查
+                if conflicts.contains_key(path.to_string_lossy().as_ref()) {
+                    continue;
+                }
+
+                let h = head.get(path);
+                let i = index.get(path.to_string_lossy().as_ref());
+                let w = if path.is_file() {
+                    self.hash_object(path).ok()
+                } else {
+                    None
+                };
+                let w = w.as_ref();
+
+                if i != h && w.is_some() && w != i {
+                    both.push(path);
+                } else if !cached && i != h {
+                    staged.push(path);
+                } else if !cached && w.is_some() && w != i {
+                    modified.push(path);
+                }
             }
+
+            let mut refused = false;
+            for (paths, reason) in [
+                (
+                    both,
+                    "staged content different from both the file and the HEAD",
+                ),
+                (staged, "changes staged in the index"),
+                (modified, "local modifications"),
+            ] {
+                if paths.is_empty() {
+                    continue;
+                }
+
+                refused = true;
+                eprintln!("error: the following files have {reason}:");
+                for path in paths {
+                    eprintln!("    {}", path.display());
+                }
+            }
+
+            if refused {
+                eprintln!("(use --cached to keep the file, or -f to force removal)");
+                return;
+            }
+        }
+
+        for path in &matched {
+            index.remove(path.to_string_lossy().as_ref());
+            if !cached {
+                if let Err(err) = remove_working_file(path) {
+                    eprintln!("rm, remove file:{:?} error:{:?}", path, err);
+                }
+            }
+            println!("rm '{}'", path.display());
+        }
+
+        if let Err(err) = self.write_index(&index) {
+            eprintln!("rm, update index error:{:?}", err);
         }
+        self.resolve_conflicts(|path| matched.iter().any(|matched| matched == path));
     }
 
-    pub fn add(&self, filenames: &[String]) {
+    pub fn mv(&self, sources: &[String], destination: &str, force: bool) {
         let mut index = match self.get_index() {
             Ok(index) => index,
             Err(err) => {
@@ -711,35 +1409,235 @@ impl Ugit {
             }
         };
 
-        for filename in filenames {
-            let path = PathBuf::from(filename);
-            if path.is_file() {
-                self.add_file(filename, &mut index);
+        let destination = normalize_path(destination);
+        let into_dir = destination.is_dir();
+        if sources.len() > 1 && !into_dir {
+            eprintln!(
+                "fatal: destination '{}' is not a directory",
+                destination.display()
+            );
+            return;
+        }
+
+        let mut moves = vec![];
+        for source in sources.iter().map(normalize_path) {
+            let target = match source.file_name() {
+                Some(name) if into_dir => destination.join(name),
+                _ => destination.clone(),
+            };
+
+            let entries = index
+                .keys()
+                .map(PathBuf::from)
+                .filter(|path| path.starts_with(&source))
+                .collect::<Vec<_>>();
+
+            let error = if !source.exists() {
+                Some("bad source")
+            } else if entries.is_empty() {
+                Some("not under version control")
+            } else if target.starts_with(&source) {
+                Some("can not move directory into itself")
+            } else if target.exists() && (!force || source.is_dir() || target.is_dir()) {
+                Some("destination exists")
             } else {
-                self.add_directory(filename, &mut index);
+                None
+            };
+
+            if let Some(error) = error {
+                eprintln!(
+                    "fatal: {error}, source={}, destination={}",
+                    source.display(),
+                    target.display()
+                );
+                return;
+            }
+
+            moves.push((source, target, entries));
+        }
+
+        for (source, target, entries) in moves {
+            if let Some(parent) = target.parent().filter(|p| !p.as_os_str().is_empty()) {
+                if let Err(err) = fs::create_dir_all(parent) {
+                    eprintln!("mv, create dir:{:?} error:{:?}", parent, err);
+                    return;
+                }
+            }
+
+            if let Err(err) = fs::rename(&source, &target) {
+                eprintln!("mv, rename {:?} to {:?} error:{:?}", source, target, err);
+                return;
+            }
+
+            for entry in entries {
+                if let Some(oid) = index.remove(entry.to_string_lossy().as_ref()) {
+                    let moved = match entry.strip_prefix(&source) {
+                        Ok(rest) if !rest.as_os_str().is_empty() => target.join(rest),
+                        _ => target.clone(),
+                    };
+                    index.insert(moved.to_string_lossy().to_string(), oid);
+                }
             }
         }
 
         if let Err(err) = self.write_index(&index) {
-            eprintln!("add file, update index error:{:?}", err);
+            eprintln!("mv, update index error:{:?}", err);
         }
     }
 
     fn checkout_index(&self, index: &HashMap<String, String>) -> Result<(), DateErr> {
         for (path, oid) in index {
-            let pathbuf = PathBuf::from(path);
-            if let Some(pathbuf) = pathbuf.parent() {
-                fs::create_dir_all(pathbuf)?;
-            }
+            self.checkout_file(Path::new(path), oid)?;
+        }
 
-            let object = self.get_object(oid, DataType::Blob)?;
-            fs::write(path, object)?;
+        Ok(())
+    }
+
+    fn checkout_file(&self, path: &Path, oid: &str) -> Result<(), DateErr> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
         }
 
+        let object = self.get_object_bytes(oid, DataType::Blob)?;
+        fs::write(path, object)?;
         Ok(())
     }
 }
 
+/// `add` 的选项
+#[derive(Debug, Default)]
+pub struct AddOptions {
+    /// 只更新已跟踪的文件，包括删除
+    pub update: bool,
+    /// 没有指定路径时作用于整个工作区
+    pub all: bool,
+    /// 只打印会做的修改
+    pub dry_run: bool,
+}
+
+/// `merge` 的选项
+#[derive(Debug, Default)]
+pub struct MergeFlags {
+    /// 覆盖合并涉及到的本地修改
+    pub force: bool,
+    pub style: ConflictStyle,
+    pub fast_forward: FastForward,
+    /// 只把合并结果放进索引和工作区，不记录 MERGE_HEAD
+    pub squash: bool,
+    /// 不指定时合并一个分支用 recursive，多个用 octopus
+    pub strategy: Option<Strategy>,
+    pub strategy_options: Vec<StrategyOption>,
+}
+
+/// 能快进时怎么处理
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FastForward {
+    /// 能快进就快进，否则生成合并提交
+    #[default]
+    Allow,
+    /// 总是生成合并提交
+    Never,
+    /// 只允许快进
+    Only,
+}
+
+/// `reset` 时除了 HEAD 之外还要更新哪些内容
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResetMode {
+    /// 只移动 HEAD
+    Soft,
+    /// 移动 HEAD 并重置索引
+    Mixed,
+    /// 移动 HEAD，重置索引和工作区
+    Hard,
+    /// 同 Hard，但保留与目标提交不冲突的本地修改
+    Keep,
+}
+
+/// 工作区中会被覆盖的本地修改
+#[derive(Debug, Default)]
+pub struct LocalChanges {
+    pub modified: Vec<PathBuf>,
+    pub untracked: Vec<PathBuf>,
+}
+
+impl LocalChanges {
+    pub fn is_empty(&self) -> bool {
+        self.modified.is_empty() && self.untracked.is_empty()
+    }
+
+    pub fn report(&self, action: &str) {
+        if !self.modified.is_empty() {
+            eprintln!(
+                "error: Your local changes to the following files would be overwritten by {action}:"
+            );
+            for path in &self.modified {
+                eprintln!("\t{}", path.display());
+            }
+            eprintln!("Please commit your changes before you {action}.");
+        }
+
+        if !self.untracked.is_empty() {
+            eprintln!(
+                "error: The following untracked working tree files would be overwritten by {action}:"
+            );
+            for path in &self.untracked {
+                eprintln!("\t{}", path.display());
+            }
+            eprintln!("Please move or remove them before you {action}.");
+        }
+
+        eprintln!("Aborting, use --force to override");
+    }
+}
+
+fn tree_to_index(tree: &HashMap<PathBuf, String>) -> HashMap<String, String> {
+    tree.iter()
+        .map(|(path, oid)| (path.to_string_lossy().to_string(), oid.clone()))
+        .collect()
+}
+
+/// 把命令行传入的路径转成相对仓库根目录的形式，`.` 表示全部
+pub fn normalize_path<T: AsRef<str>>(path: T) -> PathBuf {
+    PathBuf::from(path.as_ref())
+        .components()
+        .filter(|component| !matches!(component, std::path::Component::CurDir))
+        .collect()
+}
+
+/// 删除文件，并清理随之变空的目录
+/// 目录里是否有 `removed` 之外的文件
+fn has_other_files(dir: &Path, removed: &HashSet<&PathBuf>) -> Result<bool, Error> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let other = match path.is_dir() {
+            true => has_other_files(&path, removed)?,
+            false => !removed.contains(&path),
+        };
+        if other {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+pub(crate) fn remove_working_file(path: &Path) -> Result<(), Error> {
+    if path.is_file() {
+        fs::remove_file(path)?;
+    }
+
+    let mut parent = path.parent();
+    while let Some(dir) = parent.filter(|dir| !dir.as_os_str().is_empty()) {
+        if fs::remove_dir(dir).is_err() {
+            break;
+        }
+        parent = dir.parent();
+    }
+
+    Ok(())
+}
+
 fn is_ignored(path: &Path) -> bool {
     //TODO ignore
     for component in path.iter() {
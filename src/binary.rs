@@ -0,0 +1,503 @@
+/// 和 git 一样只检查开头这么多字节里有没有 NUL
+const FIRST_FEW_BYTES: usize = 8000;
+
+/// 开头有 NUL，或者不是合法的 UTF-8 时当作二进制文件
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(FIRST_FEW_BYTES)].contains(&0) || std::str::from_utf8(bytes).is_err()
+}
+
+/// `--binary` 输出的 `GIT binary patch`，先是新内容，再是用于反向应用的旧内容
+pub fn binary_patch(old: &[u8], new: &[u8]) -> String {
+    let mut output = String::from("GIT binary patch\n");
+    for content in [new, old] {
+        output.push_str(&format!("literal {}\n", content.len()));
+        for chunk in zlib_store(content).chunks(52) {
+            output.push(encode_len(chunk.len()));
+            output.push_str(&encode_85(chunk));
+            output.push('\n');
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+/// 每行开头用一个字母表示这一行的字节数，1-26 是 A-Z，27-52 是 a-z
+fn encode_len(len: usize) -> char {
+    match len {
+        1..=26 => (b'A' + len as u8 - 1) as char,
+        _ => (b'a' + len as u8 - 27) as char,
+    }
+}
+
+const BASE85: &[u8; 85] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+
+/// git 使用的 base85，每 4 个字节编码成 5 个字符，不足 4 个字节时补 0
+fn encode_85(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for group in bytes.chunks(4) {
+        let mut acc = group
+            .iter()
+            .chain(std::iter::repeat(&0))
+            .take(4)
+            .fold(0u32, |acc, byte| (acc << 8) | *byte as u32);
+
+        let mut chars = [0u8; 5];
+        for c in chars.iter_mut().rev() {
+            *c = BASE85[(acc % 85) as usize];
+            acc /= 85;
+        }
+        encoded.extend(chars.iter().map(|c| *c as char));
+    }
+
+    encoded
+}
+
+/// 不压缩的 zlib 流：deflate 的 stored 块加上 adler32 校验
+fn zlib_store(bytes: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 0xffff;
+
+    let mut output = vec![0x78, 0x01];
+    let mut blocks = bytes.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        output.extend([1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        output.push(last as u8);
+        output.extend(len.to_le_bytes());
+        output.extend((!len).to_le_bytes());
+        output.extend(block);
+    }
+
+    output.extend(adler32(bytes).to_be_bytes());
+    output
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in bytes {
+        a = (a + *byte as u32) % MOD;
+        b = (b + a) % MOD;
+    }
+
+    (b << 16) | a
+}
+
+/// 解析 `GIT binary patch` 中一段 literal 或 delta 的内容行，返回解压后的数据
+pub fn decode_patch_data(lines: &[&str], size: usize) -> Result<Vec<u8>, String> {
+    let mut compressed = vec![];
+    for line in lines {
+        let mut chars = line.chars();
+        let len = match chars.next() {
+            Some(c @ 'A'..='Z') => c as usize - 'A' as usize + 1,
+            Some(c @ 'a'..='z') => c as usize - 'a' as usize + 27,
+            _ => return Err(format!("corrupt binary patch line: {line:?}")),
+        };
+        let decoded = decode_85(chars.as_str())?;
+        if decoded.len() < len {
+            return Err(format!("corrupt binary patch line: {line:?}"));
+        }
+        compressed.extend(&decoded[..len]);
+    }
+
+    let data = zlib_inflate(&compressed)?;
+    if data.len() != size {
+        return Err(format!(
+            "binary patch size mismatch: expected {size}, got {}",
+            data.len()
+        ));
+    }
+    Ok(data)
+}
+
+fn decode_85(text: &str) -> Result<Vec<u8>, String> {
+    if !text.len().is_multiple_of(5) {
+        return Err(format!("invalid base85 length: {text:?}"));
+    }
+
+    let mut decoded = vec![];
+    for group in text.as_bytes().chunks(5) {
+        let mut acc = 0u64;
+        for c in group {
+            let value = BASE85
+                .iter()
+                .position(|b| b == c)
+                .ok_or_else(|| format!("invalid base85 character: {:?}", *c as char))?;
+            acc = acc * 85 + value as u64;
+        }
+        if acc > u32::MAX as u64 {
+            return Err(format!("invalid base85 group: {group:?}"));
+        }
+        decoded.extend((acc as u32).to_be_bytes());
+    }
+
+    Ok(decoded)
+}
+
+/// 应用 git 的 delta：开头是原内容和新内容的长度，后面是复制和插入指令
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, String> {
+    let corrupt = || String::from("corrupt binary delta");
+    let mut bytes = delta.iter().copied();
+    let mut size = || {
+        let (mut value, mut shift) = (0usize, 0);
+        loop {
+            let byte = bytes.next().ok_or_else(corrupt)?;
+            value |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok::<_, String>(value);
+            }
+        }
+    };
+    let (base_size, result_size) = (size()?, size()?);
+    if base_size != base.len() {
+        return Err(String::from(
+            "binary delta does not match the current contents",
+        ));
+    }
+
+    let mut result = Vec::with_capacity(result_size);
+    while let Some(op) = bytes.next() {
+        if op & 0x80 != 0 {
+            //低 4 位说明有哪几个字节的偏移，接下来 3 位是长度
+            let (mut offset, mut len) = (0usize, 0usize);
+            for i in 0..4 {
+                if op & (1 << i) != 0 {
+                    offset |= (bytes.next().ok_or_else(corrupt)? as usize) << (8 * i);
+                }
+            }
+            for i in 0..3 {
+                if op & (0x10 << i) != 0 {
+                    len |= (bytes.next().ok_or_else(corrupt)? as usize) << (8 * i);
+                }
+            }
+            if len == 0 {
+                len = 0x10000;
+            }
+            let copy = base.get(offset..offset + len).ok_or_else(corrupt)?;
+            result.extend(copy);
+        } else if op > 0 {
+            for _ in 0..op {
+                result.push(bytes.next().ok_or_else(corrupt)?);
+            }
+        } else {
+            return Err(corrupt());
+        }
+    }
+
+    if result.len() != result_size {
+        return Err(corrupt());
+    }
+    Ok(result)
+}
+
+/// 解压 zlib 流，git 生成的补丁是真正压缩过的，所以要完整实现 inflate
+fn zlib_inflate(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    match bytes {
+        [cmf, flg, data @ ..]
+            if cmf & 0x0f == 8 && (*cmf as u16 * 256 + *flg as u16).is_multiple_of(31) =>
+        {
+            let (output, used) = inflate(data)?;
+            let checksum = data
+                .get(used..used + 4)
+                .map(|sum| u32::from_be_bytes([sum[0], sum[1], sum[2], sum[3]]));
+            match checksum {
+                Some(checksum) if checksum != adler32(&output) => {
+                    Err(String::from("zlib checksum mismatch"))
+                }
+                _ => Ok(output),
+            }
+        }
+        _ => Err(String::from("invalid zlib header")),
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self
+                .data
+                .get(self.pos)
+                .ok_or_else(|| String::from("unexpected end of deflate stream"))?;
+            value |= ((*byte as u32 >> self.bit) & 1) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    /// 跳到下一个字节的开头
+    fn align(&mut self) {
+        if self.bit > 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+
+    fn decode(&mut self, huffman: &Huffman) -> Result<usize, String> {
+        //按 canonical huffman 逐位比较每个长度的第一个编码
+        let (mut code, mut first, mut index) = (0usize, 0usize, 0usize);
+        for count in &huffman.counts[1..] {
+            code |= self.bits(1)? as usize;
+            if code < first + count {
+                return Ok(huffman.symbols[index + code - first]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(String::from("invalid huffman code"))
+    }
+}
+
+struct Huffman {
+    /// 每个编码长度有多少个符号
+    counts: [usize; 16],
+    /// 按编码长度和符号排序的符号
+    symbols: Vec<usize>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0; 16];
+        for len in lengths {
+            counts[*len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut symbols = (0..lengths.len())
+            .filter(|symbol| lengths[*symbol] > 0)
+            .collect::<Vec<_>>();
+        symbols.sort_by_key(|symbol| lengths[*symbol]);
+        Huffman { counts, symbols }
+    }
+}
+
+const LENGTH_BASE: [usize; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [usize; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u32; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// 解压 deflate 数据，返回结果和用掉的字节数
+fn inflate(data: &[u8]) -> Result<(Vec<u8>, usize), String> {
+    let mut reader = BitReader {
+        data,
+        pos: 0,
+        bit: 0,
+    };
+    let mut output = vec![];
+
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let header = data
+                    .get(reader.pos..reader.pos + 4)
+                    .ok_or_else(|| String::from("unexpected end of deflate stream"))?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                let block = data
+                    .get(reader.pos + 4..reader.pos + 4 + len)
+                    .ok_or_else(|| String::from("unexpected end of deflate stream"))?;
+                output.extend(block);
+                reader.pos += 4 + len;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = read_dynamic_tables(&mut reader)?;
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+            _ => return Err(String::from("invalid deflate block type")),
+        }
+
+        if last {
+            reader.align();
+            return Ok((output, reader.pos));
+        }
+    }
+}
+
+fn read_dynamic_tables(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    const ORDER: [usize; 19] = [
+        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+    ];
+
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_count = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for i in ORDER.iter().take(code_count) {
+        code_lengths[*i] = reader.bits(3)? as u8;
+    }
+    let codes = Huffman::new(&code_lengths);
+
+    let mut lengths = vec![];
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match reader.decode(&codes)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => match lengths.last() {
+                Some(previous) => (*previous, 3 + reader.bits(2)? as usize),
+                None => return Err(String::from("invalid code length repeat")),
+            },
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat));
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(String::from("too many code lengths"));
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        match reader.decode(literals)? {
+            symbol @ 0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            symbol => {
+                let index = symbol - 257;
+                if index >= LENGTH_BASE.len() {
+                    return Err(String::from("invalid length code"));
+                }
+                let len = LENGTH_BASE[index] + reader.bits(LENGTH_EXTRA[index])? as usize;
+
+                let index = reader.decode(distances)?;
+                if index >= DIST_BASE.len() {
+                    return Err(String::from("invalid distance code"));
+                }
+                let distance = DIST_BASE[index] + reader.bits(DIST_EXTRA[index])? as usize;
+                if distance > output.len() {
+                    return Err(String::from("invalid distance"));
+                }
+
+                //复制的范围可以和输出重叠，只能逐个字节复制
+                let start = output.len() - distance;
+                for i in 0..len {
+                    output.push(output[start + i]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 取出 `binary_patch` 输出中第一段 literal 的内容行
+    fn literal(patch: &str) -> (usize, Vec<&str>) {
+        let mut lines = patch.lines().skip(1);
+        let size = lines
+            .next()
+            .and_then(|line| line.strip_prefix("literal "))
+            .and_then(|size| size.parse().ok())
+            .unwrap();
+        (size, lines.take_while(|line| !line.is_empty()).collect())
+    }
+
+    #[test]
+    fn base85_round_trip() {
+        for bytes in [&b""[..], b"a", b"abcd", b"\0\xff\x10\x80\x7f", &[0xff; 9]] {
+            let decoded = decode_85(&encode_85(bytes)).unwrap();
+            assert_eq!(&decoded[..bytes.len()], bytes);
+            assert!(decoded[bytes.len()..].iter().all(|b| *b == 0));
+        }
+        assert!(decode_85("0000").is_err());
+        assert!(decode_85("\"0000").is_err());
+        assert!(decode_85("~~~~~").is_err());
+    }
+
+    #[test]
+    fn literal_round_trip() {
+        let content = (0..200u32).map(|i| (i * 7) as u8).collect::<Vec<_>>();
+        for bytes in [&b""[..], b"\0binary\n", &content] {
+            let patch = binary_patch(b"old", bytes);
+            let (size, lines) = literal(&patch);
+            assert_eq!(size, bytes.len());
+            assert_eq!(decode_patch_data(&lines, size).unwrap(), bytes);
+            assert!(decode_patch_data(&lines, size + 1).is_err());
+        }
+    }
+
+    #[test]
+    fn inflate_compressed_streams() {
+        //zlib.compress(b"hello hello hello hello\n" * 4, 9)，固定 Huffman 表
+        let fixed = [
+            120, 218, 203, 72, 205, 201, 201, 87, 200, 64, 39, 185, 50, 168, 36, 14, 0, 171, 242,
+            34, 233,
+        ];
+        assert_eq!(
+            zlib_inflate(&fixed).unwrap(),
+            b"hello hello hello hello\n".repeat(4)
+        );
+
+        //动态 Huffman 表
+        let dynamic = [
+            120, 218, 21, 137, 177, 13, 0, 0, 12, 130, 118, 191, 68, 253, 255, 134, 214, 65, 66,
+            16, 12, 98, 140, 102, 182, 43, 39, 31, 168, 233, 158, 159, 151, 68, 147, 3, 178, 160,
+            17, 103,
+        ];
+        assert_eq!(
+            zlib_inflate(&dynamic).unwrap(),
+            b"aabaa\naabaac\naa\nabbbd\nbccbaaadbadabaaababbccb\nadcc"
+        );
+
+        let mut corrupt = fixed;
+        corrupt[20] ^= 1;
+        assert!(zlib_inflate(&corrupt).is_err());
+    }
+
+    #[test]
+    fn delta() {
+        //复制 "hello "，再插入 "rust"
+        let delta = [11, 10, 0x90, 6, 4, b'r', b'u', b's', b't'];
+        assert_eq!(apply_delta(b"hello world", &delta).unwrap(), b"hello rust");
+        assert!(apply_delta(b"hello", &delta).is_err());
+        assert!(apply_delta(b"hello world", &delta[..6]).is_err());
+    }
+}
@@ -1,10 +1,32 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+
+use crate::{
+    color::ColorWhen,
+    date,
+    diff::{DiffFormat, DiffOptions},
+    line_diff::{Algorithm, Whitespace},
+    merge::ConflictStyle,
+    pretty::{self, DateFormat, Format, PrettyFormat, PrettyOptions},
+    regex::Regex,
+    rename::{self, RenameOptions},
+    revision::{MissingAction, RevFilter},
+    revwalk::{Order, WalkOptions},
+    strategy::{Strategy, StrategyOption},
+    word_diff::{WordDiff, WordDiffMode},
+};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+    /// When to color the output: always, auto or never
+    #[arg(long, global = true, value_name = "when", num_args = 0..=1,
+          require_equals = true, default_missing_value = "always")]
+    pub color: Option<ColorWhen>,
+    /// Do not pipe the output into a pager
+    #[arg(long, global = true)]
+    pub no_pager: bool,
 }
 
 #[derive(Subcommand)]
@@ -26,15 +48,40 @@ pub enum Commands {
     /// Record changes to the repository
     #[command(name = "commit")]
     Commit {
+        /// Commit message, defaults to the prepared merge message
         #[arg(short, long)]
-        message: String,
+        message: Option<String>,
     },
     /// print the commit history
     #[command(name = "log")]
-    Log { oid: Option<String> },
+    Log {
+        /// Commits to show: A, A..B, A...B or ^A, defaults to HEAD
+        #[arg(value_name = "revision")]
+        revs: Vec<String>,
+        /// Only show commits that change these paths
+        #[arg(last = true, value_name = "path")]
+        paths: Vec<String>,
+        /// Show the patch introduced by each commit
+        #[arg(short, long)]
+        patch: bool,
+        /// Draw the commit history as a graph next to the log, in topological order
+        #[arg(long, conflicts_with = "reverse")]
+        graph: bool,
+        #[command(flatten)]
+        revisions: RevisionArgs,
+        #[command(flatten)]
+        pretty: PrettyArgs,
+        #[command(flatten)]
+        diff: DiffArgs,
+    },
     /// Switch branches or restore working tree files
     #[command(name = "checkout")]
-    CheckOut { commit: String },
+    CheckOut {
+        commit: String,
+        /// Throw away local changes that would be overwritten
+        #[arg(short, long)]
+        force: bool,
+    },
     /// Create, list, delete or verify a tag object signed with GPG
     #[command(name = "tag")]
     Tag { name: String, oid: Option<String> },
@@ -52,29 +99,534 @@ pub enum Commands {
     Status,
     /// Reset current HEAD to the specified state
     #[command(name = "reset")]
-    Reset { oid: String },
+    Reset {
+        /// Commit to reset to, HEAD by default
+        oid: Option<String>,
+        /// Only move HEAD, keep the index and working tree
+        #[arg(long, group = "mode")]
+        soft: bool,
+        /// Reset the index but not the working tree (default)
+        #[arg(long, group = "mode")]
+        mixed: bool,
+        /// Reset the index and working tree as well
+        #[arg(long, group = "mode")]
+        hard: bool,
+        /// Reset the index and working tree, keeping unrelated local changes
+        #[arg(long, group = "mode")]
+        keep: bool,
+        /// Discard local changes without checking
+        #[arg(short, long)]
+        force: bool,
+        /// Interactively choose hunks to unstage
+        #[arg(short, long, conflicts_with = "mode")]
+        patch: bool,
+        /// Only reset these paths in the index
+        #[arg(last = true)]
+        paths: Vec<String>,
+    },
     /// Show various types of objects
     #[command(name = "show")]
-    Show { oid: Option<String> },
+    Show {
+        oid: Option<String>,
+        #[command(flatten)]
+        pretty: PrettyArgs,
+        #[command(flatten)]
+        diff: DiffArgs,
+    },
     /// Show the changed files
     #[command(name = "diff")]
     Diff {
-        oid: Option<String>,
+        /// Commits to compare: none, one, two, A..B or A...B
+        #[arg(num_args = 0..=2)]
+        revs: Vec<String>,
+        /// Compare against the index instead of the working tree
         #[arg(short, long)]
         cached: bool,
+        /// Limit the diff to these paths
+        #[arg(last = true)]
+        paths: Vec<String>,
+        #[command(flatten)]
+        diff: DiffArgs,
     },
     /// Join two or more development histories together
     #[command(name = "merge")]
-    Merge { commit: String },
+    Merge {
+        #[arg(required_unless_present_any = ["abort", "continue"])]
+        commits: Vec<String>,
+        /// Overwrite local changes touched by the merge
+        #[arg(short, long)]
+        force: bool,
+        /// How conflicting hunks are written to the working tree: merge, diff3 or zdiff3
+        #[arg(long, value_name = "style", default_value = "merge")]
+        conflict: ConflictStyle,
+        /// Create a merge commit even when the merge resolves as a fast-forward
+        #[arg(long, conflicts_with = "ff_only")]
+        no_ff: bool,
+        /// Refuse to merge unless it can be resolved as a fast-forward
+        #[arg(long)]
+        ff_only: bool,
+        /// Prepare the merge result without recording MERGE_HEAD
+        #[arg(long, conflicts_with = "no_ff")]
+        squash: bool,
+        /// Merge strategy: ours, recursive, ort, octopus or subtree, recursive for one branch and octopus for several by default
+        #[arg(short, long)]
+        strategy: Option<Strategy>,
+        /// Option passed to the merge strategy: ours, theirs, ignore-space-change or subtree=<path>
+        #[arg(short = 'X', long)]
+        strategy_option: Vec<StrategyOption>,
+        /// Abort the current conflict resolution and restore the pre-merge state
+        #[arg(long, conflicts_with_all = ["commits", "continue"])]
+        abort: bool,
+        /// Conclude the merge once the conflicts are resolved
+        #[arg(long = "continue", conflicts_with = "commits")]
+        r#continue: bool,
+    },
     /// Find as good common ancestors as possible for a merge
     #[command(name = "merge-base")]
-    MergeBase { commit1: String, commit2: String },
+    MergeBase {
+        #[arg(required = true)]
+        commits: Vec<String>,
+        /// Output all best common ancestors instead of just one
+        #[arg(short, long)]
+        all: bool,
+        /// Compute the best common ancestors of all supplied commits
+        #[arg(long, conflicts_with_all = ["is_ancestor", "fork_point"])]
+        octopus: bool,
+        /// Exit with status 0 if the first commit is an ancestor of the second
+        #[arg(long, conflicts_with = "fork_point")]
+        is_ancestor: bool,
+        /// Find where the commit forked from the reflog of the given ref
+        #[arg(long)]
+        fork_point: bool,
+    },
     #[command(name = "fetch")]
     /// Download objects and refs from another repository
     Fetch { remote: String },
+    /// List the commits reachable from some revisions but not from others
+    #[command(name = "rev-list")]
+    RevList {
+        /// Commits to list: A, A..B, A...B or ^A
+        #[arg(value_name = "revision")]
+        revs: Vec<String>,
+        /// Only list commits that change these paths
+        #[arg(last = true, value_name = "path")]
+        paths: Vec<String>,
+        /// Also list the trees and blobs of the listed commits, with their paths
+        #[arg(long)]
+        objects: bool,
+        /// Print how many commits would be listed instead of listing them
+        #[arg(long)]
+        count: bool,
+        /// Mark which side of A...B each commit is on with < or >
+        #[arg(long)]
+        left_right: bool,
+        /// What to do with missing objects: error, allow-any or print them as ?<oid>
+        #[arg(long, value_name = "action", default_value = "error")]
+        missing: MissingAction,
+        #[command(flatten)]
+        revisions: RevisionArgs,
+    },
     /// Update remote refs along with associated objects
     Push { remote: String, branch: String },
     /// Add file contents to the index
     #[command(name = "add")]
-    Add { files: Vec<String> },
+    Add {
+        files: Vec<String>,
+        /// Stage modifications and deletions of tracked files only
+        #[arg(short, long, conflicts_with = "all")]
+        update: bool,
+        /// Stage all changes, including new and deleted files
+        #[arg(short = 'A', long)]
+        all: bool,
+        /// Only show what would be added or removed
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+        /// Interactively choose hunks to stage
+        #[arg(short, long, conflicts_with_all = ["update", "all", "dry_run"])]
+        patch: bool,
+    },
+    /// Remove files from the working tree and from the index
+    #[command(name = "rm")]
+    Rm {
+        #[arg(required = true)]
+        files: Vec<String>,
+        /// Only remove from the index
+        #[arg(long)]
+        cached: bool,
+        /// Allow recursive removal when a directory is given
+        #[arg(short)]
+        r: bool,
+        /// Override the up-to-date check
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Move or rename a file, a directory, or a symlink
+    #[command(name = "mv")]
+    Mv {
+        #[arg(required = true, num_args = 2..)]
+        files: Vec<String>,
+        /// Overwrite an existing destination file
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Apply a patch to files and/or to the index
+    #[command(name = "apply")]
+    Apply {
+        /// Patch files to read, the standard input by default
+        patches: Vec<String>,
+        /// Apply the patch to the index only, without touching the working tree
+        #[arg(long)]
+        cached: bool,
+        /// Apply the patch to both the index and the working tree
+        #[arg(long, conflicts_with = "cached")]
+        index: bool,
+        /// Only check whether the patch applies, without changing anything
+        #[arg(long)]
+        check: bool,
+        /// Apply the patch in reverse
+        #[arg(short = 'R', long)]
+        reverse: bool,
+        /// Fall back to a three-way merge when the patch does not apply, implies --index
+        #[arg(short = '3', long = "3way", conflicts_with = "cached")]
+        three_way: bool,
+        /// Ensure at least <n> lines of surrounding context match, allowing the rest to fuzz
+        #[arg(short = 'C', value_name = "n")]
+        context: Option<usize>,
+    },
+    /// Prepare each commit with its patch in one file per commit
+    #[command(name = "format-patch")]
+    FormatPatch {
+        /// <since> for since..HEAD, or <since>..<until>
+        range: String,
+        /// Write the files into <dir> instead of the current directory
+        #[arg(short, long, value_name = "dir")]
+        output_directory: Option<String>,
+        /// Print all commits to the standard output in mbox format
+        #[arg(long, conflicts_with = "output_directory")]
+        stdout: bool,
+        /// Treat a single revision as all commits reachable from it
+        #[arg(long)]
+        root: bool,
+    },
+    /// Apply a series of patches from a mailbox
+    #[command(name = "am")]
+    Am {
+        /// Mailbox files to read, the standard input by default
+        #[arg(conflicts_with_all = ["continue", "skip", "abort"])]
+        mbox: Vec<String>,
+        /// Commit the resolved patch and go on with the rest
+        #[arg(long = "continue", conflicts_with_all = ["skip", "abort"])]
+        r#continue: bool,
+        /// Skip the current patch
+        #[arg(long, conflicts_with = "abort")]
+        skip: bool,
+        /// Restore the original branch and abort the patching operation
+        #[arg(long)]
+        abort: bool,
+    },
+}
+
+impl Commands {
+    /// 输出可能很长、需要分页的命令
+    pub fn uses_pager(&self) -> bool {
+        matches!(
+            self,
+            Commands::Log { .. } | Commands::Show { .. } | Commands::Diff { .. }
+        )
+    }
+}
+
+/// Options shared by the commands that walk the commit history
+#[derive(Args, Debug)]
+pub struct RevisionArgs {
+    /// Exclude the commits reachable from these revisions
+    #[arg(long, value_name = "revision", num_args = 1..)]
+    pub not: Vec<String>,
+    /// Start from all refs and HEAD
+    #[arg(long)]
+    pub all: bool,
+    /// Start from all branches
+    #[arg(long)]
+    pub branches: bool,
+    /// Start from all tags
+    #[arg(long)]
+    pub tags: bool,
+    /// Output at most <n> commits
+    #[arg(short = 'n', long, value_name = "n")]
+    pub max_count: Option<usize>,
+    /// Skip <n> commits before starting to output
+    #[arg(long, value_name = "n", default_value_t = 0)]
+    pub skip: usize,
+    /// Show commits more recent than a date such as 2023-11-14 or "2 weeks ago"
+    #[arg(long, visible_alias = "after", value_name = "date", value_parser = parse_date)]
+    pub since: Option<i64>,
+    /// Show commits older than a date
+    #[arg(long, visible_alias = "before", value_name = "date", value_parser = parse_date)]
+    pub until: Option<i64>,
+    /// Only show commits whose author matches <regex>
+    #[arg(long, value_name = "regex", value_parser = Regex::new)]
+    pub author: Option<Regex>,
+    /// Only show commits whose message matches <regex>
+    #[arg(long, value_name = "regex", value_parser = Regex::new)]
+    pub grep: Option<Regex>,
+    /// Only show merge commits
+    #[arg(long, conflicts_with = "no_merges")]
+    pub merges: bool,
+    /// Do not show merge commits
+    #[arg(long)]
+    pub no_merges: bool,
+    /// Show no parents before all of their children, keeping lines of history together
+    #[arg(long, group = "order")]
+    pub topo_order: bool,
+    /// Show no parents before all of their children, otherwise by commit date
+    #[arg(long, group = "order")]
+    pub date_order: bool,
+    /// Show no parents before all of their children, otherwise by author date
+    #[arg(long, group = "order")]
+    pub author_date_order: bool,
+    /// Output the commits in reverse order
+    #[arg(long)]
+    pub reverse: bool,
+    /// Follow only the first parent of merge commits
+    #[arg(long)]
+    pub first_parent: bool,
+    /// Only show commits on a path from an excluded commit to an included one
+    #[arg(long)]
+    pub ancestry_path: bool,
+}
+
+impl RevisionArgs {
+    pub fn walk_options(&self) -> WalkOptions {
+        let order = if self.topo_order {
+            Order::Topo
+        } else if self.date_order {
+            Order::Date
+        } else if self.author_date_order {
+            Order::AuthorDate
+        } else {
+            Order::Default
+        };
+
+        WalkOptions {
+            order,
+            reverse: self.reverse,
+            first_parent: self.first_parent,
+            ancestry_path: self.ancestry_path,
+        }
+    }
+
+    /// 命令行上的修订，再加上 `--all`、`--branches`、`--tags` 和 `--not` 后面的修订
+    pub fn revisions(&self, revs: Vec<String>) -> Vec<String> {
+        let mut revs = revs;
+        let pseudo = [
+            (self.all, "--all"),
+            (self.branches, "--branches"),
+            (self.tags, "--tags"),
+        ];
+        for (enabled, name) in pseudo {
+            if enabled {
+                revs.push(name.to_string());
+            }
+        }
+        if !self.not.is_empty() {
+            revs.push(String::from("--not"));
+            revs.extend(self.not.iter().cloned());
+        }
+
+        revs
+    }
+
+    pub fn filter(&self) -> RevFilter {
+        let merges = match (self.merges, self.no_merges) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        };
+
+        RevFilter {
+            max_count: self.max_count,
+            skip: self.skip,
+            since: self.since,
+            until: self.until,
+            author: self.author.clone(),
+            grep: self.grep.clone(),
+            merges,
+        }
+    }
+}
+
+/// `--since`、`--until` 的日期转成时间戳
+fn parse_date(date: &str) -> Result<i64, String> {
+    date::parse_approxidate(date, date::now()).ok_or_else(|| format!("invalid date: {date}"))
+}
+
+/// Options shared by the commands that print commits
+#[derive(Args, Debug)]
+pub struct PrettyArgs {
+    /// Show each commit on one line, short for --pretty=oneline
+    #[arg(long)]
+    pub oneline: bool,
+    /// Print commits as oneline, short, medium, full, fuller, raw or format:<string>
+    #[arg(long, value_name = "format", value_parser = pretty::parse_format, num_args = 0..=1,
+          require_equals = true, default_missing_value = "medium", conflicts_with = "oneline")]
+    pub pretty: Option<Format>,
+    /// Print commits with a template such as "%h %an %s", one per line
+    #[arg(long = "format", value_name = "string", conflicts_with_all = ["oneline", "pretty"])]
+    pub template: Option<String>,
+    /// Show dates as default, iso, relative, unix, rfc or short
+    #[arg(long, value_name = "format", default_value = "default")]
+    pub date: DateFormat,
+}
+
+impl PrettyArgs {
+    pub fn options(&self, color: bool) -> PrettyOptions {
+        let format = if self.oneline {
+            Format::Builtin(PrettyFormat::Oneline)
+        } else if let Some(template) = &self.template {
+            Format::Template {
+                template: template.clone(),
+                terminate: true,
+            }
+        } else {
+            self.pretty.clone().unwrap_or_default()
+        };
+
+        PrettyOptions {
+            format,
+            date: self.date,
+            color,
+        }
+    }
+}
+
+/// Options shared by the commands that print diffs
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    /// Generate diffs with <n> lines of context
+    #[arg(short = 'U', long = "unified", value_name = "n", default_value_t = 3)]
+    pub context: usize,
+    /// Choose a diff algorithm: myers, patience or histogram
+    #[arg(long, value_name = "algorithm", default_value = "myers")]
+    pub diff_algorithm: Algorithm,
+    /// Generate a diff using the "patience diff" algorithm
+    #[arg(long, conflicts_with = "histogram")]
+    pub patience: bool,
+    /// Generate a diff using the "histogram diff" algorithm
+    #[arg(long)]
+    pub histogram: bool,
+    /// Detect renames, optionally with a similarity threshold such as -M=90%
+    #[arg(short = 'M', long, value_name = "n", num_args = 0..=1, require_equals = true,
+          default_missing_value = "50%", value_parser = rename::parse_similarity)]
+    pub find_renames: Option<usize>,
+    /// Detect copies as well as renames, optionally with a threshold such as -C=75%
+    #[arg(short = 'C', long, value_name = "n", num_args = 0..=1, require_equals = true,
+          default_missing_value = "50%", value_parser = rename::parse_similarity)]
+    pub find_copies: Option<usize>,
+    /// Turn off rename detection
+    #[arg(long, conflicts_with_all = ["find_renames", "find_copies"])]
+    pub no_renames: bool,
+    /// Show a diffstat with a histogram per file
+    #[arg(long, group = "format")]
+    pub stat: bool,
+    /// Show added and removed line counts per file, for scripts
+    #[arg(long, group = "format")]
+    pub numstat: bool,
+    /// Show only the last line of --stat
+    #[arg(long, group = "format")]
+    pub shortstat: bool,
+    /// Show only the names of changed files
+    #[arg(long, group = "format")]
+    pub name_only: bool,
+    /// Show the names and status letters of changed files
+    #[arg(long, group = "format")]
+    pub name_status: bool,
+    /// Show a word diff in plain, color or porcelain mode
+    #[arg(long, value_name = "mode", num_args = 0..=1, require_equals = true,
+          default_missing_value = "plain")]
+    pub word_diff: Option<WordDiffMode>,
+    /// Use <regex> to decide what a word is, implies --word-diff
+    #[arg(long, value_name = "regex", value_parser = Regex::new)]
+    pub word_diff_regex: Option<Regex>,
+    /// Ignore whitespace when comparing lines
+    #[arg(short = 'w', long)]
+    pub ignore_all_space: bool,
+    /// Ignore changes in amount of whitespace
+    #[arg(short = 'b', long)]
+    pub ignore_space_change: bool,
+    /// Ignore changes in whitespace at EOL
+    #[arg(long)]
+    pub ignore_space_at_eol: bool,
+    /// Ignore changes whose lines are all blank
+    #[arg(long)]
+    pub ignore_blank_lines: bool,
+    /// Output a binary diff that can be applied, instead of just noting the difference
+    #[arg(long)]
+    pub binary: bool,
+    /// Color moved lines differently from added and removed ones
+    #[arg(long)]
+    pub color_moved: bool,
+}
+
+impl DiffArgs {
+    pub fn options(&self, color: bool) -> DiffOptions {
+        let algorithm = if self.patience {
+            Algorithm::Patience
+        } else if self.histogram {
+            Algorithm::Histogram
+        } else {
+            self.diff_algorithm
+        };
+
+        let renames = match (self.find_renames, self.find_copies) {
+            _ if self.no_renames => None,
+            (threshold, Some(copies)) => Some(RenameOptions {
+                threshold: threshold.unwrap_or(copies),
+                copies: true,
+            }),
+            (Some(threshold), None) => Some(RenameOptions {
+                threshold,
+                copies: false,
+            }),
+            (None, None) => Some(RenameOptions::default()),
+        };
+
+        let format = if self.stat {
+            DiffFormat::Stat
+        } else if self.numstat {
+            DiffFormat::Numstat
+        } else if self.shortstat {
+            DiffFormat::Shortstat
+        } else if self.name_only {
+            DiffFormat::NameOnly
+        } else if self.name_status {
+            DiffFormat::NameStatus
+        } else {
+            DiffFormat::Patch
+        };
+
+        let word_diff = match (self.word_diff, &self.word_diff_regex) {
+            (None, None) => None,
+            (mode, regex) => Some(WordDiff {
+                mode: mode.unwrap_or_default(),
+                regex: regex.clone(),
+            }),
+        };
+
+        DiffOptions {
+            algorithm,
+            context: self.context,
+            renames,
+            format,
+            whitespace: Whitespace {
+                ignore_all_space: self.ignore_all_space,
+                ignore_space_change: self.ignore_space_change,
+                ignore_space_at_eol: self.ignore_space_at_eol,
+            },
+            ignore_blank_lines: self.ignore_blank_lines,
+            word_diff,
+            binary: self.binary,
+            color,
+            color_moved: self.color_moved,
+        }
+    }
 }
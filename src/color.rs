@@ -0,0 +1,77 @@
+use std::{
+    env,
+    io::{self, IsTerminal},
+    str::FromStr,
+};
+
+use crate::{data::Ugit, pager};
+
+pub const RESET: &str = "\x1b[m";
+pub const BOLD: &str = "\x1b[1m";
+pub const RED: &str = "\x1b[31m";
+pub const GREEN: &str = "\x1b[32m";
+pub const YELLOW: &str = "\x1b[33m";
+pub const BLUE: &str = "\x1b[34m";
+pub const CYAN: &str = "\x1b[36m";
+pub const BOLD_RED: &str = "\x1b[1;31m";
+pub const BOLD_GREEN: &str = "\x1b[1;32m";
+pub const BOLD_YELLOW: &str = "\x1b[1;33m";
+pub const BOLD_MAGENTA: &str = "\x1b[1;35m";
+pub const BOLD_CYAN: &str = "\x1b[1;36m";
+
+/// 什么时候输出颜色
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorWhen {
+    Always,
+    /// 只在输出到终端或分页器时
+    Auto,
+    Never,
+}
+
+impl FromStr for ColorWhen {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(ColorWhen::Always),
+            "auto" => Ok(ColorWhen::Auto),
+            "never" => Ok(ColorWhen::Never),
+            _ => Err(format!("unknown color mode: {s}")),
+        }
+    }
+}
+
+/// 是否输出颜色，命令行优先于 `color.ui` 配置，auto 时看 `NO_COLOR` 和是否输出到终端或分页器
+pub fn use_color(when: Option<ColorWhen>, ugit: &Ugit) -> bool {
+    let when = when.unwrap_or_else(|| match ugit.get_config("color.ui").as_deref() {
+        Some("always") => ColorWhen::Always,
+        Some("never" | "false") => ColorWhen::Never,
+        _ => ColorWhen::Auto,
+    });
+
+    match when {
+        ColorWhen::Always => true,
+        ColorWhen::Never => false,
+        ColorWhen::Auto => {
+            env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                && (io::stdout().is_terminal() || pager::in_use())
+        }
+    }
+}
+
+/// 启用时给文本加上颜色
+pub fn paint(enabled: bool, color: &str, text: &str) -> String {
+    if enabled && !text.is_empty() {
+        format!("{color}{text}{RESET}")
+    } else {
+        text.to_string()
+    }
+}
+
+/// 同 [`paint`]，但换行符放在颜色外面
+pub fn paint_line(enabled: bool, color: &str, line: &str) -> String {
+    match line.strip_suffix('\n') {
+        Some(line) => format!("{}\n", paint(enabled, color, line)),
+        None => paint(enabled, color, line),
+    }
+}
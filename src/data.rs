@@ -3,6 +3,7 @@ use std::fs::{create_dir, File};
 use std::io::{Error, Read, Write};
 use std::mem::{self};
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{env, fs, vec};
 
 use crypto::digest::Digest;
@@ -10,6 +11,10 @@ use crypto::sha1::Sha1;
 
 pub const HEAD: &str = "HEAD";
 pub const MERGE_HEAD: &str = "MERGE_HEAD";
+pub const ORIG_HEAD: &str = "ORIG_HEAD";
+/// 合并写进索引和工作区的结果对应的树，`merge --abort` 据此只恢复合并改过的路径
+pub const MERGE_TREE: &str = "MERGE_TREE";
+pub const MERGE_MSG: &str = "MERGE_MSG";
 pub const REF_PREFIX: &str = "ref: ";
 pub const DELIMITER: u8 = b'\x00';
 
@@ -54,13 +59,119 @@ impl Ugit {
         Ok(p)
     }
 
+    /// 写入索引，索引里内容变了的路径同时从冲突记录里去掉
     pub fn write_index(&self, indexs: &HashMap<String, String>) -> Result<(), Error> {
+        let mut conflicts = self.get_conflicts()?;
+        if !conflicts.is_empty() {
+            let old = self.get_index()?;
+            let count = conflicts.len();
+            conflicts.retain(|path, _| old.get(path) == indexs.get(path));
+            if conflicts.len() != count {
+                self.write_conflicts(&conflicts)?;
+            }
+        }
+
         let json = serde_json::to_string(indexs)?;
 
         fs::write(PathBuf::from(&self.git_dir).join("index"), json)?;
         Ok(())
     }
 
+    /// 合并冲突的文件在索引中记录的各方版本，只在索引里的内容不变时有效
+    pub fn get_conflicts(&self) -> Result<HashMap<String, Stages>, Error> {
+        let path = PathBuf::from(&self.git_dir).join("conflicts");
+        if !path.is_file() {
+            return Ok(HashMap::new());
+        }
+
+        let string = fs::read_to_string(path)?;
+        let p: HashMap<String, HashMap<String, String>> = serde_json::from_str(&string)?;
+
+        Ok(p.into_iter()
+            .map(|(path, stages)| (path, Stages::from(stages)))
+            .collect())
+    }
+
+    pub fn write_conflicts(&self, conflicts: &HashMap<String, Stages>) -> Result<(), Error> {
+        let path = PathBuf::from(&self.git_dir).join("conflicts");
+        if conflicts.is_empty() {
+            if path.is_file() {
+                fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+
+        let p = conflicts
+            .iter()
+            .map(|(path, stages)| (path.clone(), stages.to_map()))
+            .collect::<HashMap<_, _>>();
+        fs::write(path, serde_json::to_string(&p)?)?;
+        Ok(())
+    }
+
+    /// 合并时准备好的提交信息，`commit` 没有给 `-m` 时使用
+    pub fn get_merge_msg(&self) -> Option<String> {
+        fs::read_to_string(PathBuf::from(&self.git_dir).join(MERGE_MSG)).ok()
+    }
+
+    /// 空的信息删除文件
+    pub fn write_merge_msg(&self, message: &str) -> Result<(), Error> {
+        let path = PathBuf::from(&self.git_dir).join(MERGE_MSG);
+        if message.is_empty() {
+            if path.is_file() {
+                fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+
+        fs::write(path, message)
+    }
+
+    /// 读 `.rgit/config` 里的配置，`key` 形如 `color.ui`、`remote.origin.url`，同一个变量以最后一次为准
+    pub fn get_config(&self, key: &str) -> Option<String> {
+        let content = fs::read_to_string(PathBuf::from(&self.git_dir).join("config")).ok()?;
+        let normalize = |section: &str, name: &str| match section.split_once('.') {
+            Some((section, subsection)) => {
+                format!(
+                    "{}.{subsection}.{}",
+                    section.to_lowercase(),
+                    name.to_lowercase()
+                )
+            }
+            None => format!("{}.{}", section.to_lowercase(), name.to_lowercase()),
+        };
+        let key = match key.rsplit_once('.') {
+            Some((section, name)) => normalize(section, name),
+            None => return None,
+        };
+
+        let mut section = String::new();
+        let mut value = None;
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = match header.split_once(char::is_whitespace) {
+                    Some((name, sub)) => format!("{name}.{}", sub.trim().trim_matches('"')),
+                    None => header.to_string(),
+                };
+                continue;
+            }
+
+            //只有名字没有值的是布尔值 true
+            let (name, v) = line
+                .split_once('=')
+                .map_or((line, "true"), |(name, v)| (name.trim(), v.trim()));
+            if normalize(&section, name) == key {
+                value = Some(v.trim_matches('"').to_string());
+            }
+        }
+
+        value
+    }
+
     pub fn git_dir(&self) -> &str {
         &self.git_dir
     }
@@ -132,6 +243,12 @@ impl Ugit {
     }
 
     pub fn get_object(&self, oid: &str, expected: DataType) -> Result<String, DateErr> {
+        self.get_object_bytes(oid, expected)
+            .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+    }
+
+    /// 读出对象的原始内容，二进制文件要用这个而不是 `get_object`
+    pub fn get_object_bytes(&self, oid: &str, expected: DataType) -> Result<Vec<u8>, DateErr> {
         let current_dir = env::current_dir().expect("failed to obtain current dir");
         let current_dir: PathBuf = current_dir.join(&self.git_dir).join("objects").join(oid);
 
@@ -147,16 +264,14 @@ impl Ugit {
         };
 
         let (ty, content) = {
-            let bytes = obj.splitn(2, |v| v == &DELIMITER).collect::<Vec<_>>();
-            let ty_bytes = bytes.get(0);
-            let content_bytes = bytes.get(1);
+            let mut bytes = obj.splitn(2, |v| v == &DELIMITER);
+            let ty_bytes = bytes.next();
+            let content_bytes = bytes.next();
             (
                 ty_bytes
                     .map(|b| String::from_utf8_lossy(b).to_string())
                     .unwrap_or_else(String::new),
-                content_bytes
-                    .map(|b| String::from_utf8_lossy(b).to_string())
-                    .unwrap_or_else(String::new),
+                content_bytes.map(<[u8]>::to_vec).unwrap_or_default(),
             )
         };
 
@@ -189,7 +304,16 @@ impl Ugit {
 
     pub fn update_ref<T: AsRef<str>>(&self, ref_str: T, value: RefValue, deref: bool) {
         let ref_str = ref_str.as_ref();
-        let ref_str = self.get_ref_internal(ref_str, deref).0;
+        let (ref_str, old) = self.get_ref_internal(ref_str, deref);
+
+        if !value.symbolic && (ref_str == HEAD || ref_str.starts_with("refs/")) {
+            let old = if old.symbolic {
+                String::new()
+            } else {
+                old.value
+            };
+            self.append_reflog(&ref_str, &old, &value.value);
+        }
 
         let path = PathBuf::from(&self.git_dir).join(ref_str);
 
@@ -219,6 +343,61 @@ impl Ugit {
         }
     }
 
+    /// 在 logs/<ref> 里追加一行 `<old> <new> <timestamp>`
+    fn append_reflog(&self, ref_str: &str, old: &str, new: &str) {
+        if old == new {
+            return;
+        }
+
+        let path = PathBuf::from(&self.git_dir).join("logs").join(ref_str);
+        if let Some(parent) = path.parent() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                eprintln!("append_reflog, create dirs error:{:?}", e);
+                return;
+            }
+        }
+
+        let zero = "0".repeat(40);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let line = format!(
+            "{} {} {timestamp}\n",
+            if old.is_empty() { &zero } else { old },
+            if new.is_empty() { &zero } else { new },
+        );
+
+        let result = File::options()
+            .append(true)
+            .create(true)
+            .open(&path)
+            .and_then(|mut f| f.write_all(line.as_bytes()));
+        if let Err(err) = result {
+            eprintln!("append_reflog, write {:?} error:{:?}", path, err);
+        }
+    }
+
+    /// 引用的历史值，最新的在前
+    pub fn get_reflog(&self, ref_str: &str) -> Vec<ReflogEntry> {
+        let path = PathBuf::from(&self.git_dir).join("logs").join(ref_str);
+        let content = fs::read_to_string(path).unwrap_or_default();
+
+        let mut entries = content
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split(' ');
+                Some(ReflogEntry {
+                    old: fields.next()?.to_string(),
+                    new: fields.next()?.to_string(),
+                    timestamp: fields.next()?.parse().ok()?,
+                })
+            })
+            .collect::<Vec<_>>();
+        entries.reverse();
+        entries
+    }
+
     /// ['ref_str']: /ref/heads/branch or /refs/tags/test
     fn get_ref_internal(&self, ref_str: &str, deref: bool) -> (String, RefValue) {
         let value = {
@@ -270,6 +449,8 @@ impl Ugit {
         while let Some(dir) = dirs.pop_front() {
             let read_dir = match dir.read_dir() {
                 Ok(read_dir) => read_dir,
+                //还没有任何引用时 refs 目录不存在
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
                 Err(err) => {
                     println!("err:{:?}", err);
                     continue;
@@ -403,6 +584,62 @@ impl From<Error> for DateErr {
     }
 }
 
+/// reflog 中的一条记录，没有值的一边是 40 个 0
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReflogEntry {
+    pub old: String,
+    pub new: String,
+    pub timestamp: u64,
+}
+
+/// 冲突文件的三个版本，对应 git 索引中的 stage 1/2/3
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stages {
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+}
+
+impl Stages {
+    /// `status` 中显示的冲突类型
+    pub fn description(&self) -> &'static str {
+        match (&self.base, &self.ours, &self.theirs) {
+            (Some(_), Some(_), Some(_)) => "both modified",
+            (None, Some(_), Some(_)) => "both added",
+            (Some(_), Some(_), None) => "deleted by them",
+            (Some(_), None, Some(_)) => "deleted by us",
+            (Some(_), None, None) => "both deleted",
+            (None, Some(_), None) => "added by us",
+            (None, None, Some(_)) => "added by them",
+            (None, None, None) => "unmerged",
+        }
+    }
+
+    fn to_map(&self) -> HashMap<String, String> {
+        let mut map = HashMap::new();
+        for (name, oid) in [
+            ("base", &self.base),
+            ("ours", &self.ours),
+            ("theirs", &self.theirs),
+        ] {
+            if let Some(oid) = oid {
+                map.insert(name.to_string(), oid.clone());
+            }
+        }
+        map
+    }
+}
+
+impl From<HashMap<String, String>> for Stages {
+    fn from(mut value: HashMap<String, String>) -> Self {
+        Self {
+            base: value.remove("base"),
+            ours: value.remove("ours"),
+            theirs: value.remove("theirs"),
+        }
+    }
+}
+
 pub struct RefValue {
     pub symbolic: bool,
     pub value: String,
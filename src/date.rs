@@ -0,0 +1,327 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// 当前的 unix 时间戳
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// 某个时区下的日期和时间
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: i64,
+    /// 1-12
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    /// 0 是星期一
+    pub weekday: usize,
+}
+
+impl DateTime {
+    /// `offset` 是相对 UTC 的分钟数
+    pub fn from_timestamp(time: i64, offset: i32) -> DateTime {
+        let local = time + offset as i64 * 60;
+        let (days, seconds) = (local.div_euclid(86400), local.rem_euclid(86400));
+        let (year, month, day) = civil_from_days(days);
+
+        DateTime {
+            year,
+            month,
+            day,
+            hour: (seconds / 3600) as u32,
+            minute: (seconds / 60 % 60) as u32,
+            second: (seconds % 60) as u32,
+            //1970-01-01 是星期四
+            weekday: (days + 3).rem_euclid(7) as usize,
+        }
+    }
+
+    fn timestamp(&self, offset: i32) -> i64 {
+        days_from_civil(self.year, self.month, self.day) * 86400
+            + (self.hour * 3600 + self.minute * 60 + self.second) as i64
+            - offset as i64 * 60
+    }
+}
+
+/// 1970-01-01 之后的天数转成年月日，Howard Hinnant 的算法
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146097 + doe - 719468
+}
+
+/// `+0800` 形式的时区
+pub fn format_offset(offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let offset = offset.abs();
+    format!("{sign}{:02}{:02}", offset / 60, offset % 60)
+}
+
+pub fn parse_offset(offset: &str) -> Option<i32> {
+    let (sign, digits) = match offset.split_at_checked(1)? {
+        ("+", digits) => (1, digits),
+        ("-", digits) => (-1, digits),
+        _ => return None,
+    };
+    if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let (hours, minutes) = digits.split_at(2);
+    Some(sign * (hours.parse::<i32>().ok()? * 60 + minutes.parse::<i32>().ok()?))
+}
+
+/// 邮件头里的日期：`Tue, 14 Nov 2023 22:13:20 +0000`
+pub fn format_rfc2822(time: i64, offset: i32) -> String {
+    let date = DateTime::from_timestamp(time, offset);
+    format!(
+        "{}, {} {} {} {:02}:{:02}:{:02} {}",
+        WEEKDAYS[date.weekday],
+        date.day,
+        MONTHS[date.month as usize - 1],
+        date.year,
+        date.hour,
+        date.minute,
+        date.second,
+        format_offset(offset)
+    )
+}
+
+/// git 默认的日期格式：`Tue Nov 14 22:13:20 2023 +0000`
+pub fn format_default(time: i64, offset: i32) -> String {
+    let date = DateTime::from_timestamp(time, offset);
+    format!(
+        "{} {} {} {:02}:{:02}:{:02} {} {}",
+        WEEKDAYS[date.weekday],
+        MONTHS[date.month as usize - 1],
+        date.day,
+        date.hour,
+        date.minute,
+        date.second,
+        date.year,
+        format_offset(offset)
+    )
+}
+
+/// 解析 `[Tue, ]14 Nov 2023 22:13:20 +0000`，返回时间戳和时区
+pub fn parse_rfc2822(date: &str) -> Option<(i64, i32)> {
+    let date = date.split_once(',').map_or(date, |(_, rest)| rest);
+    let mut parts = date.split_whitespace();
+    let day = parts.next()?.parse().ok()?;
+    let month = parts.next()?;
+    let month = MONTHS
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(month))? as u32
+        + 1;
+    let year = parts.next()?.parse().ok()?;
+
+    let mut clock = parts.next()?.split(':').map(str::parse::<u32>);
+    let hour = clock.next()?.ok()?;
+    let minute = clock.next()?.ok()?;
+    let second = clock.next().unwrap_or(Ok(0)).ok()?;
+    let offset = parts.next().map_or(Some(0), parse_offset)?;
+
+    let date = DateTime {
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        weekday: 0,
+    };
+    Some((date.timestamp(offset), offset))
+}
+
+/// 环境变量里的日期：`<时间戳> <时区>`、`@<时间戳>` 或者邮件头的格式
+pub fn parse_date(date: &str) -> Option<(i64, i32)> {
+    let date = date.trim();
+    let raw = date.strip_prefix('@').unwrap_or(date);
+    let mut parts = raw.split_whitespace();
+    if let Some(Ok(time)) = parts.next().map(str::parse::<i64>) {
+        match parts.next().map(parse_offset) {
+            None => return Some((time, 0)),
+            Some(Some(offset)) => return Some((time, offset)),
+            //`14 Nov 2023 ...` 这样没有星期的邮件日期
+            Some(None) => {}
+        }
+    }
+
+    parse_rfc2822(date)
+}
+
+/// ISO 8601 风格：`2023-11-14 22:13:20 +0000`
+pub fn format_iso(time: i64, offset: i32) -> String {
+    let date = DateTime::from_timestamp(time, offset);
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}:{:02} {}",
+        date.year,
+        date.month,
+        date.day,
+        date.hour,
+        date.minute,
+        date.second,
+        format_offset(offset)
+    )
+}
+
+/// 只有日期：`2023-11-14`
+pub fn format_short(time: i64, offset: i32) -> String {
+    let date = DateTime::from_timestamp(time, offset);
+    format!("{}-{:02}-{:02}", date.year, date.month, date.day)
+}
+
+/// 相对 `now` 的时间，如 `3 hours ago`，单位的取法和 git 一致
+pub fn format_relative(time: i64, now: i64) -> String {
+    let seconds = now - time;
+    if seconds < 0 {
+        return String::from("in the future");
+    }
+
+    let plural = |count: i64, unit: &str| match count {
+        1 => format!("1 {unit}"),
+        count => format!("{count} {unit}s"),
+    };
+    //四舍五入到最近的单位
+    let round = |value: i64, unit: i64| (value + unit / 2) / unit;
+
+    if seconds < 90 {
+        return format!("{} ago", plural(seconds, "second"));
+    }
+    let minutes = round(seconds, 60);
+    if minutes < 90 {
+        return format!("{} ago", plural(minutes, "minute"));
+    }
+    let hours = round(minutes, 60);
+    if hours < 36 {
+        return format!("{} ago", plural(hours, "hour"));
+    }
+    let days = round(hours, 24);
+    if days < 14 {
+        return format!("{} ago", plural(days, "day"));
+    }
+    if days < 70 {
+        return format!("{} ago", plural(round(days, 7), "week"));
+    }
+    if days < 365 {
+        return format!("{} ago", plural(round(days, 30), "month"));
+    }
+
+    //五年以内同时显示年和月
+    let months = round(days * 12, 365);
+    let (years, months) = (months / 12, months % 12);
+    if years < 5 && months > 0 {
+        format!("{}, {} ago", plural(years, "year"), plural(months, "month"))
+    } else {
+        format!("{} ago", plural(round(days, 365), "year"))
+    }
+}
+
+/// `--since`、`--until` 的日期：`2023-11-14`、`2023-11-14 22:13[:20]`、`2 weeks ago`、
+/// `yesterday`、`now`，或者 [`parse_date`] 认识的格式，返回时间戳
+pub fn parse_approxidate(date: &str, now: i64) -> Option<i64> {
+    let date = date.trim();
+    match date {
+        "now" => return Some(now),
+        "yesterday" => return Some(now - 86400),
+        _ => {}
+    }
+
+    //`2.weeks.ago` 和 `2 weeks ago` 一样
+    let words = date.replace('.', " ");
+    let words = words.split_whitespace().collect::<Vec<_>>();
+    if let [count, unit, "ago"] = words.as_slice() {
+        let count = count.parse::<i64>().ok()?;
+        let seconds = match unit.trim_end_matches('s') {
+            "second" | "sec" => 1,
+            "minute" | "min" => 60,
+            "hour" => 3600,
+            "day" => 86400,
+            "week" => 7 * 86400,
+            "month" => 30 * 86400,
+            "year" => 365 * 86400,
+            _ => return None,
+        };
+        return Some(now - count * seconds);
+    }
+
+    if let Some(time) = parse_iso(date) {
+        return Some(time);
+    }
+    parse_date(date).map(|(time, _)| time)
+}
+
+/// `2023-11-14`、`2023-11-14 22:13[:20] [+0800]`，中间也可以是 `T`，没有时区时按 UTC
+fn parse_iso(date: &str) -> Option<i64> {
+    let (day, rest) = match date.split_once([' ', 'T']) {
+        Some((day, rest)) => (day, rest.trim()),
+        None => (date, ""),
+    };
+    let mut fields = day.split('-');
+    let year = fields.next()?.parse().ok()?;
+    let month = fields
+        .next()?
+        .parse()
+        .ok()
+        .filter(|month| (1..=12).contains(month))?;
+    let day = fields
+        .next()?
+        .parse()
+        .ok()
+        .filter(|day| (1..=31).contains(day))?;
+    if fields.next().is_some() {
+        return None;
+    }
+
+    let (clock, offset) = match rest.split_once(' ') {
+        Some((clock, offset)) => (clock, parse_offset(offset.trim())?),
+        None => (rest, 0),
+    };
+    let mut clock = clock.split(':').filter(|field| !field.is_empty());
+    let mut next = || {
+        clock
+            .next()
+            .map_or(Some(0), |field| field.parse::<u32>().ok())
+    };
+    let (hour, minute, second) = (next()?, next()?, next()?);
+
+    let date = DateTime {
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        weekday: 0,
+    };
+    Some(date.timestamp(offset))
+}
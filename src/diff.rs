@@ -1,29 +1,481 @@
 use std::{
     collections::{HashMap, HashSet},
-    io::Write,
-    path::PathBuf,
-    process::Command,
+    path::{Path, PathBuf},
 };
 
-use tempfile::NamedTempFile;
+use crate::{
+    base::Commit,
+    binary, color,
+    data::{DataType, DateErr, Stages, Ugit},
+    line_diff::{self, Algorithm, Hunk, LineKind, Whitespace},
+    merge::{self, MergeOptions, MergeResult},
+    pathspec::Pathspec,
+    rename::{Rename, RenameOptions},
+    word_diff::WordDiff,
+};
 
-use crate::data::{self, DateErr, Ugit};
+/// 输出 diff 时的选项
+#[derive(Clone, Debug)]
+pub struct DiffOptions {
+    pub algorithm: Algorithm,
+    /// hunk 前后保留的上下文行数
+    pub context: usize,
+    /// 为 None 时不检测改名
+    pub renames: Option<RenameOptions>,
+    pub format: DiffFormat,
+    pub whitespace: Whitespace,
+    /// 忽略只增删了空行的修改
+    pub ignore_blank_lines: bool,
+    /// 为 Some 时 patch 按词比较
+    pub word_diff: Option<WordDiff>,
+    /// 二进制文件输出可以应用的补丁，而不只是提示有差异
+    pub binary: bool,
+    pub color: bool,
+    /// 用不同的颜色标出移动过的行
+    pub color_moved: bool,
+}
 
-//比较两个目录，同一个key指向不同内容，则发生了变化
-pub fn diff_tree(t_from: &HashMap<PathBuf, String>, t_to: &HashMap<PathBuf, String>) -> String {
-    let keys = merge_key(vec![t_from, t_to]);
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            algorithm: Algorithm::default(),
+            context: 3,
+            renames: Some(RenameOptions::default()),
+            format: DiffFormat::default(),
+            whitespace: Whitespace::default(),
+            ignore_blank_lines: false,
+            word_diff: None,
+            binary: false,
+            color: false,
+            color_moved: false,
+        }
+    }
+}
+
+/// diff 的输出格式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiffFormat {
+    #[default]
+    Patch,
+    /// 每个文件一行修改行数和直方图，最后是合计
+    Stat,
+    /// 每个文件一行 `增加\t删除\t路径`
+    Numstat,
+    /// 只有合计
+    Shortstat,
+    NameOnly,
+    /// 每个文件一行 `状态\t路径`
+    NameStatus,
+}
+
+/// 文件是怎么变化的
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Deleted,
+    Modified,
+    Renamed { similarity: usize },
+    Copied { similarity: usize },
+}
+
+impl ChangeKind {
+    /// `--name-status` 里的状态，如 `M`、`R100`
+    pub fn letter(&self) -> String {
+        match self {
+            ChangeKind::Added => String::from("A"),
+            ChangeKind::Deleted => String::from("D"),
+            ChangeKind::Modified => String::from("M"),
+            ChangeKind::Renamed { similarity } => format!("R{similarity:03}"),
+            ChangeKind::Copied { similarity } => format!("C{similarity:03}"),
+        }
+    }
+}
+
+/// 一个文件的修改
+#[derive(Clone, Debug)]
+pub struct FileChange {
+    pub kind: ChangeKind,
+    /// 修改前的路径和对象，新增的文件为 None
+    pub old: Option<(PathBuf, String)>,
+    /// 修改后的路径和对象，删除的文件为 None
+    pub new: Option<(PathBuf, String)>,
+    pub hunks: Vec<Hunk>,
+    /// 二进制文件修改前后的内容，文本文件为 None
+    pub binary: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl FileChange {
+    /// 修改后的路径，删除的文件是原来的路径
+    pub fn path(&self) -> &Path {
+        match (&self.new, &self.old) {
+            (Some((path, _)), _) | (None, Some((path, _))) => path,
+            (None, None) => Path::new(""),
+        }
+    }
+
+    /// 改名和复制显示为 `a => b`
+    pub fn display_path(&self) -> String {
+        match (&self.kind, &self.old) {
+            (ChangeKind::Renamed { .. } | ChangeKind::Copied { .. }, Some((old, _))) => {
+                format!("{} => {}", old.display(), self.path().display())
+            }
+            _ => self.path().display().to_string(),
+        }
+    }
+
+    /// 增加和删除的行数，二进制文件都是 0
+    pub fn stat(&self) -> (usize, usize) {
+        let lines = self.hunks.iter().flat_map(|hunk| &hunk.lines);
+        lines.fold((0, 0), |(added, removed), (kind, _)| match kind {
+            LineKind::Added => (added + 1, removed),
+            LineKind::Removed => (added, removed + 1),
+            LineKind::Context => (added, removed),
+        })
+    }
+
+    /// 这个文件的 diff，按 `options` 决定是否按词比较、输出二进制补丁和颜色
+    pub fn patch(&self, options: &DiffOptions) -> String {
+        self.write_patch(options, &MovedLines::default())
+    }
+
+    fn write_patch(&self, options: &DiffOptions, moved: &MovedLines) -> String {
+        let new_path = self.path().display();
+        let old_path = self
+            .old
+            .as_ref()
+            .map_or(self.path(), |(path, _)| path)
+            .display();
+        let mut header = format!("diff --git a/{old_path} b/{new_path}\n");
+
+        //输出二进制补丁时和 git 一样使用完整的对象名
+        let abbrev = |entry: &Option<(PathBuf, String)>| match (entry, options.binary) {
+            (Some((_, oid)), true) => oid.clone(),
+            (Some((_, oid)), false) => short_oid(oid).to_string(),
+            (None, true) => "0".repeat(40),
+            (None, false) => "0".repeat(7),
+        };
+        let (o_old, o_new) = (abbrev(&self.old), abbrev(&self.new));
+        match self.kind {
+            ChangeKind::Added => {
+                header.push_str("new file mode 100644\n");
+                header.push_str(&format!("index {o_old}..{o_new}\n"));
+            }
+            ChangeKind::Deleted => {
+                header.push_str("deleted file mode 100644\n");
+                header.push_str(&format!("index {o_old}..{o_new}\n"));
+            }
+            ChangeKind::Modified => {
+                header.push_str(&format!("index {o_old}..{o_new} 100644\n"));
+            }
+            ChangeKind::Renamed { similarity } | ChangeKind::Copied { similarity } => {
+                let kind = match self.kind {
+                    ChangeKind::Copied { .. } => "copy",
+                    _ => "rename",
+                };
+                header.push_str(&format!("similarity index {similarity}%\n"));
+                header.push_str(&format!("{kind} from {old_path}\n"));
+                header.push_str(&format!("{kind} to {new_path}\n"));
+                if o_old != o_new {
+                    header.push_str(&format!("index {o_old}..{o_new} 100644\n"));
+                }
+            }
+        }
+
+        //文件头的每一行都加粗
+        let meta = |header: &str| {
+            header
+                .split_inclusive('\n')
+                .map(|line| color::paint_line(options.color, color::BOLD, line))
+                .collect::<String>()
+        };
+
+        if let Some((old, new)) = &self.binary {
+            let mut output = meta(&header);
+            if options.binary {
+                output.push_str(&binary::binary_patch(old, new));
+            } else {
+                let side = |entry: &Option<(PathBuf, String)>, prefix: &str, path| match entry {
+                    Some(_) => format!("{prefix}/{path}"),
+                    None => String::from("/dev/null"),
+                };
+                output.push_str(&format!(
+                    "Binary files {} and {} differ\n",
+                    side(&self.old, "a", &old_path),
+                    side(&self.new, "b", &new_path)
+                ));
+            }
+            return output;
+        }
+
+        if self.hunks.is_empty() {
+            return meta(&header);
+        }
+
+        match self.old {
+            Some(_) => header.push_str(&format!("--- a/{old_path}\n")),
+            None => header.push_str("--- /dev/null\n"),
+        }
+        match self.new {
+            Some(_) => header.push_str(&format!("+++ b/{new_path}\n")),
+            None => header.push_str("+++ /dev/null\n"),
+        }
+
+        let mut output = meta(&header);
+        for hunk in &self.hunks {
+            match &options.word_diff {
+                Some(word_diff) => output.push_str(&format!(
+                    "{}\n{}",
+                    color::paint(options.color, color::CYAN, &hunk.header()),
+                    word_diff.hunk(hunk)
+                )),
+                None if options.color => output.push_str(&colored_hunk(hunk, moved)),
+                None => output.push_str(&hunk.to_string()),
+            }
+        }
+
+        output
+    }
+}
+
+/// `--color-moved` 找出的移动过的行：在一处删除，又在另一处原样增加
+#[derive(Debug, Default)]
+struct MovedLines {
+    removed: HashSet<String>,
+    added: HashSet<String>,
+}
+
+impl MovedLines {
+    /// 空行不算移动
+    fn detect(changes: &[FileChange]) -> MovedLines {
+        let mut removed = HashSet::new();
+        let mut added = HashSet::new();
+        let lines = changes
+            .iter()
+            .flat_map(|change| &change.hunks)
+            .flat_map(|hunk| &hunk.lines)
+            .filter(|(_, line)| !line.trim().is_empty());
+        for (kind, line) in lines {
+            match kind {
+                LineKind::Removed => removed.insert(line.clone()),
+                LineKind::Added => added.insert(line.clone()),
+                LineKind::Context => false,
+            };
+        }
+
+        MovedLines {
+            removed: removed.intersection(&added).cloned().collect(),
+            added: added.intersection(&removed).cloned().collect(),
+        }
+    }
+}
+
+/// 带颜色的 hunk：头是青色，增加的行绿色，删除的行红色，移动的行用另一组颜色
+fn colored_hunk(hunk: &Hunk, moved: &MovedLines) -> String {
+    let mut output = format!("{}\n", color::paint(true, color::CYAN, &hunk.header()));
+    for (kind, line) in &hunk.lines {
+        let (prefix, paint) = match kind {
+            LineKind::Context => (' ', None),
+            LineKind::Added if moved.added.contains(line) => ('+', Some(color::BOLD_CYAN)),
+            LineKind::Added => ('+', Some(color::GREEN)),
+            LineKind::Removed if moved.removed.contains(line) => ('-', Some(color::BOLD_MAGENTA)),
+            LineKind::Removed => ('-', Some(color::RED)),
+        };
+
+        let text = format!("{prefix}{line}");
+        match paint {
+            Some(paint) => output.push_str(&color::paint_line(true, paint, &text)),
+            None => output.push_str(&text),
+        }
+        if !line.ends_with('\n') {
+            output.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+
+    output
+}
 
+/// 按格式输出一组文件修改
+pub fn format_changes(changes: &[FileChange], options: &DiffOptions) -> String {
     let mut output = String::new();
-    for k in keys {
-        let (from, to) = (t_from.get(k), t_to.get(k));
-        if from != to {
-            output.push_str(&format!("changed:{:?}\n", k))
+    match options.format {
+        DiffFormat::Patch => {
+            let moved = match options.color && options.color_moved {
+                true => MovedLines::detect(changes),
+                false => MovedLines::default(),
+            };
+            for change in changes {
+                output.push_str(&change.write_patch(options, &moved));
+            }
+        }
+        DiffFormat::Stat => output.push_str(&format_stat(changes, options.color)),
+        DiffFormat::Shortstat => output.push_str(&format_summary(changes)),
+        DiffFormat::Numstat => {
+            for change in changes {
+                let (added, removed) = match change.binary {
+                    Some(_) => (String::from("-"), String::from("-")),
+                    None => {
+                        let (added, removed) = change.stat();
+                        (added.to_string(), removed.to_string())
+                    }
+                };
+                output.push_str(&format!("{added}\t{removed}\t{}\n", change.display_path()));
+            }
+        }
+        DiffFormat::NameOnly => {
+            for change in changes {
+                output.push_str(&format!("{}\n", change.path().display()));
+            }
+        }
+        DiffFormat::NameStatus => {
+            for change in changes {
+                output.push_str(&change.kind.letter());
+                if let (ChangeKind::Renamed { .. } | ChangeKind::Copied { .. }, Some((old, _))) =
+                    (&change.kind, &change.old)
+                {
+                    output.push_str(&format!("\t{}", old.display()));
+                }
+                output.push_str(&format!("\t{}\n", change.path().display()));
+            }
         }
     }
 
     output
 }
 
+/// 和 git 一样按 80 列分配文件名和直方图的宽度，修改太多时按比例缩短直方图
+fn format_stat(changes: &[FileChange], colored: bool) -> String {
+    const STAT_WIDTH: usize = 80;
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let rows = changes
+        .iter()
+        .map(|change| (change.display_path(), change.stat(), change.binary.as_ref()))
+        .collect::<Vec<_>>();
+    let max_len = rows
+        .iter()
+        .map(|(name, _, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let max_change = rows.iter().map(|(_, (a, r), _)| a + r).max().unwrap_or(0);
+    //二进制文件显示 `Bin`，不参与直方图的比例
+    let bin_width = match rows.iter().any(|(_, _, binary)| binary.is_some()) {
+        true => 3,
+        false => 0,
+    };
+    let number_width = max_change.to_string().len().max(bin_width);
+
+    let width = STAT_WIDTH.max(16 + 6 + number_width);
+    let (mut name_width, mut graph_width) = (max_len, max_change);
+    if name_width + number_width + 6 + graph_width > width {
+        let limit = (width * 3 / 8).saturating_sub(number_width + 6).max(6);
+        graph_width = graph_width.min(limit);
+        if name_width > width - number_width - 6 - graph_width {
+            name_width = width - number_width - 6 - graph_width;
+        } else {
+            graph_width = width - number_width - 6 - name_width;
+        }
+    }
+
+    let scale = |count: usize| match count {
+        0 => 0,
+        _ => 1 + count * (graph_width - 1) / max_change,
+    };
+
+    let mut output = String::new();
+    for (name, (mut added, mut removed), binary) in rows {
+        if let Some((old, new)) = binary {
+            output.push_str(&format!(
+                " {:<name_width$} | {:>number_width$} {} -> {} bytes\n",
+                truncate_name(&name, name_width),
+                "Bin",
+                old.len(),
+                new.len()
+            ));
+            continue;
+        }
+
+        let total = added + removed;
+        if graph_width <= max_change {
+            let scaled = match scale(total) {
+                1 if added > 0 && removed > 0 => 2,
+                scaled => scaled,
+            };
+            if added < removed {
+                added = scale(added);
+                removed = scaled - added;
+            } else {
+                removed = scale(removed);
+                added = scaled - removed;
+            }
+        }
+
+        output.push_str(&format!(
+            " {:<name_width$} | {total:>number_width$}{}{}{}\n",
+            truncate_name(&name, name_width),
+            if total > 0 { " " } else { "" },
+            color::paint(colored, color::GREEN, &"+".repeat(added)),
+            color::paint(colored, color::RED, &"-".repeat(removed)),
+        ));
+    }
+    output.push_str(&format_summary(changes));
+    output
+}
+
+/// 文件名太长时只保留结尾，尽量从目录分隔处截断
+fn truncate_name(name: &str, width: usize) -> String {
+    let len = name.chars().count();
+    if len <= width {
+        return name.to_string();
+    }
+
+    let tail = name
+        .chars()
+        .skip(len - width.saturating_sub(3))
+        .collect::<String>();
+    match tail.find('/') {
+        Some(slash) => format!("...{}", &tail[slash..]),
+        None => format!("...{tail}"),
+    }
+}
+
+/// ` 2 files changed, 3 insertions(+), 1 deletion(-)`
+fn format_summary(changes: &[FileChange]) -> String {
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let (added, removed) = changes
+        .iter()
+        .map(FileChange::stat)
+        .fold((0, 0), |(a, r), (added, removed)| (a + added, r + removed));
+    let plural = |count: usize, one: &str, many: &str| match count {
+        1 => format!("{count} {one}"),
+        _ => format!("{count} {many}"),
+    };
+
+    let mut summary = format!(" {} changed", plural(changes.len(), "file", "files"));
+    if added > 0 || removed == 0 {
+        summary.push_str(&format!(
+            ", {}",
+            plural(added, "insertion(+)", "insertions(+)")
+        ));
+    }
+    if removed > 0 || added == 0 {
+        summary.push_str(&format!(
+            ", {}",
+            plural(removed, "deletion(-)", "deletions(-)")
+        ));
+    }
+    summary.push('\n');
+    summary
+}
+
+pub fn short_oid(oid: &str) -> &str {
+    &oid[..oid.len().min(7)]
+}
+
 pub fn iter_changed_files(
     t_from: &HashMap<PathBuf, String>,
     t_to: &HashMap<PathBuf, String>,
@@ -65,104 +517,484 @@ fn merge_key(trees: Vec<&HashMap<PathBuf, String>>) -> HashSet<&PathBuf> {
 }
 
 impl Ugit {
-    pub fn merge_tress(
+    //比较两个目录，同一个key指向不同内容，则发生了变化
+    pub fn diff_tree(
+        &self,
+        t_from: &HashMap<PathBuf, String>,
+        t_to: &HashMap<PathBuf, String>,
+        options: &DiffOptions,
+    ) -> String {
+        format_changes(&self.tree_changes(t_from, t_to, options), options)
+    }
+
+    /// 两棵树之间每个文件的修改，按路径排序
+    pub fn tree_changes(
         &self,
-        t_base: &HashMap<PathBuf, String>,
         t_from: &HashMap<PathBuf, String>,
         t_to: &HashMap<PathBuf, String>,
-    ) -> Result<HashMap<PathBuf, String>, DateErr> {
-        let keys = merge_key(vec![t_base, t_from, t_to]);
+        options: &DiffOptions,
+    ) -> Vec<FileChange> {
+        let mut keys = merge_key(vec![t_from, t_to])
+            .into_iter()
+            .collect::<Vec<_>>();
+        keys.sort();
 
-        let mut tree = HashMap::new();
+        let renames = options
+            .renames
+            .map(|renames| self.detect_renames(t_from, t_to, &renames))
+            .unwrap_or_default();
+        //复制的来源还在，它自己的修改照常输出
+        let mut renamed = HashSet::new();
+        for rename in &renames {
+            renamed.insert(&rename.to);
+            if !rename.copy {
+                renamed.insert(&rename.from);
+            }
+        }
+
+        let mut changes = vec![];
         for k in keys {
-            let (base, from, other) = (t_base.get(k), t_from.get(k), t_to.get(k));
-            match self.merge_blobs(
-                base.map(String::as_str),
-                from.map(String::as_str),
-                other.map(String::as_str),
-            ) {
-                Ok(content) => {
-                    tree.insert(k.clone(), content);
-                }
-                Err(err) => return Err(err),
+            let entry = |tree: &HashMap<PathBuf, String>, path: &PathBuf| {
+                tree.get(path).map(|oid| (path.clone(), oid.clone()))
+            };
+
+            if let Some(rename) = renames.iter().find(|rename| &rename.to == k) {
+                let kind = match rename.copy {
+                    true => ChangeKind::Copied {
+                        similarity: rename.similarity,
+                    },
+                    false => ChangeKind::Renamed {
+                        similarity: rename.similarity,
+                    },
+                };
+                let old = entry(t_from, &rename.from);
+                changes.push(self.file_change(kind, old, entry(t_to, k), options));
+                continue;
             }
+
+            let (from, to) = (t_from.get(k), t_to.get(k));
+            if from == to || renamed.contains(k) {
+                continue;
+            }
+            let kind = match (from, to) {
+                (None, _) => ChangeKind::Added,
+                (_, None) => ChangeKind::Deleted,
+                _ => ChangeKind::Modified,
+            };
+            changes.push(self.file_change(kind, entry(t_from, k), entry(t_to, k), options));
         }
 
-        Ok(tree)
+        changes
     }
 
-    pub fn merge_blobs(
+    /// `diff` 命令比较的两边，路径用 `pathspec` 过滤，`A...B` 比较 A 和 B 的合并基础与 B
+    pub fn diff_revs(
         &self,
-        o_base: Option<&str>,
-        o_head: Option<&str>,
-        o_other: Option<&str>,
-    ) -> Result<String, DateErr> {
-        let mut f_base = match NamedTempFile::new() {
-            Ok(f_base) => f_base,
-            Err(err) => return Err(DateErr::Io(err)),
+        revs: &[String],
+        cached: bool,
+        pathspec: &Pathspec,
+        options: &DiffOptions,
+    ) -> Result<Vec<FileChange>, DateErr> {
+        let (t_from, t_to) = match revs {
+            [] if cached => (self.get_head_tree(), self.get_index_tree()),
+            [] => (self.get_index_tree(), self.get_tracked_working_tree()),
+            [rev] if rev.contains("..") => {
+                //`A..B` 排除 A，`A...B` 排除合并基础，右边是不在左边的那个提交
+                let revisions = self.parse_revisions(std::slice::from_ref(rev))?;
+                let to = revisions
+                    .include
+                    .iter()
+                    .find(|oid| !revisions.left.contains(oid))
+                    .or(revisions.include.first());
+                let (from, to) = match (revisions.exclude.first(), to) {
+                    (Some(from), Some(to)) => (from, to),
+                    _ => return Err(DateErr::Err(format!("{rev}: no merge base"))),
+                };
+                (self.rev_tree(from)?, self.rev_tree(to)?)
+            }
+            [rev] if cached => (self.rev_tree(rev)?, self.get_index_tree()),
+            [rev] => (self.rev_tree(rev)?, self.get_tracked_working_tree()),
+            [a, b] => (self.rev_tree(a)?, self.rev_tree(b)?),
+            _ => return Err(DateErr::Err(String::from("too many revisions"))),
         };
 
-        let mut f_head = match NamedTempFile::new() {
-            Ok(f_head) => f_head,
-            Err(err) => return Err(DateErr::Io(err)),
+        let limit = |tree: HashMap<PathBuf, String>| {
+            tree.into_iter()
+                .filter(|(path, _)| pathspec.matches(path))
+                .collect::<HashMap<_, _>>()
         };
+        Ok(self.tree_changes(&limit(t_from), &limit(t_to), options))
+    }
+
+    fn rev_tree(&self, rev: &str) -> Result<HashMap<PathBuf, String>, DateErr> {
+        self.get_commit_tree(&self.resolve_commit(rev)?)
+            .ok_or_else(|| DateErr::Err(format!("bad revision '{rev}'")))
+    }
 
-        let mut f_other = match NamedTempFile::new() {
-            Ok(f_ohter) => f_ohter,
-            Err(err) => return Err(DateErr::Io(err)),
+    /// 工作区里已经跟踪的文件，未跟踪的文件不参与 diff
+    fn get_tracked_working_tree(&self) -> HashMap<PathBuf, String> {
+        let index = self.get_index_tree();
+        self.get_working_tree()
+            .into_iter()
+            .filter(|(path, _)| index.contains_key(path))
+            .collect()
+    }
+
+    /// 读出两边的内容算出 hunk
+    fn file_change(
+        &self,
+        kind: ChangeKind,
+        old: Option<(PathBuf, String)>,
+        new: Option<(PathBuf, String)>,
+        options: &DiffOptions,
+    ) -> FileChange {
+        let read = |entry: &Option<(PathBuf, String)>| match entry {
+            Some((_, oid)) => self
+                .get_object_bytes(oid, DataType::Blob)
+                .unwrap_or_else(|err| {
+                    eprintln!("diff, get_object failed, oid:{:?}, err:{:?}", oid, err);
+                    vec![]
+                }),
+            None => vec![],
         };
 
-        for (content, f) in [
-            (o_base, &mut f_base),
-            (o_head, &mut f_head),
-            (o_other, &mut f_other),
-        ] {
-            let content = match content {
-                Some(content) => content,
-                None => continue,
+        let (from, to) = (read(&old), read(&new));
+        if binary::is_binary(&from) || binary::is_binary(&to) {
+            let binary = (from != to).then_some((from, to));
+            return FileChange {
+                kind,
+                old,
+                new,
+                hunks: vec![],
+                binary,
+            };
+        }
+
+        let (from, to) = (String::from_utf8_lossy(&from), String::from_utf8_lossy(&to));
+        let old_lines = line_diff::split_lines(&from);
+        let new_lines = line_diff::split_lines(&to);
+        let edits = line_diff::diff_lines_with(
+            &old_lines,
+            &new_lines,
+            options.algorithm,
+            options.whitespace,
+        );
+        let mut hunks = line_diff::make_hunks(&old_lines, &new_lines, &edits, options.context);
+        if options.ignore_blank_lines {
+            hunks.retain(|hunk| {
+                hunk.lines
+                    .iter()
+                    .any(|(kind, line)| *kind != LineKind::Context && !line.trim().is_empty())
+            });
+        }
+
+        FileChange {
+            kind,
+            old,
+            new,
+            hunks,
+            binary: None,
+        }
+    }
+
+    /// 提交相对于第一个父提交的修改，根提交和空树比较
+    pub fn diff_commit(&self, commit: &Commit, options: &DiffOptions) -> String {
+        self.diff_commit_paths(commit, &Pathspec::default(), options)
+    }
+
+    /// 同 [`Ugit::diff_commit`]，只比较 `pathspec` 匹配的文件
+    pub fn diff_commit_paths(
+        &self,
+        commit: &Commit,
+        pathspec: &Pathspec,
+        options: &DiffOptions,
+    ) -> String {
+        let limit = |tree: HashMap<PathBuf, String>| {
+            tree.into_iter()
+                .filter(|(path, _)| pathspec.matches(path))
+                .collect::<HashMap<_, _>>()
+        };
+        let t_from = commit
+            .parents
+            .first()
+            .and_then(|parent| self.get_commit_tree(parent))
+            .unwrap_or_default();
+        let t_to = commit
+            .tree
+            .as_ref()
+            .and_then(|tree| self.get_tree_in_base(tree))
+            .unwrap_or_default();
+
+        self.diff_tree(&limit(t_from), &limit(t_to), options)
+    }
+
+    pub fn merge_tress(
+        &self,
+        t_base: &HashMap<PathBuf, String>,
+        t_from: &HashMap<PathBuf, String>,
+        t_to: &HashMap<PathBuf, String>,
+        options: &MergeOptions,
+    ) -> Result<MergedTree, DateErr> {
+        let renamed = self.follow_renames(t_base, t_from, t_to, options);
+        let (t_base, t_from, t_to) = (&renamed.base, &renamed.ours, &renamed.theirs);
+
+        let mut keys = merge_key(vec![t_base, t_from, t_to])
+            .into_iter()
+            .collect::<Vec<_>>();
+        keys.sort();
+
+        let mut merged = MergedTree::default();
+        merged.conflicts.extend(renamed.conflicts.clone());
+        for k in keys {
+            let (base, from, other) = (t_base.get(k), t_from.get(k), t_to.get(k));
+            let stages = Stages {
+                base: base.cloned(),
+                ours: from.cloned(),
+                theirs: other.cloned(),
             };
 
-            match self.get_object(content, data::DataType::None) {
-                Ok(content) => {
-                    if let Err(err) = f.as_file_mut().write_all(content.as_bytes()) {
-                        eprintln!(
-                            "merge_blogs, write temp file failed, oid:{:?}, err:{:?}",
-                            content, err
-                        );
+            let oid = match (base, from, other) {
+                _ if from == other || base == other => from.cloned(),
+                _ if base == from => other.cloned(),
+                //一边删除，另一边修改，保留修改的版本
+                (Some(_), None, Some(_)) | (Some(_), Some(_), None) => {
+                    let (deleted, modified) = match from {
+                        Some(_) => (&options.theirs_label, &options.ours_label),
+                        None => (&options.ours_label, &options.theirs_label),
+                    };
+                    options.report(format!(
+                        "CONFLICT (modify/delete): {} deleted in {deleted} and modified in {modified}. Version {modified} of {} left in tree.",
+                        k.display(),
+                        k.display()
+                    ));
+                    merged.conflicts.insert(k.clone(), stages);
+                    from.or(other).cloned()
+                }
+                //二进制文件没法按行合并，保留我们的版本
+                (_, Some(_), Some(_))
+                    if [base, from, other]
+                        .into_iter()
+                        .any(|oid| self.is_binary_blob(oid)) =>
+                {
+                    options.report(format!("Auto-merging {}", k.display()));
+                    options.report(format!(
+                        "warning: Cannot merge binary files: {} ({} vs. {})",
+                        k.display(),
+                        options.ours_label,
+                        options.theirs_label
+                    ));
+                    options.report(format!(
+                        "CONFLICT (content): Merge conflict in {}",
+                        k.display()
+                    ));
+                    merged.conflicts.insert(k.clone(), stages);
+                    from.cloned()
+                }
+                _ => {
+                    options.report(format!("Auto-merging {}", k.display()));
+                    let result = self.merge_blobs(
+                        base.map(String::as_str),
+                        from.map(String::as_str),
+                        other.map(String::as_str),
+                        options,
+                    )?;
+
+                    if !result.is_clean() {
+                        let kind = if base.is_none() { "add/add" } else { "content" };
+                        options.report(format!(
+                            "CONFLICT ({kind}): Merge conflict in {}",
+                            k.display()
+                        ));
+                        merged.conflicts.insert(k.clone(), stages);
                     }
+                    Some(self.hash(result.content().as_bytes(), DataType::Blob)?)
                 }
-                Err(err) => {
-                    eprintln!(
-                        "merge_blogs, get_object failed, oid:{:?}, err:{:?}",
-                        content, err
+            };
+
+            if let Some(oid) = oid {
+                merged.tree.insert(k.clone(), oid);
+            }
+        }
+
+        self.resolve_file_directory(&mut merged, t_from, options);
+        Ok(merged)
+    }
+
+    /// 一边改名另一边修改时，把另一边和共同祖先里的文件也挪到新名字下，修改才能合并过去
+    fn follow_renames(
+        &self,
+        t_base: &HashMap<PathBuf, String>,
+        t_from: &HashMap<PathBuf, String>,
+        t_to: &HashMap<PathBuf, String>,
+        options: &MergeOptions,
+    ) -> RenamedTrees {
+        let rename_options = RenameOptions::default();
+        let ours_renames = self.detect_renames(t_base, t_from, &rename_options);
+        let theirs_renames = self.detect_renames(t_base, t_to, &rename_options);
+
+        let (mut base, mut ours, mut theirs) = (t_base.clone(), t_from.clone(), t_to.clone());
+        let mut conflicts = HashMap::new();
+        let move_file = |tree: &mut HashMap<PathBuf, String>, rename: &Rename| {
+            if let Some(oid) = tree.remove(&rename.from) {
+                tree.insert(rename.to.clone(), oid);
+            }
+        };
+
+        for rename in &ours_renames {
+            match theirs_renames
+                .iter()
+                .find(|other| other.from == rename.from)
+            {
+                Some(other) if other.to == rename.to => move_file(&mut base, rename),
+                Some(other) => {
+                    options.report(format!(
+                        "CONFLICT (rename/rename): {} renamed to {} in {} and to {} in {}.",
+                        rename.from.display(),
+                        rename.to.display(),
+                        options.ours_label,
+                        other.to.display(),
+                        options.theirs_label
+                    ));
+                    let o_base = t_base.get(&rename.from).cloned();
+                    conflicts.insert(
+                        rename.to.clone(),
+                        Stages {
+                            base: o_base.clone(),
+                            ours: t_from.get(&rename.to).cloned(),
+                            theirs: None,
+                        },
+                    );
+                    conflicts.insert(
+                        other.to.clone(),
+                        Stages {
+                            base: o_base,
+                            ours: None,
+                            theirs: t_to.get(&other.to).cloned(),
+                        },
                     );
                 }
+                None if theirs.contains_key(&rename.from) && !theirs.contains_key(&rename.to) => {
+                    move_file(&mut theirs, rename);
+                    move_file(&mut base, rename);
+                }
+                None => {}
             }
         }
 
-        let (f_base_path_str, f_head_path_str, f_other_path_str) = (
-            f_base.path().to_string_lossy(),
-            f_head.path().to_string_lossy(),
-            f_other.path().to_string_lossy(),
-        );
+        for rename in &theirs_renames {
+            let renamed_by_us = ours_renames.iter().any(|other| other.from == rename.from);
+            if !renamed_by_us && ours.contains_key(&rename.from) && !ours.contains_key(&rename.to) {
+                move_file(&mut ours, rename);
+                move_file(&mut base, rename);
+            }
+        }
 
-        match Command::new("diff3")
-            .args([
-                "-m",
-                "-L",
-                "HEAD",
-                &f_head_path_str,
-                "-L",
-                "BASE",
-                &f_base_path_str,
-                "-L",
-                "MERGE_HEAD",
-                &f_other_path_str,
-            ])
-            .output()
-        {
-            Ok(output) => Ok(String::from_utf8_lossy(output.stdout.as_slice()).to_string()),
-            Err(err) => Err(DateErr::Io(err)),
+        RenamedTrees {
+            base,
+            ours,
+            theirs,
+            conflicts,
         }
     }
+
+    /// 一边是文件另一边是目录时，目录保留原名，文件改名为 `path~分支名`
+    fn resolve_file_directory(
+        &self,
+        merged: &mut MergedTree,
+        t_from: &HashMap<PathBuf, String>,
+        options: &MergeOptions,
+    ) {
+        let dirs = merged
+            .tree
+            .keys()
+            .flat_map(|path| path.ancestors().skip(1))
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map(Path::to_path_buf)
+            .collect::<HashSet<_>>();
+
+        let mut files = merged
+            .tree
+            .keys()
+            .filter(|path| dirs.contains(*path))
+            .cloned()
+            .collect::<Vec<_>>();
+        files.sort();
+
+        for file in files {
+            let oid = match merged.tree.remove(&file) {
+                Some(oid) => oid,
+                None => continue,
+            };
+
+            //文件来自哪一边，目录就来自另一边
+            let (file_side, dir_side) = if t_from.get(&file) == Some(&oid) {
+                (&options.ours_label, &options.theirs_label)
+            } else {
+                (&options.theirs_label, &options.ours_label)
+            };
+
+            let mut name = file.as_os_str().to_owned();
+            name.push(format!("~{}", file_side.replace('/', "_")));
+            let renamed = PathBuf::from(name);
+            options.report(format!(
+                "CONFLICT (file/directory): There is a directory with name {} in {dir_side}. Adding {} as {}",
+                file.display(),
+                file.display(),
+                renamed.display()
+            ));
+
+            let stages = merged.conflicts.remove(&file).unwrap_or_else(|| {
+                let mut stages = Stages::default();
+                if file_side == &options.ours_label {
+                    stages.ours = Some(oid.clone());
+                } else {
+                    stages.theirs = Some(oid.clone());
+                }
+                stages
+            });
+            merged.conflicts.insert(renamed.clone(), stages);
+            merged.tree.insert(renamed, oid);
+        }
+    }
+
+    fn is_binary_blob(&self, oid: Option<&String>) -> bool {
+        oid.and_then(|oid| self.get_object_bytes(oid, DataType::Blob).ok())
+            .is_some_and(|bytes| binary::is_binary(&bytes))
+    }
+
+    pub fn merge_blobs(
+        &self,
+        o_base: Option<&str>,
+        o_head: Option<&str>,
+        o_other: Option<&str>,
+        options: &MergeOptions,
+    ) -> Result<MergeResult, DateErr> {
+        let read = |oid: Option<&str>| match oid {
+            Some(oid) => self.get_object(oid, DataType::Blob),
+            None => Ok(String::new()),
+        };
+
+        Ok(merge::merge3(
+            &read(o_base)?,
+            &read(o_head)?,
+            &read(o_other)?,
+            options,
+        ))
+    }
+}
+
+/// 按改名调整过路径的三棵树，两边改成不同名字的文件记为冲突
+struct RenamedTrees {
+    base: HashMap<PathBuf, String>,
+    ours: HashMap<PathBuf, String>,
+    theirs: HashMap<PathBuf, String>,
+    conflicts: HashMap<PathBuf, Stages>,
+}
+
+/// 合并两棵树的结果
+#[derive(Debug, Default)]
+pub struct MergedTree {
+    pub tree: HashMap<PathBuf, String>,
+    /// 有冲突的文件及其各方版本，冲突标记已经写进了 tree 里对应的对象
+    pub conflicts: HashMap<PathBuf, Stages>,
 }
@@ -0,0 +1,312 @@
+use crate::color;
+
+/// 各条线轮流使用的颜色
+const LANE_COLORS: [&str; 6] = [
+    color::RED,
+    color::GREEN,
+    color::YELLOW,
+    color::BLUE,
+    color::BOLD_MAGENTA,
+    color::CYAN,
+];
+
+/// 一条线：它等待的提交和它的颜色
+#[derive(Clone, Debug)]
+struct Lane {
+    oid: String,
+    color: usize,
+}
+
+/// `log --graph` 左边的提交图，提交要按子提交在前的拓扑顺序放入
+#[derive(Debug, Default)]
+pub struct Graph {
+    lanes: Vec<Lane>,
+    next_color: usize,
+    color: bool,
+}
+
+/// 一条线从哪一列到哪一列
+struct Edge {
+    from: usize,
+    to: usize,
+    color: usize,
+}
+
+impl Graph {
+    pub fn new(color: bool) -> Graph {
+        Graph {
+            color,
+            ..Default::default()
+        }
+    }
+
+    /// 放入下一个提交，`text` 的每一行加上图的前缀
+    pub fn commit(&mut self, oid: &str, parents: &[String], text: &str) -> String {
+        let column = match self.lanes.iter().position(|lane| lane.oid == oid) {
+            Some(column) => column,
+            None => {
+                let lane = self.new_lane(oid);
+                self.lanes.push(lane);
+                self.lanes.len() - 1
+            }
+        };
+        let width = self.lanes.len();
+        let commit_color = self.lanes[column].color;
+
+        //提交所在的线换成它的父提交，合并提交的其余父提交在右边新开线，
+        //已经有线在等的父提交直接连过去
+        let mut expanded = vec![];
+        for (index, lane) in self.lanes.iter().enumerate() {
+            if index != column {
+                expanded.push((lane.clone(), index, false));
+                continue;
+            }
+
+            for (number, parent) in parents.iter().enumerate() {
+                let color = match number {
+                    0 => commit_color,
+                    _ => self.next_color + number - 1,
+                };
+                let waiting = self
+                    .lanes
+                    .iter()
+                    .enumerate()
+                    .any(|(other, lane)| other != column && lane.oid == *parent);
+                let lane = Lane {
+                    oid: parent.clone(),
+                    color,
+                };
+                expanded.push((lane, column, number > 0 && waiting));
+            }
+        }
+        self.next_color += parents.len().saturating_sub(1);
+
+        //等待同一个提交的线合成一条
+        let mut lanes: Vec<Lane> = vec![];
+        for (lane, _, join) in &expanded {
+            if !join && lanes.iter().all(|other| other.oid != lane.oid) {
+                lanes.push(lane.clone());
+            }
+        }
+        let edges = expanded
+            .into_iter()
+            .map(|(lane, from, _)| Edge {
+                from,
+                to: lanes
+                    .iter()
+                    .position(|other| other.oid == lane.oid)
+                    .unwrap_or(from),
+                color: lane.color,
+            })
+            .collect();
+        let old_lanes = std::mem::replace(&mut self.lanes, lanes);
+        let width = width.max(self.lanes.len()).max(1) * 2;
+
+        let mut rows = vec![pad_to(
+            self.row(&old_lanes, Some(column)),
+            old_lanes.len(),
+            width,
+        )];
+        rows.extend(self.connecting_rows(edges, width));
+
+        let mut output = String::new();
+        let mut rows = rows.into_iter();
+        for line in text.lines() {
+            let prefix = rows.next().unwrap_or_else(|| self.padding(width));
+            output.push_str(&join_prefix(&prefix, line));
+        }
+        for prefix in rows {
+            output.push_str(&join_prefix(&prefix, ""));
+        }
+
+        output
+    }
+
+    /// 两个提交之间的其它输出，每一行前面都是 `|`
+    pub fn pad(&self, text: &str) -> String {
+        let width = self.lanes.len().max(1) * 2;
+        text.lines()
+            .map(|line| join_prefix(&self.padding(width), line))
+            .collect()
+    }
+
+    fn new_lane(&mut self, oid: &str) -> Lane {
+        self.next_color += 1;
+        Lane {
+            oid: oid.to_string(),
+            color: self.next_color - 1,
+        }
+    }
+
+    fn paint(&self, color: usize, text: &str) -> String {
+        color::paint(self.color, LANE_COLORS[color % LANE_COLORS.len()], text)
+    }
+
+    /// 提交所在的行：这个提交画成 `*`，其它线画成 `|`
+    fn row(&self, lanes: &[Lane], commit: Option<usize>) -> String {
+        let mut row = String::new();
+        for (index, lane) in lanes.iter().enumerate() {
+            match commit == Some(index) {
+                true => row.push('*'),
+                false => row.push_str(&self.paint(lane.color, "|")),
+            }
+            row.push(' ');
+        }
+
+        row
+    }
+
+    fn padding(&self, width: usize) -> String {
+        pad_to(self.row(&self.lanes, None), self.lanes.len(), width)
+    }
+
+    /// 从提交所在的行连到下一行的各条线，每一行每条线最多移动一列
+    fn connecting_rows(&self, edges: Vec<Edge>, width: usize) -> Vec<String> {
+        let mut positions = edges.iter().map(|edge| edge.from).collect::<Vec<_>>();
+        let mut rows = vec![];
+        while edges
+            .iter()
+            .zip(&positions)
+            .any(|(edge, &position)| edge.to != position)
+        {
+            let mut cells = vec![String::from(" "); width];
+            for (edge, position) in edges.iter().zip(positions.iter_mut()) {
+                let (index, symbol) = match edge.to.cmp(position) {
+                    std::cmp::Ordering::Equal => (*position * 2, "|"),
+                    std::cmp::Ordering::Greater => {
+                        *position += 1;
+                        (*position * 2 - 1, "\\")
+                    }
+                    std::cmp::Ordering::Less => {
+                        *position -= 1;
+                        (*position * 2 + 1, "/")
+                    }
+                };
+                if let Some(cell) = cells.get_mut(index) {
+                    *cell = self.paint(edge.color, symbol);
+                }
+            }
+            rows.push(cells.concat());
+        }
+
+        rows
+    }
+}
+
+/// `lanes` 条线的行补上空格，凑够 `width` 列
+fn pad_to(row: String, lanes: usize, width: usize) -> String {
+    format!("{row}{}", " ".repeat(width.saturating_sub(lanes * 2)))
+}
+
+/// 前缀和内容拼成一行，空行不留前缀后面的空格
+fn join_prefix(prefix: &str, line: &str) -> String {
+    match line.is_empty() {
+        true => format!("{}\n", prefix.trim_end()),
+        false => format!("{prefix}{line}\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按顺序放入 `(提交, 父提交)`，每个提交输出一行名字
+    fn draw(commits: &[(&str, &[&str])]) -> String {
+        let mut graph = Graph::new(false);
+        commits
+            .iter()
+            .map(|(oid, parents)| {
+                let parents = parents
+                    .iter()
+                    .map(|parent| parent.to_string())
+                    .collect::<Vec<_>>();
+                graph.commit(oid, &parents, oid)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn linear() {
+        let graph = draw(&[("c", &["b"]), ("b", &["a"]), ("a", &[])]);
+        assert_eq!(graph, "* c\n* b\n* a\n");
+    }
+
+    #[test]
+    fn merge() {
+        //第二个父提交在右边新开一条线，两条线在共同的父提交处合回来
+        let graph = draw(&[
+            ("M", &["D", "E"]),
+            ("D", &["B"]),
+            ("B", &["A"]),
+            ("E", &["C"]),
+            ("C", &["A"]),
+            ("A", &[]),
+        ]);
+        assert_eq!(
+            graph,
+            "*   M\n\
+             |\\\n\
+             * | D\n\
+             * | B\n\
+             | * E\n\
+             | * C\n\
+             |/\n\
+             * A\n"
+        );
+
+        //两边的提交交错时各自留在自己的线上
+        let graph = draw(&[
+            ("M", &["D", "E"]),
+            ("E", &["C"]),
+            ("D", &["B"]),
+            ("C", &["A"]),
+            ("B", &["A"]),
+            ("A", &[]),
+        ]);
+        assert_eq!(
+            graph,
+            "*   M\n\
+             |\\\n\
+             | * E\n\
+             * | D\n\
+             | * C\n\
+             * | B\n\
+             |/\n\
+             * A\n"
+        );
+    }
+
+    #[test]
+    fn criss_cross() {
+        //M2 和 S2 互相合并了对方的第一个提交
+        let graph = draw(&[
+            ("X", &["M2", "S2"]),
+            ("M2", &["M1", "S1"]),
+            ("S2", &["S1", "M1"]),
+            ("S1", &["B"]),
+            ("M1", &["B"]),
+            ("B", &[]),
+        ]);
+        assert_eq!(
+            graph,
+            "*   X\n\
+             |\\\n\
+             * |   M2\n\
+             |\\ \\\n\
+             | | * S2\n\
+             | |/\n\
+             |/|\n\
+             | * S1\n\
+             * | M1\n\
+             |/\n\
+             * B\n"
+        );
+    }
+
+    #[test]
+    fn pad_between_commits() {
+        let mut graph = Graph::new(false);
+        graph.commit("M", &[String::from("A"), String::from("B")], "M");
+        assert_eq!(graph.pad("\nbody\n"), "| |\n| | body\n");
+    }
+}
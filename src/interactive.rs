@@ -0,0 +1,399 @@
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use tempfile::NamedTempFile;
+
+use crate::{
+    data::{self, DataType, Ugit},
+    diff,
+    line_diff::{self, Algorithm, Hunk, LineKind},
+    pathspec::Pathspec,
+};
+
+/// 交互式选择 hunk 的结果
+struct Selection {
+    selected: Vec<Hunk>,
+    rejected: Vec<Hunk>,
+    quit: bool,
+}
+
+impl Ugit {
+    /// `add -p`，逐个 hunk 询问是否把工作区的修改放进索引
+    pub fn add_patch(&self, filenames: &[String]) {
+        let mut index = match self.get_index() {
+            Ok(index) => index,
+            Err(err) => {
+                eprintln!("get_index error:{:?}", err);
+                return;
+            }
+        };
+
+        let pathspec = Pathspec::new(filenames);
+        let index_tree = self.get_index_tree();
+        let mut changes = diff::iter_changed_files(&index_tree, &self.get_working_tree())
+            .into_iter()
+            .filter(|(path, action)| action != "new file" && pathspec.matches(path))
+            .collect::<Vec<_>>();
+        changes.sort();
+
+        if changes.is_empty() {
+            println!("No changes.");
+            return;
+        }
+
+        for (path, action) in changes {
+            let key = path.to_string_lossy().to_string();
+            let old = match index_tree
+                .get(&path)
+                .map(|oid| self.get_object(oid, DataType::Blob))
+            {
+                Some(Ok(old)) => old,
+                _ => {
+                    eprintln!("add -p, read index blob of {:?} failed", path);
+                    continue;
+                }
+            };
+
+            if action == "deleted" {
+                print_file_header(&path);
+                match ask_file("Stage deletion", "stage") {
+                    Some(true) => {
+                        index.remove(&key);
+                        continue;
+                    }
+                    Some(false) => continue,
+                    None => break,
+                }
+            }
+
+            let new = match fs::read_to_string(&path) {
+                Ok(new) => new,
+                Err(err) => {
+                    eprintln!("add -p, read {:?} error:{:?}", path, err);
+                    continue;
+                }
+            };
+
+            let selection = select_hunks(&path, &old, &new, "Stage", true);
+            if !selection.selected.is_empty() {
+                let old_lines = line_diff::split_lines(&old);
+                let hunks = selection.selected.iter().collect::<Vec<_>>();
+                let content = line_diff::apply_hunks(&old_lines, &hunks);
+                match self.hash(content.as_bytes(), DataType::Blob) {
+                    Ok(oid) => {
+                        index.insert(key, oid);
+                    }
+                    Err(err) => eprintln!("add -p, hash {:?} error:{:?}", path, err),
+                }
+            }
+
+            if selection.quit {
+                break;
+            }
+        }
+
+        if let Err(err) = self.write_index(&index) {
+            eprintln!("add -p, update index error:{:?}", err);
+        }
+    }
+
+    /// `reset -p`，逐个 hunk 询问是否把已暂存的修改从索引中撤回
+    pub fn reset_patch(&self, oid: &str, filenames: &[String]) {
+        let mut index = match self.get_index() {
+            Ok(index) => index,
+            Err(err) => {
+                eprintln!("get_index error:{:?}", err);
+                return;
+            }
+        };
+
+        let target = match self.get_commit_tree(oid) {
+            Some(target) => target,
+            None if oid == data::HEAD => Default::default(),
+            None => {
+                eprintln!("reset not exists commit, oid:{}", oid);
+                return;
+            }
+        };
+
+        let pathspec = Pathspec::new(filenames);
+        let index_tree = self.get_index_tree();
+        let mut changes = diff::iter_changed_files(&target, &index_tree)
+            .into_iter()
+            .filter(|(path, _)| pathspec.matches(path))
+            .collect::<Vec<_>>();
+        changes.sort();
+
+        if changes.is_empty() {
+            println!("No changes.");
+            return;
+        }
+
+        for (path, action) in changes {
+            let key = path.to_string_lossy().to_string();
+            if action != "modified" {
+                print_file_header(&path);
+                let question = match action.as_str() {
+                    "deleted" => "Unstage deletion",
+                    _ => "Unstage addition",
+                };
+
+                match ask_file(question, "unstage") {
+                    Some(true) => match target.get(&path) {
+                        Some(oid) => {
+                            index.insert(key, oid.clone());
+                        }
+                        None => {
+                            index.remove(&key);
+                        }
+                    },
+                    Some(false) => {}
+                    None => break,
+                }
+                continue;
+            }
+
+            let (old, new) = match (
+                self.get_object(&target[&path], DataType::Blob),
+                self.get_object(&index_tree[&path], DataType::Blob),
+            ) {
+                (Ok(old), Ok(new)) => (old, new),
+                _ => {
+                    eprintln!("reset -p, read blobs of {:?} failed", path);
+                    continue;
+                }
+            };
+
+            let selection = select_hunks(&path, &old, &new, "Unstage", false);
+            if !selection.selected.is_empty() {
+                //撤回选中的 hunk，等于在旧内容上只应用没选中的 hunk
+                let old_lines = line_diff::split_lines(&old);
+                let hunks = selection.rejected.iter().collect::<Vec<_>>();
+                let content = line_diff::apply_hunks(&old_lines, &hunks);
+                match self.hash(content.as_bytes(), DataType::Blob) {
+                    Ok(oid) => {
+                        index.insert(key, oid);
+                    }
+                    Err(err) => eprintln!("reset -p, hash {:?} error:{:?}", path, err),
+                }
+            }
+
+            if selection.quit {
+                break;
+            }
+        }
+
+        if let Err(err) = self.write_index(&index) {
+            eprintln!("reset -p, update index error:{:?}", err);
+        }
+    }
+}
+
+fn print_file_header(path: &Path) {
+    let path = path.display();
+    println!("diff --git a/{path} b/{path}");
+    println!("--- a/{path}");
+    println!("+++ b/{path}");
+}
+
+/// 打印提示并读取一行回答，输入结束时返回 None
+fn prompt(question: &str) -> Option<String> {
+    print!("{question}");
+    if let Err(err) = io::stdout().flush() {
+        eprintln!("flush stdout error:{:?}", err);
+    }
+
+    let mut answer = String::new();
+    match io::stdin().read_line(&mut answer) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(answer.trim().to_lowercase()),
+    }
+}
+
+/// 询问整个文件的删除或新增，回答不认识时重新问，`q` 或输入结束时返回 None
+fn ask_file(question: &str, verb: &str) -> Option<bool> {
+    loop {
+        match prompt(&format!("{question} [y,n,q,?]? ")).as_deref() {
+            Some("y") => return Some(true),
+            Some("n") => return Some(false),
+            Some("q") | None => return None,
+            _ => {
+                println!("y - {verb} this change");
+                println!("n - do not {verb} this change");
+                println!("q - quit; do not {verb} this change or any of the remaining ones");
+                println!("? - print help");
+            }
+        }
+    }
+}
+
+fn select_hunks(path: &Path, old: &str, new: &str, verb: &str, allow_edit: bool) -> Selection {
+    let old_lines = line_diff::split_lines(old);
+    let new_lines = line_diff::split_lines(new);
+    let edits = line_diff::diff_lines(&old_lines, &new_lines, Algorithm::default());
+    let mut hunks = line_diff::make_hunks(&old_lines, &new_lines, &edits, 3);
+
+    print_file_header(path);
+
+    let mut selection = Selection {
+        selected: vec![],
+        rejected: vec![],
+        quit: false,
+    };
+
+    let mut i = 0;
+    while i < hunks.len() {
+        print!("{}", hunks[i]);
+
+        let can_split = hunks[i].split().len() > 1;
+        let mut options = String::from("y,n,q,a,d");
+        if can_split {
+            options.push_str(",s");
+        }
+        if allow_edit {
+            options.push_str(",e");
+        }
+
+        let question = format!(
+            "({}/{}) {verb} this hunk [{options},?]? ",
+            i + 1,
+            hunks.len()
+        );
+        match prompt(&question).as_deref() {
+            Some("y") => {
+                selection.selected.push(hunks[i].clone());
+                i += 1;
+            }
+            Some("n") => {
+                selection.rejected.push(hunks[i].clone());
+                i += 1;
+            }
+            Some("a") => {
+                selection.selected.extend(hunks.drain(i..));
+            }
+            Some("d") => {
+                selection.rejected.extend(hunks.drain(i..));
+            }
+            Some("q") | None => {
+                selection.rejected.extend(hunks.drain(i..));
+                selection.quit = true;
+            }
+            Some("s") if can_split => {
+                let parts = hunks[i].split();
+                println!("Split into {} hunks.", parts.len());
+                hunks.splice(i..=i, parts);
+            }
+            Some("e") if allow_edit => match edit_hunk(&hunks[i], &old_lines) {
+                Ok(edited) => {
+                    selection.selected.push(edited);
+                    i += 1;
+                }
+                Err(err) => eprintln!("{err}"),
+            },
+            _ => print_help(verb, can_split, allow_edit),
+        }
+    }
+
+    selection.selected.sort_by_key(|hunk| hunk.old_start);
+    selection.rejected.sort_by_key(|hunk| hunk.old_start);
+    selection
+}
+
+fn print_help(verb: &str, can_split: bool, allow_edit: bool) {
+    let verb = verb.to_lowercase();
+    println!("y - {verb} this hunk");
+    println!("n - do not {verb} this hunk");
+    println!("q - quit; do not {verb} this hunk or any of the remaining ones");
+    println!("a - {verb} this hunk and all later hunks in the file");
+    println!("d - do not {verb} this hunk or any of the later hunks in the file");
+    if can_split {
+        println!("s - split the current hunk into smaller hunks");
+    }
+    if allow_edit {
+        println!("e - manually edit the current hunk");
+    }
+    println!("? - print help");
+}
+
+/// 用编辑器修改 hunk，修改后的内容必须还能对上旧内容
+fn edit_hunk(hunk: &Hunk, old_lines: &[&str]) -> Result<Hunk, String> {
+    let mut file = NamedTempFile::new().map_err(|err| format!("create temp file:{err}"))?;
+    let text = format!(
+        "# Manual hunk edit mode -- see bottom for a quick guide.\n{hunk}# ---\n\
+         # To remove '-' lines, make them ' ' lines (context).\n\
+         # To remove '+' lines, delete them.\n\
+         # Lines starting with # will be removed.\n"
+    );
+    file.write_all(text.as_bytes())
+        .map_err(|err| format!("write temp file:{err}"))?;
+
+    let editor = ["RGIT_EDITOR", "VISUAL", "EDITOR"]
+        .iter()
+        .find_map(|name| env::var(name).ok().filter(|editor| !editor.is_empty()))
+        .unwrap_or_else(|| String::from("vi"));
+    let path = PathBuf::from(file.path());
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$1\""))
+        .arg(&editor)
+        .arg(&path)
+        .status()
+        .map_err(|err| format!("run editor {editor:?}:{err}"))?;
+    if !status.success() {
+        return Err(format!("editor {editor:?} exited with {status}"));
+    }
+
+    let edited = fs::read_to_string(&path).map_err(|err| format!("read temp file:{err}"))?;
+    let mut lines: Vec<(LineKind, String)> = vec![];
+    for line in edited.lines() {
+        if line.starts_with('#') || line.starts_with("@@") {
+            continue;
+        }
+
+        if line.starts_with('\\') {
+            if let Some((_, last)) = lines.last_mut() {
+                last.pop();
+            }
+            continue;
+        }
+
+        let (kind, text) = match line.chars().next() {
+            Some('+') => (LineKind::Added, &line[1..]),
+            Some('-') => (LineKind::Removed, &line[1..]),
+            Some(' ') => (LineKind::Context, &line[1..]),
+            None => (LineKind::Context, ""),
+            Some(_) => return Err(format!("Your edited hunk has a bad line: {line:?}")),
+        };
+        lines.push((kind, format!("{text}\n")));
+    }
+
+    let old_side = lines
+        .iter()
+        .filter(|(kind, _)| *kind != LineKind::Added)
+        .map(|(_, line)| line.as_str())
+        .collect::<Vec<_>>();
+    let expected = old_lines
+        .iter()
+        .skip(hunk.old_start)
+        .take(old_side.len())
+        .copied()
+        .collect::<Vec<_>>();
+    if old_side != expected {
+        return Err(String::from("Your edited hunk does not apply."));
+    }
+
+    Ok(Hunk {
+        old_start: hunk.old_start,
+        old_len: old_side.len(),
+        new_start: hunk.new_start,
+        new_len: lines
+            .iter()
+            .filter(|(kind, _)| *kind != LineKind::Removed)
+            .count(),
+        lines,
+    })
+}
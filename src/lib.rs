@@ -1,5 +1,23 @@
+pub mod apply;
 pub mod base;
+pub mod binary;
 pub mod cli;
+pub mod color;
 pub mod data;
+pub mod date;
 pub mod diff;
+pub mod graph;
+pub mod interactive;
+pub mod line_diff;
+pub mod mailbox;
+pub mod merge;
+pub mod pager;
+pub mod pathspec;
+pub mod pretty;
+pub mod regex;
 pub mod remote;
+pub mod rename;
+pub mod revision;
+pub mod revwalk;
+pub mod strategy;
+pub mod word_diff;
@@ -0,0 +1,963 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+/// 一行的编辑操作，保存的是行在旧/新内容中的下标
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+/// 一段连续的修改以及它前后的上下文
+#[derive(Clone, Debug)]
+pub struct Hunk {
+    /// 旧内容中的起始行，从 0 开始
+    pub old_start: usize,
+    pub old_len: usize,
+    /// 新内容中的起始行，从 0 开始
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<(LineKind, String)>,
+}
+
+/// 按行切分，保留换行符，这样最后一行有没有换行也能比较出来
+pub fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// 忽略空白数量的变化：连续的空白算一个空格，行尾空白不算
+pub fn collapse_space(line: &str) -> String {
+    let mut collapsed = String::with_capacity(line.len());
+    let mut space = false;
+    for c in line.chars() {
+        if c.is_whitespace() {
+            space = true;
+            continue;
+        }
+        if space {
+            collapsed.push(' ');
+            space = false;
+        }
+        collapsed.push(c);
+    }
+
+    collapsed
+}
+
+/// 比较行时忽略哪些空白，输出的仍然是原来的行
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Whitespace {
+    /// 忽略所有空白，`-w`
+    pub ignore_all_space: bool,
+    /// 忽略空白数量的变化，`-b`
+    pub ignore_space_change: bool,
+    /// 忽略行尾的空白
+    pub ignore_space_at_eol: bool,
+}
+
+impl Whitespace {
+    pub fn is_exact(&self) -> bool {
+        *self == Whitespace::default()
+    }
+
+    /// 规整成用来比较的形式
+    pub fn normalize<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        if self.ignore_all_space {
+            Cow::Owned(line.chars().filter(|c| !c.is_whitespace()).collect())
+        } else if self.ignore_space_change {
+            Cow::Owned(collapse_space(line))
+        } else if self.ignore_space_at_eol {
+            Cow::Borrowed(line.trim_end())
+        } else {
+            Cow::Borrowed(line)
+        }
+    }
+}
+
+/// 行比较使用的算法
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    /// 经典的最短编辑序列
+    #[default]
+    Myers,
+    /// 以两边都只出现一次的行为锚点，结果更贴近代码结构
+    Patience,
+    /// patience 的扩展，选择出现次数最少的行作为锚点
+    Histogram,
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "myers" => Ok(Algorithm::Myers),
+            "patience" => Ok(Algorithm::Patience),
+            "histogram" => Ok(Algorithm::Histogram),
+            _ => Err(format!("unknown diff algorithm: {s}")),
+        }
+    }
+}
+
+/// 比较两组行，返回把 `old` 变成 `new` 的编辑序列
+pub fn diff_lines(old: &[&str], new: &[&str], algorithm: Algorithm) -> Vec<Edit> {
+    let mut ids = HashMap::new();
+    let mut intern = |line: &str| {
+        let next = ids.len();
+        *ids.entry(line.to_string()).or_insert(next)
+    };
+
+    let old_ids = old.iter().map(|line| intern(line)).collect::<Vec<_>>();
+    let new_ids = new.iter().map(|line| intern(line)).collect::<Vec<_>>();
+
+    let mut edits = Vec::with_capacity(old.len().max(new.len()));
+    diff_range(algorithm, &old_ids, &new_ids, 0, 0, &mut edits);
+    edits
+}
+
+/// 同 [`diff_lines`]，但按 `whitespace` 规整过的行来比较
+pub fn diff_lines_with(
+    old: &[&str],
+    new: &[&str],
+    algorithm: Algorithm,
+    whitespace: Whitespace,
+) -> Vec<Edit> {
+    if whitespace.is_exact() {
+        return diff_lines(old, new, algorithm);
+    }
+
+    let old_keys = old
+        .iter()
+        .map(|line| whitespace.normalize(line))
+        .collect::<Vec<_>>();
+    let new_keys = new
+        .iter()
+        .map(|line| whitespace.normalize(line))
+        .collect::<Vec<_>>();
+    diff_lines(
+        &old_keys.iter().map(AsRef::as_ref).collect::<Vec<_>>(),
+        &new_keys.iter().map(AsRef::as_ref).collect::<Vec<_>>(),
+        algorithm,
+    )
+}
+
+/// 先去掉相同的开头和结尾，中间部分再交给具体的算法
+fn diff_range(
+    algorithm: Algorithm,
+    old: &[usize],
+    new: &[usize],
+    old_offset: usize,
+    new_offset: usize,
+    edits: &mut Vec<Edit>,
+) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    edits.extend((0..prefix).map(|i| Edit::Equal(old_offset + i, new_offset + i)));
+
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+    let (old_start, new_start) = (old_offset + prefix, new_offset + prefix);
+
+    if old_middle.is_empty() {
+        edits.extend((0..new_middle.len()).map(|i| Edit::Insert(new_start + i)));
+    } else if new_middle.is_empty() {
+        edits.extend((0..old_middle.len()).map(|i| Edit::Delete(old_start + i)));
+    } else {
+        match algorithm {
+            Algorithm::Myers => {
+                myers_lines(old_middle, new_middle, old_start, new_start, edits);
+            }
+            Algorithm::Patience => {
+                patience(old_middle, new_middle, old_start, new_start, edits);
+            }
+            Algorithm::Histogram => {
+                histogram(old_middle, new_middle, old_start, new_start, edits);
+            }
+        }
+    }
+
+    let (old_tail, new_tail) = (
+        old_offset + old.len() - suffix,
+        new_offset + new.len() - suffix,
+    );
+    edits.extend((0..suffix).map(|i| Edit::Equal(old_tail + i, new_tail + i)));
+}
+
+/// 和 git 一样，只在一边出现的行一定是删除或增加，先去掉再交给 myers
+fn myers_lines(
+    old: &[usize],
+    new: &[usize],
+    old_offset: usize,
+    new_offset: usize,
+    edits: &mut Vec<Edit>,
+) {
+    let (old_set, new_set) = (
+        old.iter().collect::<HashSet<_>>(),
+        new.iter().collect::<HashSet<_>>(),
+    );
+    let old_kept = (0..old.len())
+        .filter(|i| new_set.contains(&old[*i]))
+        .collect::<Vec<_>>();
+    let new_kept = (0..new.len())
+        .filter(|i| old_set.contains(&new[*i]))
+        .collect::<Vec<_>>();
+    let kept_edits = myers(
+        &old_kept.iter().map(|i| old[*i]).collect::<Vec<_>>(),
+        &new_kept.iter().map(|i| new[*i]).collect::<Vec<_>>(),
+    );
+
+    //去掉的行放回原来的位置
+    let (mut o, mut n) = (0, 0);
+    for edit in kept_edits.into_iter().map(Some).chain([None]) {
+        let (old_end, new_end) = match edit {
+            Some(Edit::Equal(i, j)) => (old_kept[i], new_kept[j]),
+            Some(Edit::Delete(i)) => (old_kept[i] + 1, n),
+            Some(Edit::Insert(j)) => (o, new_kept[j] + 1),
+            None => (old.len(), new.len()),
+        };
+        edits.extend((o..old_end).map(|i| Edit::Delete(old_offset + i)));
+        edits.extend((n..new_end).map(|j| Edit::Insert(new_offset + j)));
+        (o, n) = (old_end, new_end);
+        if let Some(Edit::Equal(..)) = edit {
+            edits.push(Edit::Equal(old_offset + o, new_offset + n));
+            (o, n) = (o + 1, n + 1);
+        }
+    }
+}
+
+fn shift(edit: Edit, old_offset: usize, new_offset: usize) -> Edit {
+    match edit {
+        Edit::Equal(o, n) => Edit::Equal(o + old_offset, n + new_offset),
+        Edit::Delete(o) => Edit::Delete(o + old_offset),
+        Edit::Insert(n) => Edit::Insert(n + new_offset),
+    }
+}
+
+/// 在两边都只出现一次的行里求最长递增子序列作为锚点，锚点之间递归比较
+fn patience(
+    old: &[usize],
+    new: &[usize],
+    old_offset: usize,
+    new_offset: usize,
+    edits: &mut Vec<Edit>,
+) {
+    //行 -> (旧内容中出现次数, 位置, 新内容中出现次数, 位置)
+    let mut counts: HashMap<usize, (usize, usize, usize, usize)> = HashMap::new();
+    for (i, line) in old.iter().enumerate() {
+        let entry = counts.entry(*line).or_default();
+        entry.0 += 1;
+        entry.1 = i;
+    }
+    for (i, line) in new.iter().enumerate() {
+        let entry = counts.entry(*line).or_default();
+        entry.2 += 1;
+        entry.3 = i;
+    }
+
+    let mut uniques = counts
+        .values()
+        .filter(|(old_count, _, new_count, _)| *old_count == 1 && *new_count == 1)
+        .map(|(_, old_pos, _, new_pos)| (*old_pos, *new_pos))
+        .collect::<Vec<_>>();
+    uniques.sort();
+
+    let anchors = longest_increasing(&uniques);
+    if anchors.is_empty() {
+        edits.extend(
+            myers(old, new)
+                .into_iter()
+                .map(|edit| shift(edit, old_offset, new_offset)),
+        );
+        return;
+    }
+
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for (old_anchor, new_anchor) in anchors {
+        diff_range(
+            Algorithm::Patience,
+            &old[old_pos..old_anchor],
+            &new[new_pos..new_anchor],
+            old_offset + old_pos,
+            new_offset + new_pos,
+            edits,
+        );
+        edits.push(Edit::Equal(
+            old_offset + old_anchor,
+            new_offset + new_anchor,
+        ));
+        old_pos = old_anchor + 1;
+        new_pos = new_anchor + 1;
+    }
+
+    diff_range(
+        Algorithm::Patience,
+        &old[old_pos..],
+        &new[new_pos..],
+        old_offset + old_pos,
+        new_offset + new_pos,
+        edits,
+    );
+}
+
+/// 按旧位置排好序的配对中，新位置递增的最长子序列（patience sorting）
+fn longest_increasing(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    //每堆牌顶的下标，以及每张牌的前驱
+    let mut piles: Vec<usize> = vec![];
+    let mut prev = vec![None; pairs.len()];
+    for (i, (_, new_pos)) in pairs.iter().enumerate() {
+        let pile = piles.partition_point(|top| pairs[*top].1 < *new_pos);
+        if pile > 0 {
+            prev[i] = Some(piles[pile - 1]);
+        }
+        if pile == piles.len() {
+            piles.push(i);
+        } else {
+            piles[pile] = i;
+        }
+    }
+
+    let mut result = vec![];
+    let mut current = piles.last().copied();
+    while let Some(i) = current {
+        result.push(pairs[i]);
+        current = prev[i];
+    }
+    result.reverse();
+    result
+}
+
+/// 超过这个出现次数的行不适合做锚点，直接退回 Myers
+const MAX_HISTOGRAM_CHAIN: usize = 64;
+
+/// 选出现次数最少的公共行，向两边扩展成最长的公共片段，再递归比较两侧
+fn histogram(
+    old: &[usize],
+    new: &[usize],
+    old_offset: usize,
+    new_offset: usize,
+    edits: &mut Vec<Edit>,
+) {
+    let mut occurrences: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, line) in old.iter().enumerate() {
+        occurrences.entry(*line).or_default().push(i);
+    }
+
+    //(旧起点, 新起点, 长度, 出现次数)
+    let mut best: Option<(usize, usize, usize, usize)> = None;
+    let mut new_pos = 0;
+    while new_pos < new.len() {
+        let positions = match occurrences.get(&new[new_pos]) {
+            Some(positions) => positions,
+            None => {
+                new_pos += 1;
+                continue;
+            }
+        };
+
+        let count = positions.len();
+        if count > MAX_HISTOGRAM_CHAIN || best.is_some_and(|(_, _, _, best)| count > best) {
+            new_pos += 1;
+            continue;
+        }
+
+        let mut next_new = new_pos + 1;
+        for old_pos in positions {
+            let (mut o, mut n) = (*old_pos, new_pos);
+            while o > 0 && n > 0 && old[o - 1] == new[n - 1] {
+                o -= 1;
+                n -= 1;
+            }
+            let mut len = 0;
+            while o + len < old.len() && n + len < new.len() && old[o + len] == new[n + len] {
+                len += 1;
+            }
+
+            let better = match best {
+                None => true,
+                Some((_, _, best_len, best_count)) => {
+                    count < best_count || (count == best_count && len > best_len)
+                }
+            };
+            if better {
+                best = Some((o, n, len, count));
+            }
+            next_new = next_new.max(n + len);
+        }
+        new_pos = next_new;
+    }
+
+    let (old_start, new_start, len, _) = match best {
+        Some(best) => best,
+        None => {
+            //公共行都出现得太频繁时交给 Myers，否则说明没有任何公共行
+            if new.iter().any(|line| occurrences.contains_key(line)) {
+                edits.extend(
+                    myers(old, new)
+                        .into_iter()
+                        .map(|edit| shift(edit, old_offset, new_offset)),
+                );
+            } else {
+                edits.extend((0..old.len()).map(|i| Edit::Delete(old_offset + i)));
+                edits.extend((0..new.len()).map(|i| Edit::Insert(new_offset + i)));
+            }
+            return;
+        }
+    };
+
+    diff_range(
+        Algorithm::Histogram,
+        &old[..old_start],
+        &new[..new_start],
+        old_offset,
+        new_offset,
+        edits,
+    );
+    edits.extend(
+        (0..len).map(|i| Edit::Equal(old_offset + old_start + i, new_offset + new_start + i)),
+    );
+    diff_range(
+        Algorithm::Histogram,
+        &old[old_start + len..],
+        &new[new_start + len..],
+        old_offset + old_start + len,
+        new_offset + new_start + len,
+        edits,
+    );
+}
+
+/// Myers 的 O(ND) 算法，用中间蛇分治，只需要线性的空间
+pub fn myers<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Edit> {
+    let size = 2 * (a.len() + b.len()) + 3;
+    let (mut forward, mut backward) = (vec![0; size], vec![0; size]);
+    let mut edits = Vec::with_capacity(a.len().max(b.len()));
+    myers_range(a, b, 0, 0, &mut forward, &mut backward, &mut edits);
+    edits
+}
+
+/// `a`、`b` 在整个序列中从 `a_offset`、`b_offset` 开始
+fn myers_range<T: PartialEq>(
+    a: &[T],
+    b: &[T],
+    a_offset: usize,
+    b_offset: usize,
+    forward: &mut [isize],
+    backward: &mut [isize],
+    edits: &mut Vec<Edit>,
+) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    edits.extend((0..prefix).map(|i| Edit::Equal(a_offset + i, b_offset + i)));
+
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let (a_start, b_start) = (a_offset + prefix, b_offset + prefix);
+    if a_mid.is_empty() {
+        edits.extend((0..b_mid.len()).map(|i| Edit::Insert(b_start + i)));
+    } else if b_mid.is_empty() {
+        edits.extend((0..a_mid.len()).map(|i| Edit::Delete(a_start + i)));
+    } else {
+        //开头和结尾不同，编辑距离至少是 2，中间蛇两边都比原来小
+        let (x, y, u, v) = middle_snake(a_mid, b_mid, forward, backward);
+        myers_range(
+            &a_mid[..x],
+            &b_mid[..y],
+            a_start,
+            b_start,
+            forward,
+            backward,
+            edits,
+        );
+        edits.extend((0..u - x).map(|i| Edit::Equal(a_start + x + i, b_start + y + i)));
+        myers_range(
+            &a_mid[u..],
+            &b_mid[v..],
+            a_start + u,
+            b_start + v,
+            forward,
+            backward,
+            edits,
+        );
+    }
+
+    let (a_tail, b_tail) = (a_offset + a.len() - suffix, b_offset + b.len() - suffix);
+    edits.extend((0..suffix).map(|i| Edit::Equal(a_tail + i, b_tail + i)));
+}
+
+/// 从两头同时搜索，返回最短编辑路径中间那条蛇的起点和终点 `(x, y, u, v)`
+fn middle_snake<T: PartialEq>(
+    a: &[T],
+    b: &[T],
+    forward: &mut [isize],
+    backward: &mut [isize],
+) -> (usize, usize, usize, usize) {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let delta = n - m;
+    let offset = n + m + 1;
+    //backward 里的 x 是从结尾往回走的距离，反向的对角线 k 对应正向的 delta - k
+    forward[(offset + 1) as usize] = 0;
+    backward[(offset + 1) as usize] = 0;
+
+    for d in 0..=(n + m + 1) / 2 {
+        for k in (-d..=d).step_by(2) {
+            let i = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && forward[i - 1] < forward[i + 1]) {
+                forward[i + 1]
+            } else {
+                forward[i - 1] + 1
+            };
+            let (x0, y0) = (x, x - k);
+            let mut y = y0;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[i] = x;
+
+            let back = delta - k;
+            if delta % 2 != 0
+                && (1 - d..d).contains(&back)
+                && x + backward[(back + offset) as usize] >= n
+            {
+                return (x0 as usize, y0 as usize, x as usize, y as usize);
+            }
+        }
+
+        for k in (-d..=d).step_by(2) {
+            let i = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && backward[i - 1] < backward[i + 1]) {
+                backward[i + 1]
+            } else {
+                backward[i - 1] + 1
+            };
+            let (x0, y0) = (x, x - k);
+            let mut y = y0;
+            while x < n && y < m && a[(n - x - 1) as usize] == b[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[i] = x;
+
+            let front = delta - k;
+            if delta % 2 == 0
+                && (-d..=d).contains(&front)
+                && x + forward[(front + offset) as usize] >= n
+            {
+                return (
+                    (n - x) as usize,
+                    (m - y) as usize,
+                    (n - x0) as usize,
+                    (m - y0) as usize,
+                );
+            }
+        }
+    }
+
+    unreachable!("two sequences always have an edit path")
+}
+
+/// 把编辑序列按 `context` 行上下文分组成 hunk，相隔不远的修改合并到一起
+pub fn make_hunks(old: &[&str], new: &[&str], edits: &[Edit], context: usize) -> Vec<Hunk> {
+    //每个编辑之前，旧/新内容已经走过的行数
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut o, mut n) = (0, 0);
+    for edit in edits {
+        positions.push((o, n));
+        match edit {
+            Edit::Equal(..) => {
+                o += 1;
+                n += 1;
+            }
+            Edit::Delete(_) => o += 1,
+            Edit::Insert(_) => n += 1,
+        }
+    }
+    positions.push((o, n));
+
+    let changes = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Equal(..)))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+
+    let mut groups: Vec<(usize, usize)> = vec![];
+    for i in changes {
+        match groups.last_mut() {
+            Some((_, end)) if i - *end <= 2 * context + 1 => *end = i,
+            _ => groups.push((i, i)),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(first, last)| {
+            let start = first.saturating_sub(context);
+            let end = (last + context + 1).min(edits.len());
+            let (old_start, new_start) = positions[start];
+            let (old_end, new_end) = positions[end];
+
+            let lines = edits[start..end]
+                .iter()
+                .map(|edit| match *edit {
+                    Edit::Equal(o, _) => (LineKind::Context, old[o].to_string()),
+                    Edit::Delete(o) => (LineKind::Removed, old[o].to_string()),
+                    Edit::Insert(n) => (LineKind::Added, new[n].to_string()),
+                })
+                .collect();
+
+            Hunk {
+                old_start,
+                old_len: old_end - old_start,
+                new_start,
+                new_len: new_end - new_start,
+                lines,
+            }
+        })
+        .collect()
+}
+
+impl Hunk {
+    /// `@@ -1,3 +1,4 @@` 形式的头
+    pub fn header(&self) -> String {
+        format!(
+            "@@ -{} +{} @@",
+            format_range(self.old_start, self.old_len),
+            format_range(self.new_start, self.new_len)
+        )
+    }
+
+    /// 按中间的上下文把 hunk 拆成更小的几个，不能拆时返回自身
+    pub fn split(&self) -> Vec<Hunk> {
+        //(上下文起点, 修改起点, 修改终点)，都是 lines 的下标
+        let mut blocks: Vec<(usize, usize, usize)> = vec![];
+        let mut context_start = 0;
+        for (i, (kind, _)) in self.lines.iter().enumerate() {
+            if *kind == LineKind::Context {
+                continue;
+            }
+
+            match blocks.last_mut() {
+                Some((_, _, end)) if *end == i => *end = i + 1,
+                _ => {
+                    if let Some((_, _, end)) = blocks.last() {
+                        context_start = *end;
+                    }
+                    blocks.push((context_start, i, i + 1));
+                }
+            }
+        }
+
+        if blocks.len() <= 1 {
+            return vec![self.clone()];
+        }
+
+        let mut hunks = vec![];
+        let (mut old_pos, mut new_pos) = (self.old_start, self.new_start);
+        //相邻的 hunk 共用中间的上下文
+        for (i, (context_start, _, change_end)) in blocks.iter().enumerate() {
+            let end = match blocks.get(i + 1) {
+                Some((_, next_start, _)) => *next_start,
+                None => self.lines.len(),
+            };
+
+            let lines = self.lines[*context_start..end].to_vec();
+            let old_len = lines
+                .iter()
+                .filter(|(kind, _)| *kind != LineKind::Added)
+                .count();
+            let new_len = lines
+                .iter()
+                .filter(|(kind, _)| *kind != LineKind::Removed)
+                .count();
+            hunks.push(Hunk {
+                old_start: old_pos,
+                old_len,
+                new_start: new_pos,
+                new_len,
+                lines,
+            });
+
+            for (kind, _) in &self.lines[*context_start..*change_end] {
+                match kind {
+                    LineKind::Context => {
+                        old_pos += 1;
+                        new_pos += 1;
+                    }
+                    LineKind::Removed => old_pos += 1,
+                    LineKind::Added => new_pos += 1,
+                }
+            }
+        }
+
+        hunks
+    }
+}
+
+impl fmt::Display for Hunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.header())?;
+        for (kind, line) in &self.lines {
+            let prefix = match kind {
+                LineKind::Context => ' ',
+                LineKind::Added => '+',
+                LineKind::Removed => '-',
+            };
+            write!(f, "{prefix}{line}")?;
+            if !line.ends_with('\n') {
+                writeln!(f, "\n\\ No newline at end of file")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn format_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+/// 只应用选中的 hunk，没选中的部分保持 `old` 的内容，hunk 要按 `old_start` 排好序
+pub fn apply_hunks(old: &[&str], hunks: &[&Hunk]) -> String {
+    let mut result = String::new();
+    let mut cursor = 0;
+    for hunk in hunks {
+        let mut pos = hunk.old_start;
+        while cursor < pos.min(old.len()) {
+            result.push_str(old[cursor]);
+            cursor += 1;
+        }
+
+        for (kind, line) in &hunk.lines {
+            match kind {
+                LineKind::Context => {
+                    if pos >= cursor {
+                        result.push_str(old.get(pos).copied().unwrap_or(line.as_str()));
+                        cursor = pos + 1;
+                    }
+                    pos += 1;
+                }
+                LineKind::Removed => {
+                    pos += 1;
+                    cursor = cursor.max(pos);
+                }
+                LineKind::Added => result.push_str(line),
+            }
+        }
+    }
+
+    for line in old.iter().skip(cursor) {
+        result.push_str(line);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALGORITHMS: [Algorithm; 3] =
+        [Algorithm::Myers, Algorithm::Patience, Algorithm::Histogram];
+
+    /// 按编辑序列从旧内容重建新内容，同时检查下标是按顺序走完的
+    fn rebuild(old: &[&str], new: &[&str], edits: &[Edit]) -> String {
+        let (mut o, mut n) = (0, 0);
+        let mut result = String::new();
+        for edit in edits {
+            match *edit {
+                Edit::Equal(i, j) => {
+                    assert_eq!((i, j), (o, n));
+                    assert_eq!(old[i], new[j]);
+                    result.push_str(old[i]);
+                    o += 1;
+                    n += 1;
+                }
+                Edit::Delete(i) => {
+                    assert_eq!(i, o);
+                    o += 1;
+                }
+                Edit::Insert(j) => {
+                    assert_eq!(j, n);
+                    result.push_str(new[j]);
+                    n += 1;
+                }
+            }
+        }
+        assert_eq!((o, n), (old.len(), new.len()));
+        result
+    }
+
+    fn check(old: &str, new: &str) {
+        let (old_lines, new_lines) = (split_lines(old), split_lines(new));
+        for algorithm in ALGORITHMS {
+            let edits = diff_lines(&old_lines, &new_lines, algorithm);
+            assert_eq!(
+                rebuild(&old_lines, &new_lines, &edits),
+                new,
+                "{algorithm:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn edits_rebuild_new_content() {
+        check("a\nb\nc\n", "a\nc\nd\n");
+        check("x\na\nb\nx\n", "a\nx\nb\nx\nx\n");
+        check(
+            "fn a() {\n}\n\nfn b() {\n}\n",
+            "fn a() {\n}\n\nfn c() {\n}\n\nfn b() {\n}\n",
+        );
+    }
+
+    #[test]
+    fn empty_input() {
+        check("", "");
+        check("", "a\nb\n");
+        check("a\nb\n", "");
+        for algorithm in ALGORITHMS {
+            assert!(diff_lines(&[], &[], algorithm).is_empty());
+        }
+    }
+
+    #[test]
+    fn missing_trailing_newline() {
+        check("a\nb", "a\nb\n");
+        let (old, new) = (split_lines("a\nb"), split_lines("a\nb\n"));
+        let edits = diff_lines(&old, &new, Algorithm::Myers);
+        assert_eq!(
+            edits,
+            vec![Edit::Equal(0, 0), Edit::Delete(1), Edit::Insert(1)]
+        );
+    }
+
+    /// 最长公共子序列的长度，用来检查 myers 的结果是最短的
+    fn lcs(a: &[u8], b: &[u8]) -> usize {
+        let mut row = vec![0; b.len() + 1];
+        for x in a {
+            let mut diagonal = 0;
+            for (j, y) in b.iter().enumerate() {
+                let up = row[j + 1];
+                row[j + 1] = if x == y { diagonal + 1 } else { up.max(row[j]) };
+                diagonal = up;
+            }
+        }
+        row[b.len()]
+    }
+
+    #[test]
+    fn myers_is_minimal() {
+        //固定种子的线性同余序列，字母表小，公共部分多
+        let mut seed = 7u32;
+        let mut next = |len: u32| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) % len
+        };
+        for _ in 0..300 {
+            let a = (0..next(12))
+                .map(|_| b'a' + next(3) as u8)
+                .collect::<Vec<_>>();
+            let b = (0..next(12))
+                .map(|_| b'a' + next(3) as u8)
+                .collect::<Vec<_>>();
+            let edits = myers(&a, &b);
+            let equal = edits
+                .iter()
+                .filter(|edit| matches!(edit, Edit::Equal(..)))
+                .count();
+            assert_eq!(equal, lcs(&a, &b), "{a:?} {b:?}");
+            assert_eq!(edits.len(), a.len() + b.len() - equal);
+        }
+    }
+
+    #[test]
+    fn large_inputs() {
+        //以前每一轮都复制整个 V 数组，这样的输入会耗尽内存
+        let old = (1..=20000).map(|i| format!("{i}\n")).collect::<String>();
+        let new = (100000..=120000)
+            .map(|i| format!("{i}\n"))
+            .collect::<String>();
+        let (old_lines, new_lines) = (split_lines(&old), split_lines(&new));
+        let edits = diff_lines(&old_lines, &new_lines, Algorithm::Myers);
+        assert_eq!(edits.len(), old_lines.len() + new_lines.len());
+        let (a, b) = (
+            (0..4000).collect::<Vec<_>>(),
+            (4000..8000).collect::<Vec<_>>(),
+        );
+        assert_eq!(myers(&a, &b).len(), 8000);
+
+        let new = (1..=20000)
+            .map(|i| match i % 100 {
+                0 => format!("changed {i}\n"),
+                _ => format!("{i}\n"),
+            })
+            .collect::<String>();
+        check(&old, &new);
+    }
+
+    #[test]
+    fn patience_anchors_on_unique_lines() {
+        let old = split_lines("}\nfn a\n}\n");
+        let new = split_lines("}\nfn b\n}\nfn a\n}\n");
+        let edits = diff_lines(&old, &new, Algorithm::Patience);
+        assert!(edits.contains(&Edit::Equal(1, 3)));
+    }
+
+    #[test]
+    fn whitespace_options() {
+        let (old, new) = (split_lines("a  b\nc\n"), split_lines("a b \nc\n"));
+        let change = Whitespace {
+            ignore_space_change: true,
+            ..Default::default()
+        };
+        let edits = diff_lines_with(&old, &new, Algorithm::Myers, change);
+        assert_eq!(edits, vec![Edit::Equal(0, 0), Edit::Equal(1, 1)]);
+        let edits = diff_lines_with(&old, &new, Algorithm::Myers, Whitespace::default());
+        assert_eq!(edits.len(), 3);
+    }
+
+    #[test]
+    fn hunks_split_and_apply() {
+        let old = split_lines("1\n2\n3\n4\n5\n6\n7\n");
+        let new = split_lines("1\nx\n3\n4\n5\ny\n7\n");
+        let edits = diff_lines(&old, &new, Algorithm::Myers);
+        let hunks = make_hunks(&old, &new, &edits, 1);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[0].header(), "@@ -1,3 +1,3 @@");
+
+        let merged = make_hunks(&old, &new, &edits, 3);
+        assert_eq!(merged.len(), 1);
+        let parts = merged[0].split();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(apply_hunks(&old, &[&parts[1]]), "1\n2\n3\n4\n5\ny\n7\n");
+        assert_eq!(
+            apply_hunks(&old, &parts.iter().collect::<Vec<_>>()),
+            new.concat()
+        );
+    }
+}
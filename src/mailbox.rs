@@ -0,0 +1,668 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    apply::{self, ApplyOptions},
+    base::{Commit, ResetMode, Signature},
+    data::{self, DateErr, Ugit},
+    date,
+    diff::{self, DiffFormat, DiffOptions},
+    pager,
+    pretty::split_message,
+    revision::{RevFilter, RevList},
+    revwalk::{Order, RevWalk, WalkOptions},
+};
+
+/// `am` 保存进度的目录
+const AM_DIR: &str = "rebase-apply";
+
+/// 文件名里的标题最多保留的字符数
+const SUBJECT_NAME_MAX: usize = 52;
+
+/// 一封补丁邮件里的内容
+#[derive(Debug)]
+struct Mail {
+    author: Signature,
+    subject: String,
+    message: String,
+    /// 从第一个 `diff --git` 开始的补丁
+    diff: String,
+}
+
+/// `format-patch` 的输出方式
+#[derive(Debug, Default)]
+pub struct FormatPatchOptions {
+    /// 为 None 时写在当前目录
+    pub output_directory: Option<PathBuf>,
+    /// 所有补丁按 mbox 格式输出到 stdout，不写文件
+    pub stdout: bool,
+    /// 范围是单个提交时表示它的全部历史，而不是它到 HEAD 之间
+    pub root: bool,
+}
+
+/// 和 git 一样，文件名里只保留字母、数字、`.` 和 `_`，其它连续的字符换成一个 `-`
+fn sanitize_subject(subject: &str) -> String {
+    let mut name = String::new();
+    for c in subject.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            name.push(c);
+        } else if !name.is_empty() && !name.ends_with('-') {
+            name.push('-');
+        }
+    }
+
+    let name = name.chars().take(SUBJECT_NAME_MAX).collect::<String>();
+    name.trim_end_matches(['-', '.']).to_string()
+}
+
+/// 含有非 ASCII 字符的邮件头按 RFC 2047 编码
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        return value.to_string();
+    }
+
+    let mut encoded = String::from("=?UTF-8?q?");
+    for byte in value.bytes() {
+        match byte {
+            b' ' => encoded.push('_'),
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'.' | b'-' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("={byte:02X}")),
+        }
+    }
+    encoded.push_str("?=");
+    encoded
+}
+
+/// 解码邮件头里 `=?charset?q?...?=` 和 `=?charset?b?...?=` 形式的内容
+fn decode_header(value: &str) -> String {
+    let mut decoded = vec![];
+    let mut rest = value;
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let word = rest[start + 2..].splitn(3, '?').collect::<Vec<_>>();
+        let (encoding, text) = match word.as_slice() {
+            [_, encoding, text] => (*encoding, *text),
+            _ => break,
+        };
+        let end = match text.find("?=") {
+            Some(end) => end,
+            None => break,
+        };
+        let bytes = match encoding {
+            "q" | "Q" => decode_q(&text[..end]),
+            "b" | "B" => decode_base64(&text[..end]),
+            _ => None,
+        };
+        let bytes = match bytes {
+            Some(bytes) => bytes,
+            None => break,
+        };
+
+        //两个编码过的词之间的空白不算内容
+        let between = &rest[..start];
+        if !(after_word && between.trim().is_empty()) {
+            decoded.extend(between.as_bytes());
+        }
+        decoded.extend(bytes);
+        after_word = true;
+        let consumed = rest.len() - text.len() + end + 2;
+        rest = &rest[consumed..];
+    }
+    decoded.extend(rest.as_bytes());
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn decode_q(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+    let mut chars = text.bytes();
+    while let Some(byte) = chars.next() {
+        match byte {
+            b'_' => bytes.push(b' '),
+            b'=' => {
+                let hex = [chars.next()?, chars.next()?];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    Some(bytes)
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut bytes = vec![];
+    let (mut acc, mut bits) = (0u32, 0);
+    for c in text.bytes().filter(|c| *c != b'=') {
+        let value = ALPHABET.iter().position(|a| *a == c)? as u32;
+        acc = (acc << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// 把 mbox 按 `From ` 开头的分隔行拆成一封封邮件，没有分隔行时整个文件是一封
+fn split_mbox(text: &str) -> Vec<String> {
+    let mut mails: Vec<String> = vec![];
+    let mut previous_blank = true;
+    for line in text.split_inclusive('\n') {
+        if line.starts_with("From ") && previous_blank {
+            mails.push(String::new());
+        } else {
+            let line = unescape_from(line);
+            match mails.last_mut() {
+                Some(mail) => mail.push_str(line),
+                None => mails.push(line.to_string()),
+            }
+        }
+        previous_blank = line.trim().is_empty();
+    }
+
+    mails.retain(|mail| !mail.trim().is_empty());
+    mails
+}
+
+/// 按 mboxrd 的规则，正文里 `>` 之后是 `From ` 的行再加一个 `>`，不会被当成分隔行
+fn escape_from(text: &str) -> String {
+    text.split_inclusive('\n')
+        .map(
+            |line| match line.trim_start_matches('>').starts_with("From ") {
+                true => format!(">{line}"),
+                false => line.to_string(),
+            },
+        )
+        .collect()
+}
+
+/// `escape_from` 的逆过程，去掉一个 `>`
+fn unescape_from(line: &str) -> &str {
+    match line.strip_prefix('>') {
+        Some(rest) if rest.trim_start_matches('>').starts_with("From ") => rest,
+        _ => line,
+    }
+}
+
+/// 去掉标题前面的 `[PATCH 1/2]` 和 `Re:`
+fn strip_subject(subject: &str) -> String {
+    let mut subject = subject.trim();
+    loop {
+        if let Some(rest) = subject.strip_prefix('[') {
+            match rest.split_once(']') {
+                Some((_, rest)) => subject = rest.trim_start(),
+                None => break,
+            }
+        } else if subject
+            .get(..3)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("re:"))
+        {
+            subject = subject[3..].trim_start();
+        } else {
+            break;
+        }
+    }
+
+    subject.to_string()
+}
+
+/// 解析一封补丁邮件：邮件头给出作者和标题，正文到 `---` 为止是提交信息的其余部分
+fn parse_mail(text: &str) -> Result<Mail, String> {
+    let (headers, body) = match text.split_once("\n\n") {
+        Some((headers, body)) => (headers, body),
+        None => (text, ""),
+    };
+
+    //以空白开头的行是上一个头的续行
+    let mut fields: Vec<(String, String)> = vec![];
+    for line in headers.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = fields.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            fields.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| decode_header(value))
+    };
+
+    let from = field("from").ok_or_else(|| String::from("patch does not have a From header"))?;
+    let (name, email) = match from.split_once('<') {
+        Some((name, email)) => (
+            name.trim().trim_matches('"').to_string(),
+            email.trim_end().trim_end_matches('>').to_string(),
+        ),
+        None => (String::new(), from.trim().to_string()),
+    };
+    let (time, offset) = field("date")
+        .and_then(|date| date::parse_rfc2822(&date))
+        .unwrap_or((date::now(), 0));
+    let author = Signature {
+        name: if name.is_empty() { email.clone() } else { name },
+        email,
+        time,
+        offset,
+    };
+    let subject = strip_subject(&field("subject").unwrap_or_default());
+
+    let mut description = String::new();
+    let mut diff = String::new();
+    let mut lines = body.split_inclusive('\n');
+    for line in lines.by_ref() {
+        if line.trim_end() == "---" || line.starts_with("diff --git ") {
+            diff.push_str(line);
+            break;
+        }
+        description.push_str(line);
+    }
+    diff.extend(lines);
+    let diff = match diff.find("diff --git ") {
+        Some(start) => diff[start..].to_string(),
+        None => diff,
+    };
+
+    let description = description.trim();
+    let message = match description.is_empty() {
+        true => subject.clone(),
+        false => format!("{subject}\n\n{description}"),
+    };
+
+    Ok(Mail {
+        author,
+        subject,
+        message,
+        diff,
+    })
+}
+
+impl Ugit {
+    /// `A..B` 中的提交，单独的 `A` 表示 `A..HEAD`，`root` 时表示 A 能到达的全部提交，从旧到新排列，不含合并提交
+    fn commits_in_range(&self, range: &str, root: bool) -> Result<Vec<String>, DateErr> {
+        let range = match range.contains("..") || root {
+            true => range.to_string(),
+            false => format!("{range}.."),
+        };
+        let revisions = self.parse_revisions(&[range])?;
+        let options = WalkOptions {
+            order: Order::Topo,
+            reverse: true,
+            ..WalkOptions::default()
+        };
+        let walk = RevWalk::new(self, revisions.include, revisions.exclude, options);
+        let filter = RevFilter {
+            merges: Some(false),
+            ..RevFilter::default()
+        };
+        Ok(RevList::new(self, walk, filter).collect())
+    }
+
+    /// 把范围内的每个提交写成一个邮件格式的补丁，返回写出的文件
+    pub fn format_patch(
+        &self,
+        range: &str,
+        options: &FormatPatchOptions,
+    ) -> Result<Vec<PathBuf>, DateErr> {
+        let commits = self.commits_in_range(range, options.root)?;
+        let directory = options
+            .output_directory
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("."));
+        if !options.stdout {
+            fs::create_dir_all(&directory)?;
+        }
+
+        let mut files = vec![];
+        for (i, oid) in commits.iter().enumerate() {
+            let commit = self
+                .get_commit(oid)
+                .ok_or_else(|| DateErr::Err(format!("bad commit {oid}")))?;
+            let number = match commits.len() {
+                1 => String::from("[PATCH]"),
+                total => format!("[PATCH {}/{total}]", i + 1),
+            };
+            let mail = self.format_mail(oid, &commit, &number);
+
+            if options.stdout {
+                pager::print(&mail);
+                continue;
+            }
+
+            let (subject, _) = split_message(commit.message.as_deref().unwrap_or_default());
+            let path = directory.join(format!("{:04}-{}.patch", i + 1, sanitize_subject(&subject)));
+            fs::write(&path, mail)?;
+            files.push(path);
+        }
+
+        Ok(files)
+    }
+
+    /// 一个提交的补丁邮件：mbox 的分隔行、邮件头、提交信息、diffstat 和 diff
+    fn format_mail(&self, oid: &str, commit: &Commit, number: &str) -> String {
+        let (subject, body) = split_message(commit.message.as_deref().unwrap_or_default());
+        let author = commit
+            .author
+            .clone()
+            .unwrap_or_else(|| self.signature("AUTHOR"));
+
+        let mut mail = format!("From {oid} Mon Sep 17 00:00:00 2001\n");
+        mail.push_str(&format!(
+            "From: {} <{}>\n",
+            encode_header(&author.name),
+            author.email
+        ));
+        mail.push_str(&format!(
+            "Date: {}\n",
+            date::format_rfc2822(author.time, author.offset)
+        ));
+        mail.push_str(&format!("Subject: {number} {}\n", encode_header(&subject)));
+        if !(subject.is_ascii() && body.is_ascii()) {
+            mail.push_str("MIME-Version: 1.0\n");
+            mail.push_str("Content-Type: text/plain; charset=UTF-8\n");
+            mail.push_str("Content-Transfer-Encoding: 8bit\n");
+        }
+        mail.push('\n');
+        if !body.is_empty() {
+            mail.push_str(&escape_from(&format!("{body}\n")));
+        }
+
+        let t_from = commit
+            .parents
+            .first()
+            .and_then(|parent| self.get_commit_tree(parent))
+            .unwrap_or_default();
+        let t_to = commit
+            .tree
+            .as_ref()
+            .and_then(|tree| self.get_tree_in_base(tree))
+            .unwrap_or_default();
+        //和 git 一样带上二进制补丁，这样 am 可以应用
+        let options = DiffOptions {
+            binary: true,
+            ..DiffOptions::default()
+        };
+        let changes = self.tree_changes(&t_from, &t_to, &options);
+        let stat = DiffOptions {
+            format: DiffFormat::Stat,
+            ..options.clone()
+        };
+        mail.push_str("---\n");
+        mail.push_str(&diff::format_changes(&changes, &stat));
+        mail.push('\n');
+        mail.push_str(&diff::format_changes(&changes, &options));
+        mail.push_str(&format!("-- \nrgit {}\n\n", env!("CARGO_PKG_VERSION")));
+
+        mail
+    }
+
+    fn am_dir(&self) -> PathBuf {
+        Path::new(self.git_dir()).join(AM_DIR)
+    }
+
+    /// 是否有停下来的 `am`
+    pub fn am_in_progress(&self) -> bool {
+        self.am_dir().is_dir()
+    }
+
+    /// 开始依次应用邮箱文件里的补丁，`mailboxes` 为空时从 stdin 读
+    pub fn am(&self, mailboxes: &[String]) -> Result<(), DateErr> {
+        let dir = self.am_dir();
+        if dir.exists() {
+            return Err(DateErr::Err(format!(
+                "previous rebase directory {} still exists but mbox given.",
+                dir.display()
+            )));
+        }
+
+        let mut mails = vec![];
+        if mailboxes.is_empty() {
+            let text = std::io::read_to_string(std::io::stdin())?;
+            mails.extend(split_mbox(&text));
+        }
+        for mailbox in mailboxes {
+            let text = fs::read_to_string(mailbox)
+                .map_err(|err| DateErr::Err(format!("could not open '{mailbox}': {err}")))?;
+            mails.extend(split_mbox(&text));
+        }
+        if mails.is_empty() {
+            return Err(DateErr::Err(String::from("empty mbox")));
+        }
+
+        fs::create_dir_all(&dir)?;
+        for (i, mail) in mails.iter().enumerate() {
+            fs::write(dir.join(format!("{:04}", i + 1)), mail)?;
+        }
+        let orig_head = self
+            .get_ref_if_not_empty(data::HEAD)
+            .map(|head| head.value)
+            .unwrap_or_default();
+        fs::write(dir.join("orig-head"), orig_head)?;
+        fs::write(dir.join("last"), mails.len().to_string())?;
+        fs::write(dir.join("next"), "1")?;
+
+        self.am_run()
+    }
+
+    /// 读出 `next` 或 `last` 的编号
+    fn am_number(&self, name: &str) -> Result<usize, DateErr> {
+        let number = fs::read_to_string(self.am_dir().join(name)).map_err(|_| {
+            DateErr::Err(String::from(
+                "Resolve operation not in progress, we are not resuming.",
+            ))
+        })?;
+        number
+            .trim()
+            .parse()
+            .map_err(|_| DateErr::Err(format!("invalid {name} in {}", self.am_dir().display())))
+    }
+
+    fn am_current(&self) -> Result<(usize, Mail), DateErr> {
+        let next = self.am_number("next")?;
+        let text = fs::read_to_string(self.am_dir().join(format!("{next:04}")))?;
+        let mail = parse_mail(&text).map_err(DateErr::Err)?;
+        Ok((next, mail))
+    }
+
+    /// 从 `next` 开始应用剩下的补丁，失败时停下来等用户处理
+    fn am_run(&self) -> Result<(), DateErr> {
+        let last = self.am_number("last")?;
+        while self.am_number("next")? <= last {
+            let (next, mail) = self.am_current()?;
+            println!("Applying: {}", mail.subject);
+            let patches = match apply::parse_patch(&mail.diff) {
+                Ok(patches) if patches.is_empty() => {
+                    println!("Patch is empty.");
+                    return self.am_stopped(next, &mail);
+                }
+                Ok(patches) => patches,
+                Err(err) => {
+                    eprintln!("error: {err}");
+                    return self.am_stopped(next, &mail);
+                }
+            };
+
+            let options = ApplyOptions {
+                index: true,
+                ..ApplyOptions::default()
+            };
+            if let Err(err) = self.apply_patches(&patches, &options) {
+                match err {
+                    DateErr::Err(message) => eprintln!("error: {message}"),
+                    err => eprintln!("error: {err:?}"),
+                }
+                return self.am_stopped(next, &mail);
+            }
+
+            self.commit_with_author(&mail.message, mail.author)?;
+            fs::write(self.am_dir().join("next"), (next + 1).to_string())?;
+        }
+
+        fs::remove_dir_all(self.am_dir())?;
+        Ok(())
+    }
+
+    /// 停在当前补丁，保留进度
+    fn am_stopped(&self, next: usize, mail: &Mail) -> Result<(), DateErr> {
+        println!("Patch failed at {next:04} {}", mail.subject);
+        println!("When you have resolved this problem, run \"rgit am --continue\".");
+        println!("If you prefer to skip this patch, run \"rgit am --skip\" instead.");
+        println!("To restore the original branch and stop patching, run \"rgit am --abort\".");
+        Ok(())
+    }
+
+    /// 用户手动应用并 `add` 之后，用补丁的作者和信息提交，再继续后面的补丁
+    pub fn am_continue(&self) -> Result<(), DateErr> {
+        let (next, mail) = self.am_current()?;
+        let head_tree = self
+            .get_ref_if_not_empty(data::HEAD)
+            .and_then(|head| self.get_commit(head.value))
+            .and_then(|commit| commit.tree);
+        if head_tree == Some(self.write_tree()?) {
+            println!("Applying: {}", mail.subject);
+            println!("No changes - did you forget to use 'rgit add'?");
+            println!("If there is nothing left to stage, chances are that something else");
+            println!("already introduced the same changes; you might want to skip this patch.");
+            return self.am_stopped(next, &mail);
+        }
+
+        println!("Applying: {}", mail.subject);
+        self.commit_with_author(&mail.message, mail.author)?;
+        fs::write(self.am_dir().join("next"), (next + 1).to_string())?;
+        self.am_run()
+    }
+
+    /// 丢掉当前补丁的修改，继续后面的补丁
+    pub fn am_skip(&self) -> Result<(), DateErr> {
+        let (next, _) = self.am_current()?;
+        if let Some(head) = self.get_ref_if_not_empty(data::HEAD) {
+            self.reset(head.value, ResetMode::Hard, true);
+        }
+        fs::write(self.am_dir().join("next"), (next + 1).to_string())?;
+        self.am_run()
+    }
+
+    /// 回到开始 `am` 之前的提交，删除保存的进度
+    pub fn am_abort(&self) -> Result<(), DateErr> {
+        let orig_head = fs::read_to_string(self.am_dir().join("orig-head")).map_err(|_| {
+            DateErr::Err(String::from(
+                "Resolve operation not in progress, we are not resuming.",
+            ))
+        })?;
+        if !orig_head.trim().is_empty() {
+            self.reset(orig_head.trim().to_string(), ResetMode::Hard, true);
+        }
+        fs::remove_dir_all(self.am_dir())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAIL: &str = "\
+From: =?UTF-8?q?J=C3=B6rg_M?= <jm@example.com>
+Date: Tue, 14 Nov 2023 16:43:20 -0530
+Subject: [PATCH 1/2] Fix the
+ parser
+
+";
+
+    #[test]
+    fn mbox_round_trip() {
+        let body = "From the start\n>From quoted\nplain\n\nFrom here on\n";
+        let mut mbox = String::new();
+        for subject in ["one", "two"] {
+            mbox.push_str(
+                "From 0000000000000000000000000000000000000000 Mon Sep 17 00:00:00 2001\n",
+            );
+            mbox.push_str(&format!(
+                "From: A <a@example.com>\nSubject: [PATCH] {subject}\n\n"
+            ));
+            mbox.push_str(&escape_from(body));
+            mbox.push_str("---\ndiff --git a/f b/f\n\n");
+        }
+        assert!(!escape_from(body)
+            .lines()
+            .any(|line| line.starts_with("From ")));
+
+        let mails = split_mbox(&mbox);
+        assert_eq!(mails.len(), 2);
+        let mail = parse_mail(&mails[1]).unwrap();
+        assert_eq!(mail.subject, "two");
+        assert_eq!(mail.message, format!("two\n\n{}", body.trim()));
+        assert_eq!(mail.diff, "diff --git a/f b/f\n\n");
+    }
+
+    #[test]
+    fn single_mail_without_separator() {
+        assert_eq!(split_mbox(MAIL), vec![MAIL.to_string()]);
+        assert!(split_mbox("").is_empty());
+        assert!(parse_mail("Subject: no author\n\n").is_err());
+    }
+
+    #[test]
+    fn mail_headers() {
+        let mail = parse_mail(MAIL).unwrap();
+        assert_eq!(mail.author.name, "Jörg M");
+        assert_eq!(mail.author.email, "jm@example.com");
+        assert_eq!((mail.author.time, mail.author.offset), (1700000000, -330));
+        assert_eq!(mail.subject, "Fix the parser");
+        assert_eq!(mail.message, "Fix the parser");
+        assert_eq!(mail.diff, "");
+
+        for value in ["plain", "Jörg", "日本語 text"] {
+            assert_eq!(decode_header(&encode_header(value)), value);
+        }
+        assert_eq!(
+            decode_header("=?UTF-8?b?SsO2cmc=?= =?UTF-8?q?_M?="),
+            "Jörg M"
+        );
+        assert_eq!(strip_subject("Re: [PATCH v2 3/4] [x] subject"), "subject");
+        assert_eq!(sanitize_subject("Fix: the  parser!"), "Fix-the-parser");
+    }
+
+    #[test]
+    fn mail_dates() {
+        for (time, offset) in [
+            (1700000000, -330),
+            (1700000000, 480),
+            (-34560000, 0),
+            (0, 0),
+        ] {
+            let formatted = date::format_rfc2822(time, offset);
+            assert_eq!(date::parse_rfc2822(&formatted), Some((time, offset)));
+        }
+        assert_eq!(
+            date::format_rfc2822(1700000000, -330),
+            "Tue, 14 Nov 2023 16:43:20 -0530"
+        );
+        assert_eq!(
+            date::format_rfc2822(-34560000, 0),
+            "Wed, 27 Nov 1968 00:00:00 +0000"
+        );
+        assert_eq!(
+            date::parse_date("1700000000 +0800"),
+            Some((1700000000, 480))
+        );
+        assert_eq!(date::parse_date("@1700000000"), Some((1700000000, 0)));
+        assert_eq!(
+            date::parse_date("14 Nov 2023 22:13"),
+            Some((1700000000 - 20, 0))
+        );
+        assert_eq!(date::parse_date("yesterday"), None);
+    }
+}
@@ -2,20 +2,36 @@ use std::{
     collections::{HashMap, HashSet},
     fs::File,
     io::Read,
-    path::{self},
+    path::{self, PathBuf},
+    process,
 };
 
 use clap::Parser;
 use rgit::{
-    base::Commit,
-    cli::{Cli, Commands},
-    data::{self, Ugit},
-    diff,
+    apply::{self, ApplyOptions},
+    base::{AddOptions, FastForward, MergeFlags, ResetMode},
+    cli::{Cli, Commands, RevisionArgs},
+    color,
+    data::{self, DateErr, Ugit},
+    diff::{self, DiffFormat, DiffOptions},
+    graph::Graph,
+    mailbox::FormatPatchOptions,
+    pager,
+    pathspec::Pathspec,
+    pretty::{self, Format, PrettyOptions},
+    rename::RenameOptions,
+    revision::{MissingAction, RevList},
+    revwalk::{Order, RevWalk},
 };
 
 fn main() {
     let cli = Cli::parse();
 
+    if !cli.no_pager && cli.command.uses_pager() {
+        pager::setup(&Ugit::default());
+    }
+    let color = color::use_color(cli.color, &Ugit::default());
+
     match cli.command {
         Commands::Init => Ugit::default().init_repo(),
         Commands::HashObject { file } => match File::open(&file) {
@@ -49,12 +65,34 @@ fn main() {
         }
         Commands::Commit { message } => {
             let ugit = Ugit::default();
-            println!("{:?}", ugit.commit(&message))
+            match message.or_else(|| ugit.get_merge_msg()) {
+                Some(message) => println!("{:?}", ugit.commit(&message)),
+                None => eprintln!("Aborting commit due to empty commit message."),
+            }
         }
-        Commands::Log { oid } => {
-            log(oid);
+        Commands::Log {
+            revs,
+            paths,
+            patch,
+            graph,
+            revisions,
+            pretty,
+            diff,
+        } => {
+            let options = diff.options(color);
+            let show_diff = patch || options.format != DiffFormat::Patch;
+            let graph = graph.then(|| Graph::new(color));
+            let pretty = pretty.options(color);
+            log(
+                revs,
+                paths,
+                &revisions,
+                show_diff.then_some(options),
+                &pretty,
+                graph,
+            );
         }
-        Commands::CheckOut { commit } => Ugit::default().checkout(commit),
+        Commands::CheckOut { commit, force } => Ugit::default().checkout(commit, force),
         Commands::Tag { name, oid } => {
             let ugit = Ugit::default();
             let oid = if let Some(oid) = oid {
@@ -69,13 +107,96 @@ fn main() {
             }
         }
         Commands::K => k(),
-        Commands::Branch { name, oid } => branch(name, oid),
-        Commands::Status => status(),
-        Commands::Reset { oid } => reset(oid),
-        Commands::Show { oid } => show(oid),
-        Commands::Diff { oid, cached } => diff(oid, cached),
-        Commands::Merge { commit } => merge(commit),
-        Commands::MergeBase { commit1, commit2 } => merge_base(commit1, commit2),
+        Commands::Branch { name, oid } => branch(name, oid, color),
+        Commands::Status => status(color),
+        Commands::Reset {
+            oid,
+            soft,
+            mixed: _,
+            hard,
+            keep,
+            force,
+            patch,
+            paths,
+        } => {
+            if patch {
+                let ugit = Ugit::default();
+                if let Some(oid) = resolve_or_head(&ugit, oid.as_deref()) {
+                    ugit.reset_patch(&oid, &paths);
+                }
+                return;
+            }
+
+            let mode = if soft {
+                ResetMode::Soft
+            } else if hard {
+                ResetMode::Hard
+            } else if keep {
+                ResetMode::Keep
+            } else {
+                ResetMode::Mixed
+            };
+            reset(oid, mode, force, paths)
+        }
+        Commands::Show { oid, pretty, diff } => {
+            show(oid, &diff.options(color), &pretty.options(color))
+        }
+        Commands::Diff {
+            revs,
+            cached,
+            paths,
+            diff,
+        } => self::diff(revs, cached, paths, &diff.options(color)),
+        Commands::Merge {
+            commits,
+            force,
+            conflict,
+            no_ff,
+            ff_only,
+            squash,
+            strategy,
+            strategy_option,
+            abort,
+            r#continue,
+        } => {
+            let ugit = Ugit::default();
+            if abort {
+                ugit.merge_abort();
+            } else if r#continue {
+                println!("{:?}", ugit.merge_continue());
+            } else {
+                let fast_forward = match (no_ff, ff_only) {
+                    (true, _) => FastForward::Never,
+                    (_, true) => FastForward::Only,
+                    _ => FastForward::Allow,
+                };
+                let flags = MergeFlags {
+                    force,
+                    style: conflict,
+                    fast_forward,
+                    squash,
+                    strategy,
+                    strategy_options: strategy_option,
+                };
+                ugit.merge(&commits, &flags);
+            }
+        }
+        Commands::MergeBase {
+            commits,
+            all,
+            octopus,
+            is_ancestor,
+            fork_point,
+        } => merge_base(commits, all, octopus, is_ancestor, fork_point),
+        Commands::RevList {
+            revs,
+            paths,
+            objects,
+            count,
+            left_right,
+            missing,
+            revisions,
+        } => rev_list(revs, paths, &revisions, objects, count, left_right, missing),
         Commands::Fetch { remote } => {
             let mut ugit = Ugit::default();
             ugit.fetch(remote);
@@ -92,43 +213,275 @@ fn main() {
                 ),
             );
         }
-        Commands::Add { files } => {
+        Commands::Add {
+            files,
+            update,
+            all,
+            dry_run,
+            patch,
+        } => {
+            let ugit = Ugit::default();
+            if patch {
+                ugit.add_patch(&files);
+                return;
+            }
+
+            ugit.add(
+                &files,
+                &AddOptions {
+                    update,
+                    all,
+                    dry_run,
+                },
+            );
+        }
+        Commands::Rm {
+            files,
+            cached,
+            r,
+            force,
+        } => Ugit::default().rm(&files, cached, r, force),
+        Commands::Mv { mut files, force } => {
+            if let Some(destination) = files.pop() {
+                Ugit::default().mv(&files, &destination, force);
+            }
+        }
+        Commands::Apply {
+            patches,
+            cached,
+            index,
+            check,
+            reverse,
+            three_way,
+            context,
+        } => {
+            let options = ApplyOptions {
+                cached,
+                index: index || three_way,
+                check,
+                reverse,
+                three_way,
+                min_context: context,
+            };
+            apply(patches, &options);
+        }
+        Commands::FormatPatch {
+            range,
+            output_directory,
+            stdout,
+            root,
+        } => {
+            let options = FormatPatchOptions {
+                output_directory: output_directory.map(PathBuf::from),
+                stdout,
+                root,
+            };
+            match Ugit::default().format_patch(&range, &options) {
+                Ok(files) => {
+                    for file in files {
+                        println!("{}", file.display());
+                    }
+                }
+                Err(err) => {
+                    eprintln!("format-patch failed, err:{:?}", err);
+                    process::exit(1);
+                }
+            }
+        }
+        Commands::Am {
+            mbox,
+            r#continue,
+            skip,
+            abort,
+        } => {
             let ugit = Ugit::default();
-            ugit.add(&files);
+            let result = if r#continue {
+                ugit.am_continue()
+            } else if skip {
+                ugit.am_skip()
+            } else if abort {
+                ugit.am_abort()
+            } else {
+                ugit.am(&mbox)
+            };
+            if let Err(err) = result {
+                match err {
+                    DateErr::Err(message) => eprintln!("error: {message}"),
+                    err => eprintln!("am failed, err:{:?}", err),
+                }
+                process::exit(1);
+            }
+            //停在某个补丁上等用户处理
+            if ugit.am_in_progress() {
+                process::exit(1);
+            }
         }
     }
 }
 
-fn log(oid: Option<String>) {
+fn log(
+    revs: Vec<String>,
+    paths: Vec<String>,
+    args: &RevisionArgs,
+    patch: Option<DiffOptions>,
+    pretty: &PrettyOptions,
+    mut graph: Option<Graph>,
+) {
     let ugit = Ugit::default();
-    let head = if let Some(oid) = oid {
-        ugit.get_oid(oid)
-    } else {
-        match ugit.get_ref_if_not_empty(data::HEAD).map(|head| head.value) {
-            Some(head) => head,
-            None => {
-                eprintln!("No Commits");
-                return;
+    let revisions = match ugit.parse_revisions(&args.revisions(revs)) {
+        Ok(revisions) if revisions.include.is_empty() && revisions.exclude.is_empty() => {
+            eprintln!("No Commits");
+            return;
+        }
+        Ok(revisions) => revisions,
+        Err(err) => {
+            match err {
+                DateErr::Err(message) => eprintln!("fatal: {message}"),
+                err => eprintln!("fatal: {err:?}"),
             }
+            process::exit(128);
         }
     };
 
+    let mut options = args.walk_options();
+    //画图时子提交必须在父提交前面
+    if graph.is_some() && options.order == Order::Default {
+        options.order = Order::Topo;
+    }
+    let walk = RevWalk::new(&ugit, revisions.include, revisions.exclude, options)
+        .paths(Pathspec::new(&paths));
+    let mut commits = RevList::new(&ugit, walk, args.filter());
+    let pathspec = Pathspec::new(&paths);
+
+    let refs = refs_by_oid(&ugit);
+    let head_branch = ugit.get_branch_name();
+    let mut first = true;
+    while let Some(oid) = commits.next() {
+        if let Some(commit) = ugit.get_commit(&oid) {
+            let separator = if first { "" } else { pretty.separator() };
+            first = false;
+
+            let refs = refs.get(&oid).map(Vec::as_slice).unwrap_or_default();
+            let decorations = pretty::decorations(refs, head_branch.as_deref(), pretty.color);
+            let mut entry = pretty::format_commit(&oid, &commit, &decorations, pretty);
+
+            //和 git 一样，合并提交默认不输出 patch
+            if let Some(options) = patch.as_ref().filter(|_| commit.parents.len() <= 1) {
+                if pretty.blank_before_diff() {
+                    entry.push('\n');
+                }
+                entry.push_str(&ugit.diff_commit_paths(&commit, &pathspec, options));
+            }
+
+            match graph.as_mut() {
+                //图的每一行都是完整的一行，`format:` 模板之间不再需要换行分隔
+                Some(graph) => {
+                    if separator == "\n" && matches!(pretty.format, Format::Builtin(_)) {
+                        pager::print(&graph.pad(separator));
+                    }
+                    pager::print(&graph.commit(&oid, &commits.parents(&oid), &entry));
+                }
+                None => pager::print(&format!("{separator}{entry}")),
+            }
+        }
+    }
+}
+
+fn rev_list(
+    revs: Vec<String>,
+    paths: Vec<String>,
+    args: &RevisionArgs,
+    objects: bool,
+    count: bool,
+    left_right: bool,
+    missing: MissingAction,
+) {
+    let revs = args.revisions(revs);
+    if revs.is_empty() {
+        eprintln!("usage: rgit rev-list [<options>] <commit>... [-- <path>...]");
+        process::exit(129);
+    }
+
+    let ugit = Ugit::default();
+    let revisions = match ugit.parse_revisions(&revs) {
+        Ok(revisions) => revisions,
+        Err(err) => {
+            match err {
+                DateErr::Err(message) => eprintln!("fatal: {message}"),
+                err => eprintln!("fatal: {err:?}"),
+            }
+            process::exit(128);
+        }
+    };
+
+    let walk = RevWalk::new(
+        &ugit,
+        revisions.include.clone(),
+        revisions.exclude.clone(),
+        args.walk_options(),
+    )
+    .paths(Pathspec::new(&paths));
+    let mut list = RevList::new(&ugit, walk, args.filter());
+    let commits = list.by_ref().collect::<Vec<_>>();
+
+    //`--left-right` 时左边能到达的提交标成 `<`，其余的标成 `>`
+    let left = ugit
+        .iter_commits_and_parents(revisions.left.clone())
+        .into_iter()
+        .collect::<HashSet<_>>();
+    let mark = |oid: &str| match (left_right, left.contains(oid)) {
+        (false, _) => "",
+        (true, true) => "<",
+        (true, false) => ">",
+    };
+
+    if count {
+        match left_right {
+            true => {
+                let lefts = commits.iter().filter(|oid| left.contains(*oid)).count();
+                pager::print(&format!("{lefts}\t{}\n", commits.len() - lefts));
+            }
+            false => pager::print(&format!("{}\n", commits.len())),
+        }
+        return;
+    }
+
+    for oid in &commits {
+        pager::print(&format!("{}{oid}\n", mark(oid)));
+    }
+
+    let mut missing_objects = list.missing().to_vec();
+    if objects {
+        for object in ugit.list_objects(&commits, &revisions.exclude) {
+            match object.missing {
+                true => missing_objects.push(object.oid),
+                false => pager::print(&format!("{} {}\n", object.oid, object.path)),
+            }
+        }
+    }
+
+    for oid in missing_objects {
+        match missing {
+            MissingAction::Error => {
+                eprintln!("fatal: missing object {oid}");
+                process::exit(1);
+            }
+            MissingAction::AllowAny => {}
+            MissingAction::Print => pager::print(&format!("?{oid}\n")),
+        }
+    }
+}
+
+/// 每个提交上有哪些引用
+fn refs_by_oid(ugit: &Ugit) -> HashMap<String, Vec<String>> {
     let mut refs: HashMap<String, Vec<String>> = HashMap::new();
     for ref_name in ugit.iter_refs() {
         if let Some(oid) = ugit.get_ref_if_not_empty(&ref_name) {
-            let refs = refs.entry(oid.value).or_default();
-            refs.push(ref_name);
+            refs.entry(oid.value).or_default().push(ref_name);
         }
     }
 
-    for oid in ugit.iter_commits_and_parents(vec![head]) {
-        if let Some(commit) = ugit.get_commit(&oid) {
-            match refs.get(&oid) {
-                Some(refs) => print_commit(&oid, &commit, refs),
-                None => print_commit(&oid, &commit, &[]),
-            };
-        }
-    }
+    refs
 }
 
 ///Render graph, you need to know well about graphviz tool first
@@ -163,7 +516,7 @@ fn k() {
     println!("{dot}");
 }
 
-fn branch(name: Option<String>, oid: Option<String>) {
+fn branch(name: Option<String>, oid: Option<String>, color: bool) {
     let ugit = Ugit::default();
     if let Some(name) = name {
         let oid = if let Some(oid) = oid {
@@ -183,13 +536,16 @@ fn branch(name: Option<String>, oid: Option<String>) {
     } else {
         let current = ugit.get_branch_name().unwrap_or_default();
         for name in ugit.iter_branch_names() {
-            let prefix = if name == current { "*" } else { " " };
-            println!("{prefix} {name}")
+            if name == current {
+                println!("* {}", color::paint(color, color::GREEN, &name));
+            } else {
+                println!("  {name}");
+            }
         }
     }
 }
 
-fn status() {
+fn status(color: bool) {
     let ugit = Ugit::default();
     let oid = ugit.get_oid(data::HEAD);
     let branch = ugit.get_branch_name();
@@ -199,11 +555,7 @@ fn status() {
         println!("HEAD detached at {oid:10}")
     }
 
-    let tree_id = match ugit
-        .get_ref_if_not_empty(&oid)
-        .and_then(|r| ugit.get_commit(r.value))
-        .and_then(|c| c.tree)
-    {
+    let tree_id = match ugit.get_commit(&oid).and_then(|c| c.tree) {
         Some(tree_id) => tree_id,
         None => return,
     };
@@ -211,28 +563,98 @@ fn status() {
     if let Some(ref_value) = ugit.get_ref_if_not_empty(data::MERGE_HEAD) {
         println!("Merging with {}", ref_value.value);
     }
+    if ugit.am_in_progress() {
+        println!("You are in the middle of an am session.");
+        println!("  (fix conflicts and then run \"rgit am --continue\")");
+        println!("  (use \"rgit am --skip\" to skip this patch)");
+        println!("  (use \"rgit am --abort\" to restore the original branch)");
+    }
+
+    let mut conflicts = ugit
+        .get_conflicts()
+        .unwrap_or_default()
+        .into_iter()
+        .collect::<Vec<_>>();
+    conflicts.sort_by(|a, b| a.0.cmp(&b.0));
+    let unmerged = |path: &PathBuf| {
+        conflicts
+            .iter()
+            .any(|(conflict, _)| path.as_os_str() == conflict.as_str())
+    };
 
     let index_tree = ugit.get_index_tree();
     if let Some(tree_map) = ugit.get_tree_in_base(&tree_id) {
-        let actions = diff::iter_changed_files(&tree_map, &index_tree);
+        let mut actions = diff::iter_changed_files(&tree_map, &index_tree);
+        let renames = ugit.detect_renames(&tree_map, &index_tree, &RenameOptions::default());
+        for rename in &renames {
+            actions.remove(&rename.from);
+            actions.remove(&rename.to);
+        }
+
         println!("\nChanges to be committed:");
+        for rename in renames {
+            let line = format!("{:>12}: {:?} -> {:?}", "renamed", rename.from, rename.to);
+            println!("{}", color::paint(color, color::GREEN, &line));
+        }
         for (path, action) in actions {
-            println!("{:>12}: {:?}", action, path);
+            if !unmerged(&path) {
+                let line = format!("{:>12}: {:?}", action, path);
+                println!("{}", color::paint(color, color::GREEN, &line));
+            }
+        }
+    }
+
+    if !conflicts.is_empty() {
+        println!("\nUnmerged paths:");
+        for (path, stages) in &conflicts {
+            let line = format!("{:>12}: {:?}", stages.description(), path);
+            println!("{}", color::paint(color, color::RED, &line));
         }
     }
 
     let actions = diff::iter_changed_files(&index_tree, &ugit.get_working_tree());
     println!("\nChanges not staged for commit:");
     for (path, action) in actions {
-        println!("{:>12}: {:?}", action, path);
+        if !unmerged(&path) {
+            let line = format!("{:>12}: {:?}", action, path);
+            println!("{}", color::paint(color, color::RED, &line));
+        }
+    }
+}
+
+fn reset(oid: Option<String>, mode: ResetMode, force: bool, paths: Vec<String>) {
+    let ugit = Ugit::default();
+    let Some(oid) = resolve_or_head(&ugit, oid.as_deref()) else {
+        return;
+    };
+    if paths.is_empty() {
+        ugit.reset(oid, mode, force)
+    } else if mode != ResetMode::Mixed {
+        eprintln!("Cannot do {mode:?} reset with paths.");
+    } else {
+        ugit.reset_paths(&oid, &paths)
     }
 }
 
-fn reset(oid: String) {
-    Ugit::default().reset(oid)
+/// 解析 `HEAD~1` 这样的修订，没有给出时是 HEAD，还没有提交时也照样返回
+fn resolve_or_head(ugit: &Ugit, rev: Option<&str>) -> Option<String> {
+    let Some(rev) = rev else {
+        return Some(ugit.get_oid(data::HEAD));
+    };
+    match ugit.resolve_commit(rev) {
+        Ok(oid) => Some(oid),
+        Err(DateErr::Err(message)) => {
+            eprintln!("fatal: {message}");
+            None
+        }
+        Err(err) => {
+            eprintln!("fatal: {err:?}");
+            None
+        }
+    }
 }
 
-fn show(oid: Option<String>) {
+fn show(oid: Option<String>, options: &DiffOptions, pretty: &PrettyOptions) {
     let ugit = Ugit::default();
     let oid = if let Some(oid) = oid {
         ugit.get_oid(oid)
@@ -241,68 +663,132 @@ fn show(oid: Option<String>) {
     };
 
     match ugit.get_commit(&oid) {
-        Some(commit) => print_commit(&oid, &commit, &[]),
+        Some(commit) => {
+            let refs = refs_by_oid(&ugit);
+            let refs = refs.get(&oid).map(Vec::as_slice).unwrap_or_default();
+            let head_branch = ugit.get_branch_name();
+            let decorations = pretty::decorations(refs, head_branch.as_deref(), pretty.color);
+            let mut output = pretty::format_commit(&oid, &commit, &decorations, pretty);
+            if pretty.blank_before_diff() {
+                output.push('\n');
+            }
+            output.push_str(&ugit.diff_commit(&commit, options));
+            pager::print(&output);
+        }
         None => {
             eprint!("Show command can't not find commit, oid:{}", oid);
         }
     }
 }
 
-fn print_commit(oid: &str, commit: &Commit, refs: &[String]) {
-    let refs_str = refs.join(",");
-    println!("commit {oid} {refs_str}");
-    println!(
-        "       {}",
-        if let Some(msg) = commit.message.as_ref() {
-            &msg
-        } else {
-            ""
-        }
-    );
-}
-
-fn diff(oid: Option<String>, cached: bool) {
-    let ugit = Ugit::default();
-    let oid = if let Some(oid) = oid {
-        Some(ugit.get_oid(oid))
-    } else {
-        oid
+fn apply(files: Vec<String>, options: &ApplyOptions) {
+    let mut text = String::new();
+    let read = match files.is_empty() {
+        true => std::io::stdin().read_to_string(&mut text).map(|_| ()),
+        false => files.iter().try_for_each(|file| {
+            text.push_str(&std::fs::read_to_string(file)?);
+            Ok(())
+        }),
     };
-
-    let mut tree_from = None;
-    if let Some(oid) = oid.as_ref() {
-        tree_from = ugit.get_tree_in_base(oid);
+    if let Err(err) = read {
+        eprintln!("error: can't read patch: {err}");
+        process::exit(128);
     }
 
-    let tree_to = if cached {
-        let tree_to = Some(ugit.get_index_tree());
-        if oid.is_none() {
-            tree_from = ugit
-                .get_commit(ugit.get_oid(data::HEAD))
-                .and_then(|commit| commit.tree)
-                .and_then(|tree| ugit.get_tree_in_base(&tree));
+    let patches = match apply::parse_patch(&text) {
+        Ok(patches) if patches.is_empty() => {
+            eprintln!("error: No valid patches in input");
+            process::exit(128);
         }
-
-        tree_to
-    } else {
-        let tree_to = Some(ugit.get_working_tree());
-        if oid.is_none() {
-            tree_from = Some(ugit.get_index_tree());
+        Ok(patches) => patches,
+        Err(err) => {
+            eprintln!("error: {err}");
+            process::exit(128);
         }
-        tree_to
     };
 
-    if let (Some(t_from), Some(t_to)) = (tree_from, tree_to) {
-        let output = diff::diff_tree(&t_from, &t_to);
-        println!("{output}");
+    match Ugit::default().apply_patches(&patches, options) {
+        Ok(result) => {
+            let mut checking = None;
+            for hunk in &result.hunks {
+                if checking != Some(&hunk.path) {
+                    eprintln!("Checking patch {}...", hunk.path.display());
+                    checking = Some(&hunk.path);
+                }
+                eprintln!("{hunk}");
+            }
+            for path in &result.conflicts {
+                println!("U {}", path.display());
+            }
+            if !result.conflicts.is_empty() {
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            match err {
+                DateErr::Err(message) => eprintln!("error: {message}"),
+                err => eprintln!("error: {err:?}"),
+            }
+            process::exit(1);
+        }
     }
 }
 
-fn merge(commit: String) {
-    Ugit::default().merge(&commit);
+fn diff(revs: Vec<String>, cached: bool, paths: Vec<String>, options: &DiffOptions) {
+    let ugit = Ugit::default();
+    match ugit.diff_revs(&revs, cached, &Pathspec::new(&paths), options) {
+        Ok(changes) => pager::print(&diff::format_changes(&changes, options)),
+        Err(err) => eprintln!("diff failed, err:{:?}", err),
+    }
 }
 
-fn merge_base(commit1: String, commit2: String) {
+fn merge_base(commits: Vec<String>, all: bool, octopus: bool, is_ancestor: bool, fork_point: bool) {
     let ugit = Ugit::default();
-    println!("merge_base: {:?}", ugit.get_merge_base(&commit1, &commit2));
+
+    if fork_point {
+        let (refname, commit) = match commits.as_slice() {
+            [refname] => (refname, String::from(data::HEAD)),
+            [refname, commit] => (refname, commit.clone()),
+            _ => {
+                eprintln!("usage: rgit merge-base --fork-point <ref> [<commit>]");
+                return;
+            }
+        };
+        if let Some(oid) = ugit.get_fork_point(refname, &ugit.get_oid(commit)) {
+            println!("{oid}");
+        }
+        return;
+    }
+
+    let oids = commits
+        .iter()
+        .map(|commit| ugit.get_oid(commit))
+        .collect::<Vec<_>>();
+    if is_ancestor {
+        match oids.as_slice() {
+            [ancestor, descendant] => {
+                if !ugit.is_ancestor_of(descendant, ancestor) {
+                    process::exit(1);
+                }
+            }
+            _ => eprintln!("usage: rgit merge-base --is-ancestor <commit> <commit>"),
+        }
+        return;
+    }
+
+    let bases = match oids.split_first() {
+        _ if octopus => ugit.get_octopus_merge_bases(&oids),
+        Some((one, others)) if !others.is_empty() => ugit.get_merge_bases(one, others),
+        _ => {
+            eprintln!("usage: rgit merge-base [-a] <commit> <commit>...");
+            return;
+        }
+    };
+
+    if bases.is_empty() {
+        process::exit(1);
+    }
+    for oid in bases.iter().take(if all { bases.len() } else { 1 }) {
+        println!("{oid}");
+    }
 }
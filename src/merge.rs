@@ -0,0 +1,473 @@
+use std::{borrow::Cow, str::FromStr};
+
+use crate::line_diff::{self, Algorithm, Edit, Whitespace};
+
+/// 冲突区域的输出格式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictStyle {
+    /// 只显示双方的版本
+    #[default]
+    Merge,
+    /// 额外显示共同祖先的版本
+    Diff3,
+    /// 同 diff3，但把双方相同的开头和结尾移出冲突区域
+    Zdiff3,
+}
+
+impl FromStr for ConflictStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "merge" => Ok(ConflictStyle::Merge),
+            "diff3" => Ok(ConflictStyle::Diff3),
+            "zdiff3" => Ok(ConflictStyle::Zdiff3),
+            _ => Err(format!("unknown conflict style: {s}")),
+        }
+    }
+}
+
+/// 三方合并的选项
+#[derive(Clone, Debug)]
+pub struct MergeOptions {
+    pub style: ConflictStyle,
+    pub algorithm: Algorithm,
+    /// 冲突标记里 ours/base/theirs 的名字
+    pub ours_label: String,
+    pub base_label: String,
+    pub theirs_label: String,
+    /// 冲突时直接采用哪一边，`-X ours`/`-X theirs`
+    pub favor: Option<Favor>,
+    /// 比较时忽略的空白，`-X ignore-space-change` 等
+    pub whitespace: Whitespace,
+    /// 不打印合并过程，合并虚拟祖先时使用
+    pub quiet: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Favor {
+    Ours,
+    Theirs,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        Self {
+            style: ConflictStyle::default(),
+            algorithm: Algorithm::default(),
+            ours_label: String::from("ours"),
+            base_label: String::from("base"),
+            theirs_label: String::from("theirs"),
+            favor: None,
+            whitespace: Whitespace::default(),
+            quiet: false,
+        }
+    }
+}
+
+impl MergeOptions {
+    /// 打印合并过程中的信息
+    pub fn report(&self, message: String) {
+        if !self.quiet {
+            println!("{message}");
+        }
+    }
+}
+
+/// 双方都修改了同一段内容
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConflictRegion {
+    pub base: Vec<String>,
+    pub ours: Vec<String>,
+    pub theirs: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MergeResult {
+    Clean(String),
+    /// `content` 中带有冲突标记
+    Conflicted {
+        content: String,
+        regions: Vec<ConflictRegion>,
+    },
+}
+
+impl MergeResult {
+    pub fn is_clean(&self) -> bool {
+        matches!(self, MergeResult::Clean(_))
+    }
+
+    pub fn content(&self) -> &str {
+        match self {
+            MergeResult::Clean(content) => content,
+            MergeResult::Conflicted { content, .. } => content,
+        }
+    }
+}
+
+/// 以 `base` 为共同祖先合并 `ours` 和 `theirs`
+pub fn merge3(base: &str, ours: &str, theirs: &str, options: &MergeOptions) -> MergeResult {
+    let base_lines = line_diff::split_lines(base);
+    let our_lines = line_diff::split_lines(ours);
+    let their_lines = line_diff::split_lines(theirs);
+
+    //忽略空白时用规整过的行来比较，输出的仍然是原来的行
+    let base_keys = comparable(&base_lines, options);
+    let our_keys = comparable(&our_lines, options);
+    let their_keys = comparable(&their_lines, options);
+
+    let ours_match = match_base(&base_keys, &our_keys, options.algorithm);
+    let theirs_match = match_base(&base_keys, &their_keys, options.algorithm);
+
+    let mut chunks = vec![];
+    let (mut i, mut a, mut b) = (0, 0, 0);
+    loop {
+        let stable = a;
+        while i < base_lines.len() && ours_match[i] == Some(a) && theirs_match[i] == Some(b) {
+            i += 1;
+            a += 1;
+            b += 1;
+        }
+        if a > stable {
+            chunks.push(Chunk::Clean(to_owned(&our_lines[stable..a])));
+        }
+
+        if i == base_lines.len() && a == our_lines.len() && b == their_lines.len() {
+            break;
+        }
+
+        //下一个双方都保留的基准行
+        let (j, ja, jb) = (i..base_lines.len())
+            .find_map(|j| match (ours_match[j], theirs_match[j]) {
+                (Some(ja), Some(jb)) => Some((j, ja, jb)),
+                _ => None,
+            })
+            .unwrap_or((base_lines.len(), our_lines.len(), their_lines.len()));
+
+        let (base_key, our_key, their_key) =
+            (&base_keys[i..j], &our_keys[a..ja], &their_keys[b..jb]);
+        let (our_chunk, their_chunk) = (&our_lines[a..ja], &their_lines[b..jb]);
+
+        if their_key == base_key {
+            chunks.push(Chunk::Clean(to_owned(our_chunk)));
+        } else if our_key == base_key || our_key == their_key {
+            chunks.push(Chunk::Clean(to_owned(their_chunk)));
+        } else {
+            chunks.push(Chunk::Conflict(ConflictRegion {
+                base: to_owned(&base_lines[i..j]),
+                ours: to_owned(our_chunk),
+                theirs: to_owned(their_chunk),
+            }));
+        }
+
+        i = j;
+        a = ja;
+        b = jb;
+    }
+
+    if options.style == ConflictStyle::Merge {
+        chunks = simplify(refine(chunks, options.algorithm));
+    }
+
+    let mut content = String::new();
+    let mut regions = vec![];
+    for chunk in chunks {
+        match chunk {
+            Chunk::Clean(lines) => content.push_str(&lines.concat()),
+            Chunk::Conflict(region) => match options.favor {
+                Some(Favor::Ours) => content.push_str(&region.ours.concat()),
+                Some(Favor::Theirs) => content.push_str(&region.theirs.concat()),
+                None => {
+                    write_conflict(&mut content, &region, options);
+                    regions.push(region);
+                }
+            },
+        }
+    }
+
+    if regions.is_empty() {
+        MergeResult::Clean(content)
+    } else {
+        MergeResult::Conflicted { content, regions }
+    }
+}
+
+enum Chunk {
+    Clean(Vec<String>),
+    Conflict(ConflictRegion),
+}
+
+/// 再比较一次冲突区域里双方的内容，双方相同的行移出冲突
+fn refine(chunks: Vec<Chunk>, algorithm: Algorithm) -> Vec<Chunk> {
+    let mut refined = vec![];
+    for chunk in chunks {
+        let region = match chunk {
+            Chunk::Conflict(region) => region,
+            clean => {
+                refined.push(clean);
+                continue;
+            }
+        };
+
+        let ours = region.ours.iter().map(String::as_str).collect::<Vec<_>>();
+        let theirs = region.theirs.iter().map(String::as_str).collect::<Vec<_>>();
+        let mut common = vec![];
+        let mut pending: Option<ConflictRegion> = None;
+        for edit in line_diff::diff_lines(&ours, &theirs, algorithm) {
+            if let Edit::Equal(o, _) = edit {
+                if let Some(conflict) = pending.take() {
+                    refined.push(Chunk::Conflict(conflict));
+                }
+                common.push(region.ours[o].clone());
+                continue;
+            }
+
+            if !common.is_empty() {
+                refined.push(Chunk::Clean(std::mem::take(&mut common)));
+            }
+            let conflict = pending.get_or_insert_with(|| ConflictRegion {
+                base: region.base.clone(),
+                ours: vec![],
+                theirs: vec![],
+            });
+            match edit {
+                Edit::Delete(o) => conflict.ours.push(region.ours[o].clone()),
+                Edit::Insert(t) => conflict.theirs.push(region.theirs[t].clone()),
+                Edit::Equal(..) => {}
+            }
+        }
+
+        if let Some(conflict) = pending {
+            refined.push(Chunk::Conflict(conflict));
+        }
+        if !common.is_empty() {
+            refined.push(Chunk::Clean(common));
+        }
+    }
+
+    refined
+}
+
+/// 和 git 一样，间隔少于三行或者间隔里没有字母数字的两个冲突合并成一个
+fn simplify(chunks: Vec<Chunk>) -> Vec<Chunk> {
+    let mut simplified: Vec<Chunk> = vec![];
+    for chunk in chunks {
+        let region = match chunk {
+            Chunk::Conflict(region) => region,
+            Chunk::Clean(lines) => {
+                match simplified.last_mut() {
+                    Some(Chunk::Clean(last)) => last.extend(lines),
+                    _ => simplified.push(Chunk::Clean(lines)),
+                }
+                continue;
+            }
+        };
+
+        let mergeable = match simplified.as_slice() {
+            [.., Chunk::Conflict(_), Chunk::Clean(gap)] => {
+                gap.len() < 3
+                    || !gap
+                        .iter()
+                        .any(|line| line.chars().any(char::is_alphanumeric))
+            }
+            _ => false,
+        };
+
+        if !mergeable {
+            simplified.push(Chunk::Conflict(region));
+            continue;
+        }
+
+        if let (Some(Chunk::Clean(gap)), Some(Chunk::Conflict(mut previous))) =
+            (simplified.pop(), simplified.pop())
+        {
+            previous.base.extend(gap.iter().cloned());
+            previous.base.extend(region.base);
+            previous.ours.extend(gap.iter().cloned());
+            previous.ours.extend(region.ours);
+            previous.theirs.extend(gap);
+            previous.theirs.extend(region.theirs);
+            simplified.push(Chunk::Conflict(previous));
+        }
+    }
+
+    simplified
+}
+
+fn to_owned(lines: &[&str]) -> Vec<String> {
+    lines.iter().map(|line| line.to_string()).collect()
+}
+
+fn comparable<'a>(lines: &[&'a str], options: &MergeOptions) -> Vec<Cow<'a, str>> {
+    lines
+        .iter()
+        .map(|line| options.whitespace.normalize(line))
+        .collect()
+}
+
+/// 基准中每一行在另一边对应的行号，没保留的为 None
+fn match_base(base: &[Cow<str>], other: &[Cow<str>], algorithm: Algorithm) -> Vec<Option<usize>> {
+    let base = base.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    let other = other.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+
+    let mut matches = vec![None; base.len()];
+    for edit in line_diff::diff_lines(&base, &other, algorithm) {
+        if let Edit::Equal(b, o) = edit {
+            matches[b] = Some(o);
+        }
+    }
+
+    matches
+}
+
+fn write_conflict(content: &mut String, region: &ConflictRegion, options: &MergeOptions) {
+    let (ours, theirs) = (&region.ours, &region.theirs);
+
+    //双方相同的开头和结尾不算冲突，diff3 风格要完整显示所以不裁剪
+    let (prefix, suffix) = match options.style {
+        ConflictStyle::Diff3 => (0, 0),
+        ConflictStyle::Merge | ConflictStyle::Zdiff3 => {
+            let prefix = ours.iter().zip(theirs).take_while(|(a, b)| a == b).count();
+            let suffix = ours[prefix..]
+                .iter()
+                .rev()
+                .zip(theirs[prefix..].iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count();
+            (prefix, suffix)
+        }
+    };
+
+    for line in &ours[..prefix] {
+        content.push_str(line);
+    }
+
+    push_marker(content, '<', &options.ours_label);
+    push_lines(content, &ours[prefix..ours.len() - suffix]);
+    if options.style != ConflictStyle::Merge {
+        push_marker(content, '|', &options.base_label);
+        push_lines(content, &region.base);
+    }
+    push_marker(content, '=', "");
+    push_lines(content, &theirs[prefix..theirs.len() - suffix]);
+    push_marker(content, '>', &options.theirs_label);
+
+    for line in &ours[ours.len() - suffix..] {
+        content.push_str(line);
+    }
+}
+
+fn push_marker(content: &mut String, marker: char, label: &str) {
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+
+    content.extend(std::iter::repeat_n(marker, 7));
+    if !label.is_empty() {
+        content.push(' ');
+        content.push_str(label);
+    }
+    content.push('\n');
+}
+
+fn push_lines(content: &mut String, lines: &[String]) {
+    for line in lines {
+        content.push_str(line);
+    }
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge(base: &str, ours: &str, theirs: &str) -> MergeResult {
+        merge3(base, ours, theirs, &MergeOptions::default())
+    }
+
+    #[test]
+    fn clean_merges() {
+        let base = "a\nb\nc\nd\ne\n";
+        assert_eq!(
+            merge(base, "A\nb\nc\nd\ne\n", "a\nb\nc\nd\nE\n"),
+            MergeResult::Clean(String::from("A\nb\nc\nd\nE\n"))
+        );
+        assert_eq!(
+            merge(base, "a\nB\nc\nd\ne\n", "a\nB\nc\nd\ne\n"),
+            MergeResult::Clean(String::from("a\nB\nc\nd\ne\n"))
+        );
+        assert_eq!(
+            merge("", "", "a\n"),
+            MergeResult::Clean(String::from("a\n"))
+        );
+        assert_eq!(merge("", "", ""), MergeResult::Clean(String::new()));
+    }
+
+    #[test]
+    fn conflicting_hunks() {
+        let result = merge("a\nb\nc\n", "a\nx\nc\n", "a\ny\nc\n");
+        assert_eq!(
+            result.content(),
+            "a\n<<<<<<< ours\nx\n=======\ny\n>>>>>>> theirs\nc\n"
+        );
+        match result {
+            MergeResult::Conflicted { regions, .. } => assert_eq!(
+                regions,
+                vec![ConflictRegion {
+                    base: vec![String::from("b\n")],
+                    ours: vec![String::from("x\n")],
+                    theirs: vec![String::from("y\n")],
+                }]
+            ),
+            MergeResult::Clean(_) => panic!("expected a conflict"),
+        }
+    }
+
+    #[test]
+    fn conflict_styles() {
+        let (base, ours, theirs) = ("a\nb\n", "a\nx\nz\n", "a\ny\nz\n");
+        let options = |style| MergeOptions {
+            style,
+            ..Default::default()
+        };
+        assert_eq!(
+            merge3(base, ours, theirs, &options(ConflictStyle::Merge)).content(),
+            "a\n<<<<<<< ours\nx\n=======\ny\n>>>>>>> theirs\nz\n"
+        );
+        assert_eq!(
+            merge3(base, ours, theirs, &options(ConflictStyle::Diff3)).content(),
+            "a\n<<<<<<< ours\nx\nz\n||||||| base\nb\n=======\ny\nz\n>>>>>>> theirs\n"
+        );
+        assert_eq!(
+            merge3(base, ours, theirs, &options(ConflictStyle::Zdiff3)).content(),
+            "a\n<<<<<<< ours\nx\n||||||| base\nb\n=======\ny\n>>>>>>> theirs\nz\n"
+        );
+    }
+
+    #[test]
+    fn favor_a_side() {
+        let options = MergeOptions {
+            favor: Some(Favor::Theirs),
+            ..Default::default()
+        };
+        assert_eq!(
+            merge3("a\nb\n", "a\nx\n", "a\ny\n", &options),
+            MergeResult::Clean(String::from("a\ny\n"))
+        );
+    }
+
+    #[test]
+    fn missing_trailing_newline() {
+        let result = merge("a\n", "a\nb", "a\nc");
+        assert_eq!(
+            result.content(),
+            "a\n<<<<<<< ours\nb\n=======\nc\n>>>>>>> theirs\n"
+        );
+        assert_eq!(
+            merge("a\nb\n", "a\nb", "a\nb\n"),
+            MergeResult::Clean(String::from("a\nb"))
+        );
+    }
+}
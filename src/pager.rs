@@ -0,0 +1,122 @@
+use std::{
+    env,
+    io::{self, ErrorKind, IsTerminal, Write},
+    process::{self, Child},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use crate::data::Ugit;
+
+/// 当前进程的 stdout 已经接到分页器上
+static IN_USE: AtomicBool = AtomicBool::new(false);
+
+/// 正在运行的分页器，退出前要等它结束
+static PAGER: Mutex<Option<Child>> = Mutex::new(None);
+
+/// 当前进程的输出是不是通过管道交给了分页器
+pub fn in_use() -> bool {
+    IN_USE.load(Ordering::Relaxed)
+}
+
+/// 依次取 `RGIT_PAGER`、`core.pager`、`PAGER`，默认 `less -FRX`，`cat` 和空值表示不分页
+fn pager_command(ugit: &Ugit) -> Option<String> {
+    let pager = env::var("RGIT_PAGER")
+        .ok()
+        .or_else(|| ugit.get_config("core.pager"))
+        .or_else(|| env::var("PAGER").ok())
+        .unwrap_or_else(|| String::from("less -FRX"));
+
+    match pager.trim() {
+        "" | "cat" => None,
+        _ => Some(pager),
+    }
+}
+
+/// stdout 是终端时启动分页器，把当前进程的 stdout 换成通向分页器的管道，退出时等分页器结束
+#[cfg(unix)]
+pub fn setup(ugit: &Ugit) {
+    use std::{
+        os::fd::AsRawFd,
+        process::{Command, Stdio},
+    };
+
+    if in_use() || !io::stdout().is_terminal() {
+        return;
+    }
+    let pager = match pager_command(ugit) {
+        Some(pager) => pager,
+        None => return,
+    };
+
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(&pager).stdin(Stdio::piped());
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            eprintln!("unable to start pager {pager:?}: {err}");
+            return;
+        }
+    };
+
+    let stdin = match child.stdin.take() {
+        Some(stdin) => stdin,
+        None => return,
+    };
+    //SAFETY: 两个描述符都是打开的，dup2 只是让 1 号描述符也指向管道
+    if unsafe { libc::dup2(stdin.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+        eprintln!(
+            "unable to redirect output to pager: {}",
+            io::Error::last_os_error()
+        );
+        drop(stdin);
+        let _ = child.wait();
+        return;
+    }
+    drop(stdin);
+
+    if let Ok(mut running) = PAGER.lock() {
+        *running = Some(child);
+    }
+    IN_USE.store(true, Ordering::Relaxed);
+    //SAFETY: 注册的是不会 unwind 的 extern "C" 函数
+    unsafe {
+        libc::atexit(wait_for_pager);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn setup(_ugit: &Ugit) {}
+
+/// 关闭 stdout 让分页器读到结尾，再等用户退出分页器
+#[cfg(unix)]
+extern "C" fn wait_for_pager() {
+    let _ = io::stdout().flush();
+    //SAFETY: 之后不会再写 stdout
+    unsafe {
+        libc::close(libc::STDOUT_FILENO);
+    }
+    if let Some(mut child) = PAGER.lock().ok().and_then(|mut running| running.take()) {
+        let _ = child.wait();
+    }
+}
+
+/// 写到 stdout，读的一端已经关闭时，比如分页器提前退出，安静地结束进程
+pub fn print(text: &str) {
+    let mut stdout = io::stdout().lock();
+    if let Err(err) = stdout
+        .write_all(text.as_bytes())
+        .and_then(|_| stdout.flush())
+    {
+        if err.kind() == ErrorKind::BrokenPipe {
+            process::exit(0);
+        }
+        eprintln!("write stdout error:{:?}", err);
+        process::exit(1);
+    }
+}
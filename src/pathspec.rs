@@ -0,0 +1,157 @@
+use std::path::Path;
+
+use crate::base::normalize_path;
+
+/// 命令行上的路径过滤条件，支持目录、`*`、`?`、`[...]` 通配符和 `:(exclude)`、`:!`、`:^`
+#[derive(Debug, Default)]
+pub struct Pathspec {
+    includes: Vec<String>,
+    excludes: Vec<String>,
+}
+
+impl Pathspec {
+    pub fn new<T: AsRef<str>>(specs: &[T]) -> Self {
+        let mut pathspec = Pathspec::default();
+        for spec in specs {
+            let spec = spec.as_ref();
+            let exclude = [":(exclude)", ":!", ":^"]
+                .iter()
+                .find_map(|magic| spec.strip_prefix(magic));
+
+            match exclude {
+                Some(pattern) => pathspec.excludes.push(normalize_pattern(pattern)),
+                None => pathspec.includes.push(normalize_pattern(spec)),
+            }
+        }
+
+        pathspec
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.includes.is_empty() && self.excludes.is_empty()
+    }
+
+    /// 包含的模式，用来检查是否每个都至少匹配到一个文件
+    pub fn includes(&self) -> &[String] {
+        &self.includes
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        let path = path.to_string_lossy();
+        let included = self.includes.is_empty()
+            || self
+                .includes
+                .iter()
+                .any(|pattern| matches_pattern(pattern, &path));
+
+        included
+            && !self
+                .excludes
+                .iter()
+                .any(|pattern| matches_pattern(pattern, &path))
+    }
+}
+
+fn normalize_pattern(pattern: &str) -> String {
+    normalize_path(pattern).to_string_lossy().to_string()
+}
+
+/// 普通路径按前缀匹配，带通配符的按 glob 匹配整个路径
+pub fn matches_pattern(pattern: &str, path: &str) -> bool {
+    if pattern.is_empty() {
+        return true;
+    }
+
+    if !pattern.contains(['*', '?', '[']) {
+        return Path::new(path).starts_with(pattern);
+    }
+
+    glob_match(pattern.as_bytes(), path.as_bytes())
+}
+
+/// 简单的 fnmatch，`*` 可以跨越目录
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                while pattern.get(p) == Some(&b'*') {
+                    p += 1;
+                }
+                backtrack = Some((p, t));
+                continue;
+            }
+            Some(b'?') => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            Some(b'[') => {
+                if let Some((matched, len)) = match_class(&pattern[p..], text[t]) {
+                    if matched {
+                        p += len;
+                        t += 1;
+                        continue;
+                    }
+                } else if text[t] == b'[' {
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+            }
+            Some(b'\\') if pattern.get(p + 1) == Some(&text[t]) => {
+                p += 2;
+                t += 1;
+                continue;
+            }
+            Some(c) if *c == text[t] => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        match backtrack {
+            Some((star_p, star_t)) => {
+                p = star_p;
+                t = star_t + 1;
+                backtrack = Some((star_p, star_t + 1));
+            }
+            None => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+/// 匹配 `[abc]`、`[a-z]`、`[!a]`，返回是否匹配以及模式的长度
+fn match_class(pattern: &[u8], c: u8) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negate = matches!(pattern.get(i), Some(b'!') | Some(b'^'));
+    if negate {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    while let Some(&start) = pattern.get(i) {
+        if start == b']' && !first {
+            return Some((matched != negate, i + 1));
+        }
+        first = false;
+
+        if pattern.get(i + 1) == Some(&b'-') && pattern.get(i + 2).is_some_and(|end| *end != b']') {
+            let end = pattern[i + 2];
+            matched |= start <= c && c <= end;
+            i += 3;
+        } else {
+            matched |= start == c;
+            i += 1;
+        }
+    }
+
+    None
+}
@@ -0,0 +1,619 @@
+use std::str::FromStr;
+
+use crate::{
+    base::{Commit, Signature},
+    color, date,
+    diff::short_oid,
+};
+
+/// `--date` 的格式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DateFormat {
+    /// `Tue Nov 14 22:13:20 2023 +0000`
+    #[default]
+    Default,
+    /// `2023-11-14 22:13:20 +0000`
+    Iso,
+    /// `3 hours ago`
+    Relative,
+    /// 时间戳
+    Unix,
+    /// `Tue, 14 Nov 2023 22:13:20 +0000`
+    Rfc,
+    /// `2023-11-14`
+    Short,
+}
+
+impl FromStr for DateFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(DateFormat::Default),
+            "iso" | "iso8601" => Ok(DateFormat::Iso),
+            "relative" => Ok(DateFormat::Relative),
+            "unix" => Ok(DateFormat::Unix),
+            "rfc" | "rfc2822" => Ok(DateFormat::Rfc),
+            "short" => Ok(DateFormat::Short),
+            _ => Err(format!("unknown date format: {s}")),
+        }
+    }
+}
+
+impl DateFormat {
+    pub fn format(&self, time: i64, offset: i32) -> String {
+        match self {
+            DateFormat::Default => date::format_default(time, offset),
+            DateFormat::Iso => date::format_iso(time, offset),
+            DateFormat::Relative => date::format_relative(time, date::now()),
+            DateFormat::Unix => time.to_string(),
+            DateFormat::Rfc => date::format_rfc2822(time, offset),
+            DateFormat::Short => date::format_short(time, offset),
+        }
+    }
+}
+
+/// 内置的提交格式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PrettyFormat {
+    /// 短对象名、引用和标题在一行
+    Oneline,
+    /// 作者和标题
+    Short,
+    /// 作者、日期和完整的提交信息
+    #[default]
+    Medium,
+    /// 作者、提交者和完整的提交信息
+    Full,
+    /// 同 full，再加上两者的日期
+    Fuller,
+    /// 提交对象的原始内容
+    Raw,
+}
+
+/// 一个提交输出成什么样
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    Builtin(PrettyFormat),
+    /// `--format` 的模板，`terminate` 为 true 时每个提交后面都换行，否则换行只用来分隔提交
+    Template {
+        template: String,
+        terminate: bool,
+    },
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format::Builtin(PrettyFormat::default())
+    }
+}
+
+/// `--pretty` 的值：内置格式的名字、`format:<模板>`、`tformat:<模板>`，或者直接是含有 `%` 的模板
+pub fn parse_format(value: &str) -> Result<Format, String> {
+    let builtin = match value {
+        "oneline" => PrettyFormat::Oneline,
+        "short" => PrettyFormat::Short,
+        "medium" => PrettyFormat::Medium,
+        "full" => PrettyFormat::Full,
+        "fuller" => PrettyFormat::Fuller,
+        "raw" => PrettyFormat::Raw,
+        _ => {
+            let (template, terminate) = if let Some(template) = value.strip_prefix("format:") {
+                (template, false)
+            } else if let Some(template) = value.strip_prefix("tformat:") {
+                (template, true)
+            } else if value.contains('%') {
+                (value, true)
+            } else {
+                return Err(format!("invalid --pretty format: {value}"));
+            };
+            return Ok(Format::Template {
+                template: template.to_string(),
+                terminate,
+            });
+        }
+    };
+
+    Ok(Format::Builtin(builtin))
+}
+
+/// 输出提交的选项
+#[derive(Clone, Debug, Default)]
+pub struct PrettyOptions {
+    pub format: Format,
+    pub date: DateFormat,
+    pub color: bool,
+}
+
+impl PrettyOptions {
+    /// 两个提交之间的分隔：多行的内置格式之间空一行，`format:` 模板之间换行
+    pub fn separator(&self) -> &'static str {
+        match &self.format {
+            Format::Builtin(PrettyFormat::Oneline) => "",
+            Format::Builtin(_) => "\n",
+            Format::Template { terminate, .. } => match terminate {
+                true => "",
+                false => "\n",
+            },
+        }
+    }
+
+    /// 提交后面接着 diff 时，中间是否要空一行
+    pub fn blank_before_diff(&self) -> bool {
+        !matches!(
+            self.format,
+            Format::Builtin(PrettyFormat::Oneline)
+                | Format::Template {
+                    terminate: true,
+                    ..
+                }
+        )
+    }
+}
+
+/// 提交信息的第一段是标题，多行时用空格连起来，剩下的是正文
+pub fn split_message(message: &str) -> (String, String) {
+    let message = message.trim();
+    let (subject, body) = message.split_once("\n\n").unwrap_or((message, ""));
+    let subject = subject.lines().map(str::trim).collect::<Vec<_>>().join(" ");
+    (subject, body.trim().to_string())
+}
+
+/// 和 git 的装饰颜色一致：HEAD 青色，分支绿色，标签黄色，远程分支红色
+pub fn ref_color(name: &str) -> &'static str {
+    if name.starts_with("refs/tags/") {
+        color::BOLD_YELLOW
+    } else if name.starts_with("refs/remotes/") {
+        color::BOLD_RED
+    } else if name.starts_with("refs/heads/") {
+        color::BOLD_GREEN
+    } else {
+        color::BOLD_CYAN
+    }
+}
+
+/// 提交上的引用，写成 `HEAD -> master`、`tag: v1`、`origin/master` 的形式
+pub fn decorations(refs: &[String], head_branch: Option<&str>, colored: bool) -> Vec<String> {
+    let mut refs = refs
+        .iter()
+        .filter(|name| *name == "HEAD" || name.starts_with("refs/"))
+        .collect::<Vec<_>>();
+    refs.sort_by_key(|name| (*name != "HEAD", name.as_str()));
+
+    //HEAD 指向的分支和 HEAD 合在一起显示
+    let head_branch = head_branch
+        .map(|branch| format!("refs/heads/{branch}"))
+        .filter(|branch| refs.iter().any(|name| *name == "HEAD") && refs.contains(&branch));
+    let mut names = vec![];
+    for name in refs {
+        let paint = |text: &str| color::paint(colored, ref_color(name), text);
+        if let Some(branch) = &head_branch {
+            if name == "HEAD" {
+                let branch =
+                    color::paint(colored, color::BOLD_GREEN, &branch["refs/heads/".len()..]);
+                names.push(format!("{} -> {branch}", paint("HEAD")));
+                continue;
+            } else if name == branch {
+                continue;
+            }
+        }
+
+        let short = if let Some(tag) = name.strip_prefix("refs/tags/") {
+            format!("tag: {tag}")
+        } else {
+            name.strip_prefix("refs/heads/")
+                .or_else(|| name.strip_prefix("refs/remotes/"))
+                .unwrap_or(name)
+                .to_string()
+        };
+        names.push(paint(&short));
+    }
+
+    names
+}
+
+/// ` (HEAD -> master, tag: v1)`，没有引用时为空
+fn format_decorations(decorations: &[String], colored: bool, with_space: bool) -> String {
+    if decorations.is_empty() {
+        return String::new();
+    }
+
+    let paint = |text: &str| color::paint(colored, color::YELLOW, text);
+    let joined = decorations.join(&paint(", "));
+    match with_space {
+        true => format!(" {}{joined}{}", paint("("), paint(")")),
+        false => joined,
+    }
+}
+
+/// 按格式输出一个提交，`decorations` 来自 [`decorations`]
+pub fn format_commit(
+    oid: &str,
+    commit: &Commit,
+    decorations: &[String],
+    options: &PrettyOptions,
+) -> String {
+    let format = match &options.format {
+        Format::Builtin(format) => *format,
+        Format::Template {
+            template,
+            terminate,
+        } => {
+            let mut entry = expand_template(template, oid, commit, decorations, options);
+            if *terminate {
+                entry.push('\n');
+            }
+            return entry;
+        }
+    };
+
+    let message = commit.message.as_deref().unwrap_or_default();
+    let (subject, _) = split_message(message);
+    let deco = format_decorations(decorations, options.color, true);
+    if format == PrettyFormat::Oneline {
+        let oid = color::paint(options.color, color::YELLOW, short_oid(oid));
+        return format!("{oid}{deco} {subject}\n");
+    }
+
+    let mut entry = format!(
+        "{}{deco}\n",
+        color::paint(options.color, color::YELLOW, &format!("commit {oid}"))
+    );
+    if format == PrettyFormat::Raw {
+        if let Some(tree) = &commit.tree {
+            entry.push_str(&format!("tree {tree}\n"));
+        }
+        for parent in &commit.parents {
+            entry.push_str(&format!("parent {parent}\n"));
+        }
+        if let Some(author) = &commit.author {
+            entry.push_str(&format!("author {author}\n"));
+        }
+        if let Some(committer) = &commit.committer {
+            entry.push_str(&format!("committer {committer}\n"));
+        }
+    } else {
+        if commit.parents.len() > 1 {
+            let parents = commit
+                .parents
+                .iter()
+                .map(|parent| short_oid(parent))
+                .collect::<Vec<_>>();
+            entry.push_str(&format!("Merge: {}\n", parents.join(" ")));
+        }
+
+        let person = |signature: &Option<Signature>| {
+            signature
+                .as_ref()
+                .map(|signature| format!("{} <{}>", signature.name, signature.email))
+        };
+        let when = |signature: &Option<Signature>| {
+            signature
+                .as_ref()
+                .map(|signature| options.date.format(signature.time, signature.offset))
+        };
+        let mut header = |label: &str, value: Option<String>| {
+            if let Some(value) = value {
+                entry.push_str(&format!("{label}{value}\n"));
+            }
+        };
+        match format {
+            PrettyFormat::Short => header("Author: ", person(&commit.author)),
+            PrettyFormat::Full => {
+                header("Author: ", person(&commit.author));
+                header("Commit: ", person(&commit.committer));
+            }
+            PrettyFormat::Fuller => {
+                header("Author:     ", person(&commit.author));
+                header("AuthorDate: ", when(&commit.author));
+                header("Commit:     ", person(&commit.committer));
+                header("CommitDate: ", when(&commit.committer));
+            }
+            _ => {
+                header("Author: ", person(&commit.author));
+                header("Date:   ", when(&commit.author));
+            }
+        }
+    }
+
+    entry.push('\n');
+    let message = match format {
+        PrettyFormat::Short => subject,
+        _ => message.to_string(),
+    };
+    for line in message.lines() {
+        entry.push_str(&format!("    {line}\n"));
+    }
+
+    entry
+}
+
+/// 展开 `--format` 模板里的占位符，不认识的原样输出
+fn expand_template(
+    template: &str,
+    oid: &str,
+    commit: &Commit,
+    decorations: &[String],
+    options: &PrettyOptions,
+) -> String {
+    let message = commit.message.as_deref().unwrap_or_default();
+    let (subject, body) = split_message(message);
+    let mut output = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('%') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        let (expanded, used) = expand_placeholder(rest, oid, commit, decorations, options, || {
+            (subject.clone(), body.clone())
+        });
+        match expanded {
+            Some(expanded) => output.push_str(&expanded),
+            None => output.push('%'),
+        }
+        rest = &rest[used..];
+    }
+    output.push_str(rest);
+
+    output
+}
+
+/// 展开 `%` 后面的一个占位符，返回展开的内容和用掉的字节数
+fn expand_placeholder(
+    spec: &str,
+    oid: &str,
+    commit: &Commit,
+    decorations: &[String],
+    options: &PrettyOptions,
+    message: impl Fn() -> (String, String),
+) -> (Option<String>, usize) {
+    let mut chars = spec.chars();
+    let first = match chars.next() {
+        Some(first) => first,
+        None => return (None, 0),
+    };
+
+    let simple = match first {
+        '%' => Some(String::from("%")),
+        'n' => Some(String::from("\n")),
+        'H' => Some(oid.to_string()),
+        'h' => Some(short_oid(oid).to_string()),
+        'T' => Some(commit.tree.clone().unwrap_or_default()),
+        't' => Some(short_oid(commit.tree.as_deref().unwrap_or_default()).to_string()),
+        'P' => Some(commit.parents.join(" ")),
+        'p' => Some(
+            commit
+                .parents
+                .iter()
+                .map(|parent| short_oid(parent))
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        's' => Some(message().0),
+        'b' => {
+            let (_, body) = message();
+            Some(match body.is_empty() {
+                true => body,
+                false => format!("{body}\n"),
+            })
+        }
+        'B' => Some(format!(
+            "{}\n",
+            commit.message.as_deref().unwrap_or_default()
+        )),
+        'd' => Some(format_decorations(decorations, options.color, true)),
+        'D' => Some(format_decorations(decorations, options.color, false)),
+        _ => None,
+    };
+    if simple.is_some() {
+        return (simple, first.len_utf8());
+    }
+
+    match first {
+        'a' | 'c' => {
+            let signature = match first {
+                'a' => commit.author.as_ref(),
+                _ => commit.committer.as_ref(),
+            };
+            let field = match chars.next() {
+                Some(field) => field,
+                None => return (None, 0),
+            };
+            let value = signature.and_then(|signature| {
+                let (time, offset) = (signature.time, signature.offset);
+                match field {
+                    'n' => Some(signature.name.clone()),
+                    'e' => Some(signature.email.clone()),
+                    'd' => Some(options.date.format(time, offset)),
+                    'r' => Some(DateFormat::Relative.format(time, offset)),
+                    't' => Some(DateFormat::Unix.format(time, offset)),
+                    'i' => Some(DateFormat::Iso.format(time, offset)),
+                    'D' => Some(DateFormat::Rfc.format(time, offset)),
+                    's' => Some(DateFormat::Short.format(time, offset)),
+                    _ => None,
+                }
+            });
+            match (value, field) {
+                (Some(value), _) => (Some(value), 2),
+                (None, 'n' | 'e' | 'd' | 'r' | 't' | 'i' | 'D' | 's') => (Some(String::new()), 2),
+                (None, _) => (None, 0),
+            }
+        }
+        'C' => {
+            let names = [
+                ("red", color::RED),
+                ("green", color::GREEN),
+                ("yellow", color::YELLOW),
+                ("blue", color::BLUE),
+                ("cyan", color::CYAN),
+                ("reset", color::RESET),
+            ];
+            for (name, code) in names {
+                if spec[1..].starts_with(name) {
+                    let code = if options.color { code } else { "" };
+                    return (Some(code.to_string()), 1 + name.len());
+                }
+            }
+            (None, 0)
+        }
+        _ => (None, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OID: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    /// 作者和提交者不同、带正文的合并提交
+    fn commit() -> Commit {
+        let signature = |name: &str, email: &str, time| Signature {
+            name: name.to_string(),
+            email: email.to_string(),
+            time,
+            offset: 480,
+        };
+        Commit {
+            tree: Some(String::from("fedcba9876543210fedcba9876543210fedcba98")),
+            parents: vec![
+                String::from("1111111111111111111111111111111111111111"),
+                String::from("2222222222222222222222222222222222222222"),
+            ],
+            author: Some(signature("A U Thor", "author@example.com", 1_700_000_000)),
+            committer: Some(signature(
+                "C O Mitter",
+                "committer@example.com",
+                1_700_003_600,
+            )),
+            message: Some(String::from(
+                "Subject line\ncontinued\n\nBody one.\n\nBody two.\n",
+            )),
+        }
+    }
+
+    fn expand(template: &str) -> String {
+        let options = PrettyOptions::default();
+        expand_template(template, OID, &commit(), &[], &options)
+    }
+
+    #[test]
+    fn parse_formats() {
+        assert_eq!(
+            parse_format("oneline"),
+            Ok(Format::Builtin(PrettyFormat::Oneline))
+        );
+        assert_eq!(
+            parse_format("format:%h"),
+            Ok(Format::Template {
+                template: String::from("%h"),
+                terminate: false
+            })
+        );
+        assert_eq!(
+            parse_format("tformat:%h"),
+            Ok(Format::Template {
+                template: String::from("%h"),
+                terminate: true
+            })
+        );
+        //含有 % 的值当成 tformat
+        assert_eq!(
+            parse_format("%h %s"),
+            Ok(Format::Template {
+                template: String::from("%h %s"),
+                terminate: true
+            })
+        );
+        assert!(parse_format("nope").is_err());
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(expand("%H"), OID);
+        assert_eq!(expand("%h %t"), "0123456 fedcba9");
+        assert_eq!(expand("%p"), "1111111 2222222");
+        assert_eq!(
+            expand("%P"),
+            format!("{} {}", "1".repeat(40), "2".repeat(40))
+        );
+        assert_eq!(expand("%s"), "Subject line continued");
+        assert_eq!(expand("%b"), "Body one.\n\nBody two.\n");
+        assert_eq!(
+            expand("%an <%ae>%n%cn <%ce>"),
+            "A U Thor <author@example.com>\nC O Mitter <committer@example.com>"
+        );
+        assert_eq!(expand("100%% %s"), "100% Subject line continued");
+        //不认识的占位符原样输出
+        assert_eq!(expand("%x %a %Cpurple"), "%x %a %Cpurple");
+        //关掉颜色时颜色占位符展开成空
+        assert_eq!(expand("%Cred%h%Creset"), "0123456");
+    }
+
+    #[test]
+    fn date_placeholders() {
+        assert_eq!(expand("%at %ct"), "1700000000 1700003600");
+        assert_eq!(expand("%ad"), "Wed Nov 15 06:13:20 2023 +0800");
+        assert_eq!(expand("%ai"), "2023-11-15 06:13:20 +0800");
+        assert_eq!(expand("%aD"), "Wed, 15 Nov 2023 06:13:20 +0800");
+        assert_eq!(expand("%as %cs"), "2023-11-15 2023-11-15");
+        assert_eq!(expand("%ci"), "2023-11-15 07:13:20 +0800");
+
+        //%ad 跟随 --date
+        let options = PrettyOptions {
+            date: DateFormat::Short,
+            ..PrettyOptions::default()
+        };
+        assert_eq!(
+            expand_template("%ad", OID, &commit(), &[], &options),
+            "2023-11-15"
+        );
+    }
+
+    #[test]
+    fn templates_and_builtins() {
+        let decorations = decorations(
+            &[
+                String::from("HEAD"),
+                String::from("refs/heads/master"),
+                String::from("refs/tags/v1"),
+            ],
+            Some("master"),
+            false,
+        );
+        assert_eq!(decorations, ["HEAD -> master", "tag: v1"]);
+
+        let template = |template: &str, terminate| PrettyOptions {
+            format: Format::Template {
+                template: template.to_string(),
+                terminate,
+            },
+            ..PrettyOptions::default()
+        };
+        assert_eq!(
+            format_commit(OID, &commit(), &decorations, &template("%h%d", false)),
+            "0123456 (HEAD -> master, tag: v1)"
+        );
+        assert_eq!(
+            format_commit(OID, &commit(), &decorations, &template("%D", true)),
+            "HEAD -> master, tag: v1\n"
+        );
+
+        let builtin = |format| PrettyOptions {
+            format: Format::Builtin(format),
+            ..PrettyOptions::default()
+        };
+        assert_eq!(
+            format_commit(OID, &commit(), &[], &builtin(PrettyFormat::Oneline)),
+            "0123456 Subject line continued\n"
+        );
+        assert_eq!(
+            format_commit(OID, &commit(), &[], &builtin(PrettyFormat::Medium)),
+            "commit 0123456789abcdef0123456789abcdef01234567\n\
+             Merge: 1111111 2222222\n\
+             Author: A U Thor <author@example.com>\n\
+             Date:   Wed Nov 15 06:13:20 2023 +0800\n\
+             \n    Subject line\n    continued\n    \n    Body one.\n    \n    Body two.\n"
+        );
+    }
+}
@@ -0,0 +1,568 @@
+/// 小型的正则表达式，用于 `--word-diff-regex`、`--grep` 等，编译成指令后用 Pike VM 匹配，耗时和输入长度成线性
+#[derive(Clone, Debug)]
+pub struct Regex {
+    program: Vec<Inst>,
+    ignore_case: bool,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Char(char),
+    Any,
+    Class {
+        items: Vec<ClassItem>,
+        negated: bool,
+    },
+    Start,
+    End,
+    Group(Vec<Vec<Node>>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+    },
+}
+
+#[derive(Clone, Debug)]
+enum ClassItem {
+    Range(char, char),
+    Named(fn(char) -> bool, bool),
+}
+
+/// 编译后的指令，`Split` 优先走第一个分支
+#[derive(Clone, Debug)]
+enum Inst {
+    /// 匹配一个字符，节点是 `Char`、`Any` 或 `Class`
+    Char(Node),
+    Split(usize, usize),
+    Jmp(usize),
+    Start,
+    End,
+    Match,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let chars = pattern.chars().collect::<Vec<_>>();
+        let mut parser = Parser { chars, pos: 0 };
+        let alternatives = parser.alternatives()?;
+        if parser.pos < parser.chars.len() {
+            return Err(format!("unmatched ')' in {pattern:?}"));
+        }
+
+        let mut program = vec![];
+        compile_alternatives(&alternatives, &mut program);
+        program.push(Inst::Match);
+        Ok(Regex {
+            program,
+            ignore_case: false,
+        })
+    }
+
+    /// 忽略大小写
+    pub fn ignore_case(mut self, ignore_case: bool) -> Regex {
+        self.ignore_case = ignore_case;
+        self
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find_at(text, 0).is_some()
+    }
+
+    /// 从 `start` 开始找第一个匹配，返回字节范围，结果和回溯实现的最左、贪婪优先一致
+    pub fn find_at(&self, text: &str, start: usize) -> Option<(usize, usize)> {
+        let offsets = text
+            .char_indices()
+            .map(|(offset, _)| offset)
+            .chain([text.len()])
+            .collect::<Vec<_>>();
+        let chars = text.chars().collect::<Vec<_>>();
+        let first = offsets.iter().position(|offset| *offset >= start)?;
+
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut matched = None;
+        for pos in first..=chars.len() {
+            //还没有匹配时，从这里开始的线程优先级最低
+            if matched.is_none() {
+                self.add_thread(&mut current, 0, pos, pos, chars.len());
+            }
+            if current.list.is_empty() && matched.is_some() {
+                break;
+            }
+
+            next.clear();
+            for &(pc, begin) in &current.list {
+                match &self.program[pc] {
+                    Inst::Match => {
+                        matched = Some((begin, pos));
+                        break;
+                    }
+                    Inst::Char(node)
+                        if chars.get(pos).is_some_and(|c| self.matches_char(node, *c)) =>
+                    {
+                        self.add_thread(&mut next, pc + 1, pos + 1, begin, chars.len());
+                    }
+                    _ => {}
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+        }
+
+        matched.map(|(begin, end)| (offsets[begin], offsets[end]))
+    }
+
+    /// 沿着不消耗字符的指令展开，把停在 `Char` 和 `Match` 上的线程按优先级加入
+    fn add_thread(&self, threads: &mut Threads, pc: usize, pos: usize, begin: usize, len: usize) {
+        let mut stack = vec![pc];
+        while let Some(pc) = stack.pop() {
+            if !threads.mark(pc) {
+                continue;
+            }
+
+            match self.program[pc] {
+                Inst::Jmp(target) => stack.push(target),
+                Inst::Split(first, second) => {
+                    stack.push(second);
+                    stack.push(first);
+                }
+                Inst::Start if pos == 0 => stack.push(pc + 1),
+                Inst::End if pos == len => stack.push(pc + 1),
+                Inst::Start | Inst::End => {}
+                Inst::Char(_) | Inst::Match => threads.list.push((pc, begin)),
+            }
+        }
+    }
+
+    fn matches_char(&self, node: &Node, c: char) -> bool {
+        match node {
+            Node::Char(expected) => {
+                *expected == c || (self.ignore_case && expected.to_lowercase().eq(c.to_lowercase()))
+            }
+            Node::Any => c != '\n',
+            Node::Class { items, negated } => {
+                let matched = items.iter().any(|item| class_item(item, c))
+                    || (self.ignore_case
+                        && c.to_lowercase()
+                            .chain(c.to_uppercase())
+                            .any(|c| items.iter().any(|item| class_item(item, c))));
+                matched != *negated
+            }
+            _ => false,
+        }
+    }
+
+    /// 所有不重叠的匹配
+    pub fn find_iter(&self, text: &str) -> Vec<(usize, usize)> {
+        let mut matches = vec![];
+        let mut start = 0;
+        while let Some((begin, end)) = self.find_at(text, start) {
+            matches.push((begin, end));
+            start = match text[end..].chars().next() {
+                _ if end > begin => end,
+                Some(c) => end + c.len_utf8(),
+                None => break,
+            };
+        }
+
+        matches
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    fn alternatives(&mut self) -> Result<Vec<Vec<Node>>, String> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.sequence()?);
+        }
+
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Node>, String> {
+        let mut nodes = vec![];
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+
+            let atom = self.atom()?;
+            nodes.push(self.quantifier(atom)?);
+        }
+
+        Ok(nodes)
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some('(') => {
+                if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                }
+                let alternatives = self.alternatives()?;
+                match self.next() {
+                    Some(')') => Ok(Node::Group(alternatives)),
+                    _ => Err(String::from("missing ')'")),
+                }
+            }
+            Some('[') => self.class(),
+            Some('.') => Ok(Node::Any),
+            Some('^') => Ok(Node::Start),
+            Some('$') => Ok(Node::End),
+            Some('\\') => match self.next() {
+                Some(c) => Ok(escape(c)
+                    .map(|item| Node::Class {
+                        items: vec![item],
+                        negated: false,
+                    })
+                    .unwrap_or(Node::Char(unescape(c)))),
+                None => Err(String::from("trailing '\\'")),
+            },
+            Some(c @ ('*' | '+' | '?')) => Err(format!("nothing to repeat before '{c}'")),
+            Some(c) => Ok(Node::Char(c)),
+            None => Err(String::from("unexpected end of pattern")),
+        }
+    }
+
+    fn quantifier(&mut self, node: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('{') => match self.bounds() {
+                Some(bounds) => bounds,
+                None => return Ok(node),
+            },
+            Some(c @ ('*' | '+' | '?')) => {
+                self.pos += 1;
+                match c {
+                    '*' => (0, None),
+                    '+' => (1, None),
+                    _ => (0, Some(1)),
+                }
+            }
+            _ => return Ok(node),
+        };
+
+        let greedy = self.peek() != Some('?');
+        if !greedy {
+            self.pos += 1;
+        }
+
+        Ok(Node::Repeat {
+            node: Box::new(node),
+            min,
+            max,
+            greedy,
+        })
+    }
+
+    /// 解析 `{n}`、`{n,}`、`{n,m}`，不是合法的区间时当作普通字符
+    fn bounds(&mut self) -> Option<(usize, Option<usize>)> {
+        let rest = self.chars[self.pos..].iter().collect::<String>();
+        let close = rest.find('}')?;
+        let inner = &rest[1..close];
+        let (min, max) = match inner.split_once(',') {
+            Some((min, "")) => (min.parse().ok()?, None),
+            Some((min, max)) => (min.parse().ok()?, Some(max.parse().ok()?)),
+            None => {
+                let n = inner.parse().ok()?;
+                (n, Some(n))
+            }
+        };
+
+        self.pos += inner.chars().count() + 2;
+        Some((min, max))
+    }
+
+    fn class(&mut self) -> Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+
+        let mut items = vec![];
+        let mut first = true;
+        loop {
+            let c = match self.next() {
+                Some(']') if !first => break,
+                Some(c) => c,
+                None => return Err(String::from("missing ']'")),
+            };
+            first = false;
+
+            if c == '[' && self.peek() == Some(':') {
+                let rest = self.chars[self.pos..].iter().collect::<String>();
+                if let Some(end) = rest.find(":]") {
+                    let name = &rest[1..end];
+                    items.push(posix_class(name).ok_or(format!("invalid class [:{name}:]"))?);
+                    self.pos += rest[..end + 2].chars().count();
+                    continue;
+                }
+            }
+
+            let start = match c {
+                '\\' => match self.next() {
+                    Some(e) => match escape(e) {
+                        Some(item) => {
+                            items.push(item);
+                            continue;
+                        }
+                        None => unescape(e),
+                    },
+                    None => return Err(String::from("missing ']'")),
+                },
+                c => c,
+            };
+
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|c| *c != ']') {
+                self.pos += 1;
+                let end = match self.next() {
+                    Some('\\') => self.next().map(unescape).unwrap_or('\\'),
+                    Some(end) => end,
+                    None => return Err(String::from("missing ']'")),
+                };
+                items.push(ClassItem::Range(start, end));
+            } else {
+                items.push(ClassItem::Range(start, start));
+            }
+        }
+
+        Ok(Node::Class { items, negated })
+    }
+}
+
+fn escape(c: char) -> Option<ClassItem> {
+    let item = match c {
+        'd' => ClassItem::Named(|c| c.is_ascii_digit(), false),
+        'D' => ClassItem::Named(|c| c.is_ascii_digit(), true),
+        'w' => ClassItem::Named(is_word, false),
+        'W' => ClassItem::Named(is_word, true),
+        's' => ClassItem::Named(char::is_whitespace, false),
+        'S' => ClassItem::Named(char::is_whitespace, true),
+        _ => return None,
+    };
+    Some(item)
+}
+
+fn unescape(c: char) -> char {
+    match c {
+        't' => '\t',
+        'n' => '\n',
+        'r' => '\r',
+        c => c,
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn posix_class(name: &str) -> Option<ClassItem> {
+    let f: fn(char) -> bool = match name {
+        "alpha" => |c| c.is_alphabetic(),
+        "digit" => |c| c.is_ascii_digit(),
+        "alnum" => |c| c.is_alphanumeric(),
+        "space" => |c| c.is_whitespace(),
+        "upper" => |c| c.is_uppercase(),
+        "lower" => |c| c.is_lowercase(),
+        "punct" => |c| c.is_ascii_punctuation(),
+        "xdigit" => |c| c.is_ascii_hexdigit(),
+        "blank" => |c| c == ' ' || c == '\t',
+        "cntrl" => |c| c.is_control(),
+        _ => return None,
+    };
+    Some(ClassItem::Named(f, false))
+}
+
+fn class_item(item: &ClassItem, c: char) -> bool {
+    match item {
+        ClassItem::Range(start, end) => *start <= c && c <= *end,
+        ClassItem::Named(f, negated) => f(c) != *negated,
+    }
+}
+
+/// 同一个位置上的线程：指令位置和匹配开始的位置，每条指令最多经过一次
+struct Threads {
+    list: Vec<(usize, usize)>,
+    /// 指令最后一次经过时的代数，清空时代数加一
+    marks: Vec<usize>,
+    generation: usize,
+}
+
+impl Threads {
+    fn new(len: usize) -> Threads {
+        Threads {
+            list: vec![],
+            marks: vec![0; len],
+            generation: 1,
+        }
+    }
+
+    /// 第一次经过这条指令时返回 true
+    fn mark(&mut self, pc: usize) -> bool {
+        let first = self.marks[pc] != self.generation;
+        self.marks[pc] = self.generation;
+        first
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        self.generation += 1;
+    }
+}
+
+fn compile_alternatives(alternatives: &[Vec<Node>], program: &mut Vec<Inst>) {
+    //除了最后一个分支，每个分支前面一个 Split，后面跳到结尾
+    let mut jumps = vec![];
+    for (index, sequence) in alternatives.iter().enumerate() {
+        if index + 1 == alternatives.len() {
+            compile_sequence(sequence, program);
+            break;
+        }
+
+        let split = program.len();
+        program.push(Inst::Split(split + 1, 0));
+        compile_sequence(sequence, program);
+        jumps.push(program.len());
+        program.push(Inst::Jmp(0));
+        program[split] = Inst::Split(split + 1, program.len());
+    }
+
+    let end = program.len();
+    for jump in jumps {
+        program[jump] = Inst::Jmp(end);
+    }
+}
+
+fn compile_sequence(nodes: &[Node], program: &mut Vec<Inst>) {
+    for node in nodes {
+        compile_node(node, program);
+    }
+}
+
+fn compile_node(node: &Node, program: &mut Vec<Inst>) {
+    match node {
+        Node::Start => program.push(Inst::Start),
+        Node::End => program.push(Inst::End),
+        Node::Group(alternatives) => compile_alternatives(alternatives, program),
+        Node::Repeat {
+            node,
+            min,
+            max,
+            greedy,
+        } => {
+            for _ in 0..*min {
+                compile_node(node, program);
+            }
+
+            let split = |first: usize, second: usize| match greedy {
+                true => Inst::Split(first, second),
+                false => Inst::Split(second, first),
+            };
+            match max {
+                None => {
+                    let start = program.len();
+                    program.push(Inst::Jmp(0));
+                    compile_node(node, program);
+                    program.push(Inst::Jmp(start));
+                    program[start] = split(start + 1, program.len());
+                }
+                Some(max) => {
+                    //可选的部分嵌套展开，任意一次不匹配都直接跳到结尾
+                    let mut splits = vec![];
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Inst::Jmp(0));
+                        compile_node(node, program);
+                    }
+                    let end = program.len();
+                    for start in splits {
+                        program[start] = split(start + 1, end);
+                    }
+                }
+            }
+        }
+        node => program.push(Inst::Char(node.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, text: &str) -> Option<(usize, usize)> {
+        Regex::new(pattern).unwrap().find_at(text, 0)
+    }
+
+    #[test]
+    fn leftmost_greedy_and_lazy() {
+        assert_eq!(find("b+", "abbbc"), Some((1, 4)));
+        assert_eq!(find("b+?", "abbbc"), Some((1, 2)));
+        assert_eq!(find("a|ab", "ab"), Some((0, 1)));
+        assert_eq!(find("(a|ab)c", "abc"), Some((0, 3)));
+        assert_eq!(find("x{2,3}", "xxxxx"), Some((0, 3)));
+        assert_eq!(find("x*", "yx"), Some((0, 0)));
+    }
+
+    #[test]
+    fn anchors_and_classes() {
+        assert_eq!(find("^b", "ab"), None);
+        assert_eq!(find("b$", "abb"), Some((2, 3)));
+        assert_eq!(find("[[:digit:]]+", "ab12c"), Some((2, 4)));
+        assert_eq!(find("[^a-c]", "abcd"), Some((3, 4)));
+        assert_eq!(find("\\w+", "  héllo "), Some((2, 8)));
+        assert!(Regex::new("HELLO")
+            .unwrap()
+            .ignore_case(true)
+            .is_match("say hello"));
+    }
+
+    #[test]
+    fn empty_input() {
+        assert_eq!(find("", ""), Some((0, 0)));
+        assert_eq!(find("a*", ""), Some((0, 0)));
+        assert_eq!(find("a", ""), None);
+        assert_eq!(
+            Regex::new("a*").unwrap().find_iter("baa"),
+            vec![(0, 0), (1, 3), (3, 3)]
+        );
+    }
+
+    #[test]
+    fn long_input() {
+        let line = "x".repeat(200_000);
+        assert_eq!(find(".+", &line), Some((0, line.len())));
+        assert_eq!(find("(x|y)*z", &line), None);
+    }
+
+    #[test]
+    fn nested_quantifiers() {
+        let text = "a".repeat(5_000);
+        assert_eq!(find("(a*)*b", &text), None);
+        assert_eq!(find("(a+)+$", &text), Some((0, text.len())));
+        assert_eq!(find("(a*)*b", "aab"), Some((0, 3)));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        assert!(Regex::new("(a").is_err());
+        assert!(Regex::new("a)").is_err());
+        assert!(Regex::new("*a").is_err());
+        assert!(Regex::new("[a").is_err());
+    }
+}
@@ -1,6 +1,10 @@
 use std::{collections::HashMap, path};
 
-use crate::data::{RefValue, Ugit};
+use crate::{
+    data::{RefValue, Ugit},
+    revision::{RevFilter, RevList},
+    revwalk::{RevWalk, WalkOptions},
+};
 
 const REMOTE_REF_BASE: &str = "refs/heads";
 const LOCAL_REFS_BASE: &str = "refs/remote";
@@ -26,18 +30,24 @@ impl Ugit {
             }
         }
 
-        let remote_objects = self.iter_objects_in_commits(
-            known_remote_refs
-                .values()
-                .filter(|val| self.objects_exists(val))
-                .cloned()
-                .collect::<Vec<_>>(),
+        //远程已有的提交不用再推，只推它们的树里没有的对象
+        let remote_commits = known_remote_refs
+            .values()
+            .filter(|val| self.objects_exists(val))
+            .cloned()
+            .collect::<Vec<_>>();
+        let walk = RevWalk::new(
+            self,
+            vec![ref_val.clone()],
+            remote_commits.clone(),
+            WalkOptions::default(),
         );
-        let local_objects = self.iter_objects_in_commits(vec![ref_val.clone()]);
-        let objects_to_push = local_objects.difference(&remote_objects);
+        let commits = RevList::new(self, walk, RevFilter::default()).collect::<Vec<_>>();
+        let objects = self.list_objects(&commits, &remote_commits);
+        let objects_to_push = objects.into_iter().map(|object| object.oid).chain(commits);
 
         for oid in objects_to_push {
-            if let Err(err) = self.push_object(oid, remote_path) {
+            if let Err(err) = self.push_object(&oid, remote_path) {
                 eprintln!("push object to {:?} err:{:?}", remote_path, err);
             }
         }
@@ -52,15 +62,35 @@ impl Ugit {
         println!("Will fetch the following refs:");
         let refs = self.get_remote_refs(&remote_path, REMOTE_REF_BASE);
 
-        let oids = refs.iter().map(|refs| refs.1.clone()).collect::<Vec<_>>();
-        self.iter_objects_in_commits_fetch(oids, &|str| {
-            if let Err(err) = self.fetch_object_if_missing(str, &remote_path) {
+        //本地引用指向的提交在远程也有时，它们能到达的对象不用再取
+        let local_tips = match self.parse_revisions(&["--all"]) {
+            Ok(revisions) => revisions.include,
+            Err(err) => {
+                eprintln!("fetch, list local refs err:{:?}", err);
+                return;
+            }
+        };
+        let remote_tips = refs.iter().map(|refs| refs.1.clone()).collect::<Vec<_>>();
+
+        let old_dir =
+            self.change_git_dir(format!("{}{}.rgit", remote_path, path::MAIN_SEPARATOR_STR));
+        let known = local_tips
+            .into_iter()
+            .filter(|oid| self.objects_exists(oid))
+            .collect::<Vec<_>>();
+        let walk = RevWalk::new(self, remote_tips, known.clone(), WalkOptions::default());
+        let commits = RevList::new(self, walk, RevFilter::default()).collect::<Vec<_>>();
+        let objects = self.list_objects(&commits, &known);
+        self.change_git_dir(old_dir);
+
+        for oid in objects.into_iter().map(|object| object.oid).chain(commits) {
+            if let Err(err) = self.fetch_object_if_missing(&oid, &remote_path) {
                 eprintln!(
                     "fetch remote object err, remote_path:{:?}, oid:{:?}, err:{:?}",
-                    remote_path, str, err
+                    remote_path, oid, err
                 );
             }
-        });
+        }
 
         for (ref_name, val) in refs {
             if let Some(ref_name) = ref_name.strip_prefix(REMOTE_REF_BASE) {
@@ -0,0 +1,207 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use crate::{
+    data::{DataType, Ugit},
+    line_diff::{self, Algorithm, Edit},
+};
+
+/// 改名和复制检测的选项
+#[derive(Clone, Copy, Debug)]
+pub struct RenameOptions {
+    /// 相似度达到这个百分比才算改名
+    pub threshold: usize,
+    /// 同时检测复制，来源可以是被修改过的文件
+    pub copies: bool,
+}
+
+impl Default for RenameOptions {
+    fn default() -> Self {
+        Self {
+            threshold: 50,
+            copies: false,
+        }
+    }
+}
+
+/// 解析 `-M`/`-C` 后面的相似度，`90%` 是百分比，`9` 和 `0.9` 一样是小数
+pub fn parse_similarity(value: &str) -> Result<usize, String> {
+    let invalid = || format!("invalid similarity: {value:?}");
+    let score = match value.strip_suffix('%') {
+        Some(percent) => percent.parse::<usize>().map_err(|_| invalid())?,
+        None => {
+            let digits = value.strip_prefix("0.").unwrap_or(value);
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            let digits = &digits[..digits.len().min(2)];
+            digits.parse::<usize>().map_err(|_| invalid())? * 10_usize.pow(2 - digits.len() as u32)
+        }
+    };
+
+    if score > 100 {
+        return Err(invalid());
+    }
+    Ok(score)
+}
+
+/// 一个文件从 `from` 改名或复制到了 `to`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// 相似度百分比
+    pub similarity: usize,
+    pub copy: bool,
+}
+
+impl Ugit {
+    /// 在 `t_from` 到 `t_to` 的修改中找出改名和复制，一个被删除的源文件只算一次改名，其余都是复制
+    pub fn detect_renames(
+        &self,
+        t_from: &HashMap<PathBuf, String>,
+        t_to: &HashMap<PathBuf, String>,
+        options: &RenameOptions,
+    ) -> Vec<Rename> {
+        let mut added = t_to
+            .keys()
+            .filter(|path| !t_from.contains_key(*path))
+            .collect::<Vec<_>>();
+        let mut sources = t_from
+            .iter()
+            .filter(|(path, oid)| match t_to.get(*path) {
+                None => true,
+                Some(to) => options.copies && to != *oid,
+            })
+            .map(|(path, _)| path)
+            .collect::<Vec<_>>();
+        if added.is_empty() || sources.is_empty() {
+            return vec![];
+        }
+        added.sort();
+        sources.sort();
+
+        let mut candidates = vec![];
+        for to in &added {
+            for from in &sources {
+                if t_from[*from] == t_to[*to] {
+                    candidates.push((100, *from, *to));
+                }
+            }
+        }
+
+        let exact = candidates
+            .iter()
+            .map(|(_, _, to)| *to)
+            .collect::<HashSet<_>>();
+        let mut contents = HashMap::new();
+        let mut read = |oid: &String| {
+            contents
+                .entry(oid.clone())
+                .or_insert_with(|| self.get_object(oid, DataType::Blob).unwrap_or_default())
+                .clone()
+        };
+        for to in added.iter().filter(|to| !exact.contains(*to)) {
+            let new = read(&t_to[*to]);
+            for from in &sources {
+                let old = read(&t_from[*from]);
+                let score = similarity(&old, &new, options.threshold);
+                if score >= options.threshold {
+                    candidates.push((score, *from, *to));
+                }
+            }
+        }
+
+        //相似度高的先配对，同分时按路径排序保证结果稳定
+        candidates.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)).then(a.2.cmp(b.2)));
+
+        let mut renamed = HashSet::new();
+        let mut paired = HashSet::new();
+        let mut renames = vec![];
+        for (similarity, from, to) in candidates {
+            if paired.contains(to) {
+                continue;
+            }
+
+            let deleted = !t_to.contains_key(from);
+            let copy = !deleted || renamed.contains(from);
+            if copy && !options.copies {
+                continue;
+            }
+
+            paired.insert(to);
+            if !copy {
+                renamed.insert(from);
+            }
+            renames.push(Rename {
+                from: from.clone(),
+                to: to.clone(),
+                similarity,
+                copy,
+            });
+        }
+
+        renames.sort_by(|a, b| a.to.cmp(&b.to));
+        renames
+    }
+}
+
+/// 两边相同的行的字节数占较大一边的百分比，大小差太多时直接返回 0
+fn similarity(old: &str, new: &str, threshold: usize) -> usize {
+    let max = old.len().max(new.len());
+    if max == 0 {
+        return 100;
+    }
+    if old.len().min(new.len()) * 100 < max * threshold {
+        return 0;
+    }
+
+    let old_lines = line_diff::split_lines(old);
+    let new_lines = line_diff::split_lines(new);
+    let common = line_diff::diff_lines(&old_lines, &new_lines, Algorithm::default())
+        .into_iter()
+        .map(|edit| match edit {
+            Edit::Equal(o, _) => old_lines[o].len(),
+            _ => 0,
+        })
+        .sum::<usize>();
+
+    common * 100 / max
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn similarity_thresholds() {
+        assert_eq!(parse_similarity("90%"), Ok(90));
+        assert_eq!(parse_similarity("100%"), Ok(100));
+        assert_eq!(parse_similarity("0%"), Ok(0));
+        //不带百分号时当成小数
+        assert_eq!(parse_similarity("9"), Ok(90));
+        assert_eq!(parse_similarity("0.9"), Ok(90));
+        assert_eq!(parse_similarity("75"), Ok(75));
+        assert_eq!(parse_similarity("0.755"), Ok(75));
+        assert_eq!(parse_similarity("05"), Ok(5));
+
+        for bad in ["", "%", "101%", "abc", "-5", "0.", "9x"] {
+            assert!(parse_similarity(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn similarity_score() {
+        assert_eq!(similarity("", "", 50), 100);
+        assert_eq!(similarity("a\nb\n", "a\nb\n", 50), 100);
+        assert_eq!(similarity("a\nb\n", "c\nd\n", 50), 0);
+        //改一行，4 字节里有 2 字节相同
+        assert_eq!(similarity("a\nb\n", "a\nc\n", 50), 50);
+        assert_eq!(similarity("a\nb\nc\nd\n", "a\nb\nc\nx\n", 50), 75);
+        //大小差太多时不用比较内容
+        assert_eq!(similarity("a\n", "a\nb\nc\nd\n", 50), 0);
+        assert_eq!(similarity("a\n", "a\nb\nc\nd\n", 0), 25);
+    }
+}
@@ -0,0 +1,374 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use crate::{
+    data::{self, DataType, DateErr, Ugit},
+    regex::Regex,
+    revwalk::RevWalk,
+};
+
+/// 命令行上的修订解析出来的提交：从 `include` 出发，跳过 `exclude` 能到达的提交
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Revisions {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    /// `A...B` 左边的提交，`--left-right` 用它区分两边
+    pub left: Vec<String>,
+}
+
+impl Revisions {
+    fn add(&mut self, oid: String, exclude: bool) {
+        let list = match exclude {
+            true => &mut self.exclude,
+            false => &mut self.include,
+        };
+        if !list.contains(&oid) {
+            list.push(oid);
+        }
+    }
+}
+
+/// 逐个提交判断的过滤条件，以及数量的限制
+#[derive(Clone, Debug, Default)]
+pub struct RevFilter {
+    /// 最多输出多少个提交
+    pub max_count: Option<usize>,
+    /// 先跳过多少个提交
+    pub skip: usize,
+    /// 提交时间不早于这个时间戳
+    pub since: Option<i64>,
+    /// 提交时间不晚于这个时间戳
+    pub until: Option<i64>,
+    /// 匹配 `名字 <邮箱>` 形式的作者
+    pub author: Option<Regex>,
+    /// 匹配提交信息
+    pub grep: Option<Regex>,
+    /// `Some(true)` 只要合并提交，`Some(false)` 不要合并提交
+    pub merges: Option<bool>,
+}
+
+impl Ugit {
+    /// 解析 `A`、`A..B`、`A...B`、`^A` 和 `--not`、`--all`、`--branches`、`--tags`，没有参数时是 HEAD
+    pub fn parse_revisions<T: AsRef<str>>(&self, args: &[T]) -> Result<Revisions, DateErr> {
+        let mut revisions = Revisions::default();
+        if args.is_empty() {
+            if let Some(head) = self.get_ref_if_not_empty(data::HEAD) {
+                revisions.include.push(head.value);
+            }
+            return Ok(revisions);
+        }
+
+        let mut not = false;
+        for arg in args {
+            let arg = arg.as_ref();
+            let prefix = match arg {
+                "--not" => {
+                    not = !not;
+                    continue;
+                }
+                "--all" => Some(""),
+                "--branches" => Some("refs/heads/"),
+                "--tags" => Some("refs/tags/"),
+                _ => None,
+            };
+            if let Some(prefix) = prefix {
+                for oid in self.ref_tips(prefix) {
+                    revisions.add(oid, not);
+                }
+                continue;
+            }
+
+            if let Some((left, right)) = arg.split_once("...") {
+                let (left, right) = (self.resolve_commit(left)?, self.resolve_commit(right)?);
+                for base in self.get_merge_bases(&left, std::slice::from_ref(&right)) {
+                    revisions.add(base, !not);
+                }
+                if !revisions.left.contains(&left) {
+                    revisions.left.push(left.clone());
+                }
+                revisions.add(left, not);
+                revisions.add(right, not);
+            } else if let Some((left, right)) = arg.split_once("..") {
+                revisions.add(self.resolve_commit(left)?, !not);
+                revisions.add(self.resolve_commit(right)?, not);
+            } else if let Some(rev) = arg.strip_prefix('^') {
+                revisions.add(self.resolve_commit(rev)?, !not);
+            } else {
+                revisions.add(self.resolve_commit(arg)?, not);
+            }
+        }
+
+        Ok(revisions)
+    }
+
+    /// 把修订解析成提交，认识后缀 `~<n>` 和 `^<n>`，空的修订表示 HEAD
+    pub fn resolve_commit(&self, rev: &str) -> Result<String, DateErr> {
+        let bad_revision = || DateErr::Err(format!("bad revision '{rev}'"));
+        let end = rev.find(['~', '^']).unwrap_or(rev.len());
+        let (name, mut suffix) = rev.split_at(end);
+        let mut oid = match name {
+            "" => self.get_oid(data::HEAD),
+            name => self.get_oid(name),
+        };
+        if !self.objects_exists(&oid) || self.get_commit(&oid).is_none() {
+            return Err(bad_revision());
+        }
+
+        while let Some(kind) = suffix.chars().next() {
+            suffix = &suffix[1..];
+            let digits = suffix.len()
+                - suffix
+                    .trim_start_matches(|c: char| c.is_ascii_digit())
+                    .len();
+            let count = match &suffix[..digits] {
+                "" => 1,
+                digits => digits.parse::<usize>().map_err(|_| bad_revision())?,
+            };
+            suffix = &suffix[digits..];
+
+            let parents = |oid: &str| self.get_commit(oid).map(|c| c.parents).unwrap_or_default();
+            oid = match kind {
+                //`~n` 沿着第一个父提交走 n 步
+                '~' => (0..count).try_fold(oid, |oid, _| parents(&oid).into_iter().next()),
+                //`^n` 是第 n 个父提交，`^0` 是提交本身
+                _ if count == 0 => Some(oid),
+                _ => parents(&oid).into_iter().nth(count - 1),
+            }
+            .ok_or_else(bad_revision)?;
+        }
+
+        Ok(oid)
+    }
+
+    /// 名字以 `prefix` 开头的引用指向的提交，`prefix` 为空时还包括 HEAD
+    fn ref_tips(&self, prefix: &str) -> Vec<String> {
+        let mut tips = vec![];
+        for ref_name in self.iter_refs_prefix(prefix) {
+            if ref_name == data::MERGE_HEAD {
+                continue;
+            }
+            if let Some(oid) = self.get_ref_if_not_empty(&ref_name).map(|val| val.value) {
+                if self.get_commit(&oid).is_some() && !tips.contains(&oid) {
+                    tips.push(oid);
+                }
+            }
+        }
+
+        tips
+    }
+}
+
+/// 遇到不在仓库里的对象时怎么办
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissingAction {
+    /// 报错退出
+    #[default]
+    Error,
+    /// 跳过
+    AllowAny,
+    /// 输出成 `?<oid>`
+    Print,
+}
+
+impl FromStr for MissingAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(MissingAction::Error),
+            "allow-any" => Ok(MissingAction::AllowAny),
+            "print" => Ok(MissingAction::Print),
+            _ => Err(format!("unknown missing action: {s}")),
+        }
+    }
+}
+
+/// 提交的树里的一个对象
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListedObject {
+    pub oid: String,
+    /// 在提交的树里的路径，根目录的树是空的路径
+    pub path: String,
+    /// 对象不在仓库里
+    pub missing: bool,
+}
+
+impl Ugit {
+    /// 这些提交的树里所有的树和文件，`exclude` 的树里已有的对象跳过，每个对象只列一次
+    pub fn list_objects(&self, commits: &[String], exclude: &[String]) -> Vec<ListedObject> {
+        let mut seen = HashSet::new();
+        let mut ignored = vec![];
+        for oid in exclude {
+            if let Some(tree) = self.get_commit(oid).and_then(|commit| commit.tree) {
+                self.list_tree(&tree, String::new(), &mut seen, &mut ignored);
+            }
+        }
+
+        let mut objects = vec![];
+        for oid in commits {
+            if let Some(tree) = self.get_commit(oid).and_then(|commit| commit.tree) {
+                self.list_tree(&tree, String::new(), &mut seen, &mut objects);
+            }
+        }
+
+        objects
+    }
+
+    fn list_tree(
+        &self,
+        oid: &str,
+        path: String,
+        seen: &mut HashSet<String>,
+        objects: &mut Vec<ListedObject>,
+    ) {
+        if !seen.insert(oid.to_string()) {
+            return;
+        }
+
+        let missing = !self.objects_exists(oid);
+        objects.push(ListedObject {
+            oid: oid.to_string(),
+            path: path.clone(),
+            missing,
+        });
+        if missing {
+            return;
+        }
+
+        for (data_type, oid, name) in self.iter_tree_entires(oid).unwrap_or_default() {
+            let path = match path.as_str() {
+                "" => name,
+                dir => format!("{dir}/{name}"),
+            };
+            match data_type {
+                DataType::Tree => self.list_tree(&oid, path, seen, objects),
+                _ => {
+                    if seen.insert(oid.clone()) {
+                        objects.push(ListedObject {
+                            missing: !self.objects_exists(&oid),
+                            oid,
+                            path,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 在 [`RevWalk`] 上加上过滤条件和数量限制，`log` 和 `push` 都用它找出要处理的提交
+pub struct RevList<'a> {
+    ugit: &'a Ugit,
+    walk: RevWalk<'a>,
+    filter: RevFilter,
+    matched: HashMap<String, bool>,
+    skipped: usize,
+    count: usize,
+}
+
+impl<'a> RevList<'a> {
+    pub fn new(ugit: &'a Ugit, walk: RevWalk<'a>, filter: RevFilter) -> RevList<'a> {
+        RevList {
+            ugit,
+            walk,
+            filter,
+            matched: HashMap::new(),
+            skipped: 0,
+            count: 0,
+        }
+    }
+
+    /// 遍历时遇到的不在仓库里的提交
+    pub fn missing(&self) -> &[String] {
+        self.walk.missing()
+    }
+
+    /// 提交在输出里的父提交：跳过被过滤掉的提交，连到最近的会输出的祖先
+    pub fn parents(&mut self, oid: &str) -> Vec<String> {
+        let parents = self.walk.parents(oid);
+        let mut rewritten = vec![];
+        let mut visited = vec![];
+        let mut stack = parents.into_iter().rev().collect::<Vec<_>>();
+        while let Some(oid) = stack.pop() {
+            if visited.contains(&oid) || self.walk.is_excluded(&oid) {
+                continue;
+            }
+            visited.push(oid.clone());
+
+            if self.matches(&oid) {
+                if !rewritten.contains(&oid) {
+                    rewritten.push(oid);
+                }
+            } else if !self.too_old(&oid) {
+                stack.extend(self.walk.parents(&oid).into_iter().rev());
+            }
+        }
+
+        rewritten
+    }
+
+    /// 提交是否满足过滤条件和路径限制
+    fn matches(&mut self, oid: &str) -> bool {
+        if let Some(matched) = self.matched.get(oid) {
+            return *matched;
+        }
+
+        let matched = self.walk.touches_paths(oid)
+            && self.ugit.get_commit(oid).is_some_and(|commit| {
+                let filter = &self.filter;
+                let time = commit.committer.as_ref().map_or(0, |c| c.time);
+                let author = commit
+                    .author
+                    .as_ref()
+                    .map(|author| format!("{} <{}>", author.name, author.email))
+                    .unwrap_or_default();
+                let message = commit.message.as_deref().unwrap_or_default();
+
+                filter
+                    .merges
+                    .is_none_or(|merges| merges == (commit.parents.len() > 1))
+                    && filter.since.is_none_or(|since| time >= since)
+                    && filter.until.is_none_or(|until| time <= until)
+                    && filter.author.as_ref().is_none_or(|re| re.is_match(&author))
+                    && filter.grep.as_ref().is_none_or(|re| re.is_match(message))
+            });
+        self.matched.insert(oid.to_string(), matched);
+        matched
+    }
+
+    /// 早于 `since` 的提交，它的祖先也不会输出
+    fn too_old(&self, oid: &str) -> bool {
+        self.filter.since.is_some_and(|since| {
+            self.ugit
+                .get_commit(oid)
+                .and_then(|commit| commit.committer)
+                .is_some_and(|committer| committer.time < since)
+        })
+    }
+}
+
+impl Iterator for RevList<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if self.filter.max_count.is_some_and(|max| self.count >= max) {
+            return None;
+        }
+
+        loop {
+            let oid = self.walk.next()?;
+            if !self.matches(&oid) {
+                continue;
+            }
+            if self.skipped < self.filter.skip {
+                self.skipped += 1;
+                continue;
+            }
+
+            self.count += 1;
+            return Some(oid);
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+    path::PathBuf,
+    vec,
+};
+
+use crate::{data::Ugit, pathspec::Pathspec};
+
+/// 提交的输出顺序
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Order {
+    /// 按提交时间从新到旧，边走边输出
+    #[default]
+    Default,
+    /// 子提交都在父提交前面，同一条线上的提交尽量放在一起
+    Topo,
+    /// 子提交都在父提交前面，其余按提交时间
+    Date,
+    /// 子提交都在父提交前面，其余按作者时间
+    AuthorDate,
+}
+
+/// 遍历提交的选项
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WalkOptions {
+    pub order: Order,
+    /// 从最旧的提交开始输出
+    pub reverse: bool,
+    /// 合并提交只沿着第一个父提交往下走
+    pub first_parent: bool,
+    /// 只保留排除的提交的后代，也就是 `A..B` 中从 A 到 B 路径上的提交
+    pub ancestry_path: bool,
+}
+
+impl WalkOptions {
+    /// 是否要先找出全部提交再输出
+    fn limited(&self) -> bool {
+        self.order != Order::Default || self.reverse || self.ancestry_path
+    }
+}
+
+/// 遍历时需要的提交信息
+#[derive(Clone, Debug)]
+struct CommitInfo {
+    parents: Vec<String>,
+    commit_time: i64,
+    author_time: i64,
+    /// 限定路径时，这些路径的内容和某个父提交完全一样
+    treesame: bool,
+}
+
+/// 等待输出的提交，时间新的先出队，时间相同时先入队的先出
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct Pending {
+    time: i64,
+    sequence: Reverse<usize>,
+    oid: String,
+}
+
+/// 从一组提交出发，按时间遍历它们的祖先，跳过排除的提交能到达的所有提交
+pub struct RevWalk<'a> {
+    ugit: &'a Ugit,
+    options: WalkOptions,
+    include: Vec<String>,
+    bottoms: Vec<String>,
+    paths: Pathspec,
+    trees: HashMap<String, HashMap<PathBuf, String>>,
+    infos: HashMap<String, CommitInfo>,
+    excluded: HashSet<String>,
+    missing: Vec<String>,
+    seen: HashSet<String>,
+    queue: BinaryHeap<Pending>,
+    sequence: usize,
+    started: bool,
+    sorted: Option<vec::IntoIter<String>>,
+}
+
+impl<'a> RevWalk<'a> {
+    pub fn new(
+        ugit: &'a Ugit,
+        include: Vec<String>,
+        exclude: Vec<String>,
+        options: WalkOptions,
+    ) -> RevWalk<'a> {
+        RevWalk {
+            ugit,
+            options,
+            include,
+            bottoms: exclude.clone(),
+            paths: Pathspec::default(),
+            trees: HashMap::new(),
+            infos: HashMap::new(),
+            excluded: ugit.iter_commits_and_parents(exclude).into_iter().collect(),
+            missing: vec![],
+            seen: HashSet::new(),
+            queue: BinaryHeap::new(),
+            sequence: 0,
+            started: false,
+            sorted: None,
+        }
+    }
+
+    /// 只关心这些路径，用来简化历史
+    pub fn paths(mut self, paths: Pathspec) -> RevWalk<'a> {
+        self.paths = paths;
+        self
+    }
+
+    /// 第一次取提交时才开始遍历，限定了顺序等的情况下先排好全部提交
+    fn start(&mut self) {
+        self.started = true;
+        for oid in std::mem::take(&mut self.include) {
+            self.enqueue(oid);
+        }
+
+        if self.options.limited() {
+            let mut commits = self.by_ref().collect::<Vec<_>>();
+            if self.options.ancestry_path {
+                let bottoms = self.bottoms.clone();
+                commits = self.ancestry_path(commits, &bottoms);
+            }
+            let mut commits = match self.options.order {
+                Order::Default => commits,
+                Order::Topo => self.sort(commits, None),
+                Order::Date => self.sort(commits, Some(|info: &CommitInfo| info.commit_time)),
+                Order::AuthorDate => self.sort(commits, Some(|info: &CommitInfo| info.author_time)),
+            };
+            if self.options.reverse {
+                commits.reverse();
+            }
+            self.sorted = Some(commits.into_iter());
+        }
+    }
+
+    /// 是否是排除的提交能到达的提交
+    pub fn is_excluded(&self, oid: &str) -> bool {
+        self.excluded.contains(oid)
+    }
+
+    /// 遍历时遇到的不在仓库里的提交
+    pub fn missing(&self) -> &[String] {
+        &self.missing
+    }
+
+    /// 限定路径时，提交是否改动了这些路径，没有限定路径时总是 true
+    pub fn touches_paths(&mut self, oid: &str) -> bool {
+        self.info(oid).is_some_and(|info| !info.treesame)
+    }
+
+    /// 提交在图里的父提交，`first_parent` 时只有第一个，简化历史时只有路径相同的那个
+    pub fn parents(&mut self, oid: &str) -> Vec<String> {
+        self.info(oid).map(|info| info.parents).unwrap_or_default()
+    }
+
+    fn info(&mut self, oid: &str) -> Option<CommitInfo> {
+        if let Some(info) = self.infos.get(oid) {
+            return Some(info.clone());
+        }
+
+        if !self.ugit.objects_exists(oid) {
+            return None;
+        }
+        let commit = self.ugit.get_commit(oid)?;
+        let mut parents = commit.parents;
+        if self.options.first_parent {
+            parents.truncate(1);
+        }
+
+        let mut treesame = false;
+        if !self.paths.is_empty() {
+            let tree = self.tree(oid);
+            match parents
+                .iter()
+                .find(|parent| self.tree(parent) == tree)
+                .cloned()
+            {
+                Some(parent) => {
+                    parents = vec![parent];
+                    treesame = true;
+                }
+                None => treesame = parents.is_empty() && tree.is_empty(),
+            }
+        }
+
+        let info = CommitInfo {
+            parents,
+            commit_time: commit.committer.map_or(0, |committer| committer.time),
+            author_time: commit.author.map_or(0, |author| author.time),
+            treesame,
+        };
+        self.infos.insert(oid.to_string(), info.clone());
+        Some(info)
+    }
+
+    /// 提交的树里属于限定路径的文件
+    fn tree(&mut self, oid: &str) -> HashMap<PathBuf, String> {
+        if let Some(tree) = self.trees.get(oid) {
+            return tree.clone();
+        }
+
+        let tree = self
+            .ugit
+            .get_commit_tree(oid)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(path, _)| self.paths.matches(path))
+            .collect::<HashMap<_, _>>();
+        self.trees.insert(oid.to_string(), tree.clone());
+        tree
+    }
+
+    fn enqueue(&mut self, oid: String) {
+        if self.excluded.contains(&oid) || self.seen.contains(&oid) {
+            return;
+        }
+
+        let time = match self.info(&oid) {
+            Some(info) => info.commit_time,
+            None => {
+                if !self.missing.contains(&oid) {
+                    self.missing.push(oid);
+                }
+                return;
+            }
+        };
+        self.sequence += 1;
+        self.queue.push(Pending {
+            time,
+            sequence: Reverse(self.sequence),
+            oid,
+        });
+    }
+
+    /// 只保留排除的提交的后代
+    fn ancestry_path(&mut self, commits: Vec<String>, bottoms: &[String]) -> Vec<String> {
+        let mut descendants = bottoms.iter().cloned().collect::<HashSet<_>>();
+        loop {
+            let mut changed = false;
+            for oid in &commits {
+                if !descendants.contains(oid)
+                    && self.parents(oid).iter().any(|p| descendants.contains(p))
+                {
+                    descendants.insert(oid.clone());
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        commits
+            .into_iter()
+            .filter(|oid| descendants.contains(oid))
+            .collect()
+    }
+
+    /// 拓扑排序，提交总是在它所有的子提交之后，没有 key 时沿第一个父提交深度优先
+    fn sort(&mut self, commits: Vec<String>, key: Option<fn(&CommitInfo) -> i64>) -> Vec<String> {
+        let included = commits.iter().cloned().collect::<HashSet<_>>();
+        let mut children = HashMap::<String, usize>::new();
+        for oid in &commits {
+            for parent in self.parents(oid) {
+                if included.contains(&parent) {
+                    *children.entry(parent).or_default() += 1;
+                }
+            }
+        }
+
+        //没有 key 时后入队的先出，相当于一个栈
+        let mut ready = BinaryHeap::new();
+        let mut sequence = 0;
+        let mut push = |walk: &mut Self, oid: String, ready: &mut BinaryHeap<Pending>| {
+            sequence += 1;
+            let time = match key {
+                Some(key) => walk.info(&oid).as_ref().map_or(0, key),
+                None => sequence as i64,
+            };
+            ready.push(Pending {
+                time,
+                sequence: Reverse(sequence),
+                oid,
+            });
+        };
+        //起点倒着入队，第一个起点最先出队
+        for oid in commits.iter().rev() {
+            if !children.contains_key(oid) {
+                push(self, oid.clone(), &mut ready);
+            }
+        }
+
+        let mut sorted = vec![];
+        while let Some(Pending { oid, .. }) = ready.pop() {
+            //没有 key 时倒着处理父提交，第一个父提交最先出队
+            let mut parents = self.parents(&oid);
+            if key.is_none() {
+                parents.reverse();
+            }
+            for parent in parents {
+                if let Some(count) = children.get_mut(&parent) {
+                    *count -= 1;
+                    if *count == 0 {
+                        push(self, parent, &mut ready);
+                    }
+                }
+            }
+            sorted.push(oid);
+        }
+
+        sorted
+    }
+}
+
+impl Iterator for RevWalk<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if !self.started {
+            self.start();
+        }
+        if let Some(sorted) = self.sorted.as_mut() {
+            return sorted.next();
+        }
+
+        while let Some(Pending { oid, .. }) = self.queue.pop() {
+            if !self.seen.insert(oid.clone()) {
+                continue;
+            }
+
+            for parent in self.parents(&oid) {
+                self.enqueue(parent);
+            }
+            return Some(oid);
+        }
+
+        None
+    }
+}
@@ -0,0 +1,312 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    data::{DateErr, Ugit},
+    diff::MergedTree,
+    merge::MergeOptions,
+};
+
+/// 要合并进来的提交，`name` 用在冲突标记和提交信息里
+#[derive(Clone, Debug)]
+pub struct MergeHead {
+    pub name: String,
+    pub oid: String,
+}
+
+/// 合并策略，根据 HEAD 和要合并的提交算出合并后的树
+pub trait MergeStrategy {
+    fn merge(
+        &self,
+        ugit: &Ugit,
+        head: &str,
+        others: &[MergeHead],
+        options: &MergeOptions,
+    ) -> Result<MergedTree, DateErr>;
+}
+
+/// 命令行上可选的策略
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    /// 保留当前分支的树，只记录合并历史
+    Ours,
+    /// 三方合并，有多个共同祖先时先合并出虚拟祖先
+    Recursive,
+    /// 同 recursive
+    Ort,
+    /// 依次合并多个分支，生成一个多父提交
+    Octopus,
+    /// 同 recursive，但先把对方的树移到匹配的子目录下
+    Subtree,
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ours" => Ok(Strategy::Ours),
+            "recursive" => Ok(Strategy::Recursive),
+            "ort" => Ok(Strategy::Ort),
+            "octopus" => Ok(Strategy::Octopus),
+            "subtree" => Ok(Strategy::Subtree),
+            _ => Err(format!("unknown merge strategy: {s}")),
+        }
+    }
+}
+
+impl Strategy {
+    pub fn build(self, options: &[StrategyOption]) -> Box<dyn MergeStrategy> {
+        let prefix = options.iter().find_map(|option| match option {
+            StrategyOption::Subtree(prefix) => Some(PathBuf::from(prefix)),
+            _ => None,
+        });
+
+        match self {
+            Strategy::Ours => Box::new(Ours),
+            Strategy::Octopus => Box::new(Octopus),
+            Strategy::Subtree => Box::new(Subtree { prefix }),
+            Strategy::Recursive | Strategy::Ort => match prefix {
+                Some(prefix) => Box::new(Subtree {
+                    prefix: Some(prefix),
+                }),
+                None => Box::new(Recursive),
+            },
+        }
+    }
+}
+
+/// `-X` 给策略的选项
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StrategyOption {
+    Ours,
+    Theirs,
+    IgnoreSpaceChange,
+    IgnoreAllSpace,
+    IgnoreSpaceAtEol,
+    /// 把对方的树放到这个子目录下
+    Subtree(String),
+}
+
+impl FromStr for StrategyOption {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ours" => Ok(StrategyOption::Ours),
+            "theirs" => Ok(StrategyOption::Theirs),
+            "ignore-space-change" => Ok(StrategyOption::IgnoreSpaceChange),
+            "ignore-all-space" => Ok(StrategyOption::IgnoreAllSpace),
+            "ignore-space-at-eol" => Ok(StrategyOption::IgnoreSpaceAtEol),
+            _ => match s.strip_prefix("subtree=") {
+                Some(prefix) => Ok(StrategyOption::Subtree(prefix.to_string())),
+                None => Err(format!("unknown strategy option: -X{s}")),
+            },
+        }
+    }
+}
+
+pub struct Ours;
+
+impl MergeStrategy for Ours {
+    fn merge(
+        &self,
+        ugit: &Ugit,
+        head: &str,
+        _others: &[MergeHead],
+        _options: &MergeOptions,
+    ) -> Result<MergedTree, DateErr> {
+        Ok(MergedTree {
+            tree: commit_tree(ugit, head)?,
+            conflicts: HashMap::new(),
+        })
+    }
+}
+
+pub struct Recursive;
+
+impl MergeStrategy for Recursive {
+    fn merge(
+        &self,
+        ugit: &Ugit,
+        head: &str,
+        others: &[MergeHead],
+        options: &MergeOptions,
+    ) -> Result<MergedTree, DateErr> {
+        let other = single_head(others, "recursive")?;
+        let bases = ugit.get_merge_bases(head, std::slice::from_ref(&other.oid));
+        let t_base = virtual_merge_base(ugit, &bases, options)?;
+
+        ugit.merge_tress(
+            &t_base,
+            &commit_tree(ugit, head)?,
+            &commit_tree(ugit, &other.oid)?,
+            options,
+        )
+    }
+}
+
+pub struct Octopus;
+
+impl MergeStrategy for Octopus {
+    fn merge(
+        &self,
+        ugit: &Ugit,
+        head: &str,
+        others: &[MergeHead],
+        options: &MergeOptions,
+    ) -> Result<MergedTree, DateErr> {
+        let mut merged = MergedTree {
+            tree: commit_tree(ugit, head)?,
+            conflicts: HashMap::new(),
+        };
+        let mut merged_heads = vec![head.to_string()];
+
+        for (i, other) in others.iter().enumerate() {
+            //前一个分支有冲突就没法继续，只有最后一个允许留下冲突给用户解决
+            if !merged.conflicts.is_empty() {
+                return Err(DateErr::Err(String::from(
+                    "Automated merge did not work. Should not be doing an octopus.",
+                )));
+            }
+
+            let bases = ugit.get_merge_bases(&other.oid, &merged_heads);
+            if bases.contains(&other.oid) {
+                options.report(format!("Already up to date with {}", other.name));
+                continue;
+            }
+
+            options.report(format!("Trying simple merge with {}", other.name));
+            let step_options = MergeOptions {
+                theirs_label: other.name.clone(),
+                ..options.clone()
+            };
+            let t_base = virtual_merge_base(ugit, &bases, &step_options)?;
+            merged = ugit.merge_tress(
+                &t_base,
+                &merged.tree,
+                &commit_tree(ugit, &other.oid)?,
+                &step_options,
+            )?;
+            merged_heads.push(other.oid.clone());
+
+            if !merged.conflicts.is_empty() && i + 1 < others.len() {
+                return Err(DateErr::Err(String::from(
+                    "Automated merge did not work. Should not be doing an octopus.",
+                )));
+            }
+        }
+
+        Ok(merged)
+    }
+}
+
+pub struct Subtree {
+    /// 不指定时找和对方的树最匹配的子目录
+    pub prefix: Option<PathBuf>,
+}
+
+impl MergeStrategy for Subtree {
+    fn merge(
+        &self,
+        ugit: &Ugit,
+        head: &str,
+        others: &[MergeHead],
+        options: &MergeOptions,
+    ) -> Result<MergedTree, DateErr> {
+        let other = single_head(others, "subtree")?;
+        let t_head = commit_tree(ugit, head)?;
+        let t_other = commit_tree(ugit, &other.oid)?;
+
+        let prefix = self
+            .prefix
+            .clone()
+            .or_else(|| find_subtree_prefix(&t_head, &t_other));
+        let shift = |tree: HashMap<PathBuf, String>| match &prefix {
+            Some(prefix) => tree
+                .into_iter()
+                .map(|(path, oid)| (prefix.join(path), oid))
+                .collect(),
+            None => tree,
+        };
+
+        let bases = ugit.get_merge_bases(head, std::slice::from_ref(&other.oid));
+        let t_base = virtual_merge_base(ugit, &bases, options)?;
+        ugit.merge_tress(&shift(t_base), &t_head, &shift(t_other), options)
+    }
+}
+
+fn single_head<'a>(others: &'a [MergeHead], strategy: &str) -> Result<&'a MergeHead, DateErr> {
+    match others {
+        [other] => Ok(other),
+        _ => Err(DateErr::Err(format!(
+            "Strategy {strategy} can only merge one branch"
+        ))),
+    }
+}
+
+fn commit_tree(ugit: &Ugit, oid: &str) -> Result<HashMap<PathBuf, String>, DateErr> {
+    ugit.get_commit_tree(oid)
+        .ok_or_else(|| DateErr::TreeNotExists(oid.to_string()))
+}
+
+/// 有多个最好的共同祖先时，先把它们两两合并成一个虚拟的祖先，冲突标记也留在里面
+fn virtual_merge_base(
+    ugit: &Ugit,
+    bases: &[String],
+    options: &MergeOptions,
+) -> Result<HashMap<PathBuf, String>, DateErr> {
+    let mut bases = bases.iter();
+    let first = match bases.next() {
+        Some(first) => first,
+        None => return Ok(HashMap::new()),
+    };
+
+    let mut merged = vec![first.clone()];
+    let mut tree = commit_tree(ugit, first)?;
+    for base in bases {
+        let inner_bases = ugit.get_merge_bases(base, &merged);
+        let inner_options = MergeOptions {
+            ours_label: String::from("Temporary merge branch 1"),
+            theirs_label: String::from("Temporary merge branch 2"),
+            favor: None,
+            quiet: true,
+            ..options.clone()
+        };
+        let t_inner = virtual_merge_base(ugit, &inner_bases, &inner_options)?;
+
+        tree = ugit
+            .merge_tress(&t_inner, &tree, &commit_tree(ugit, base)?, &inner_options)?
+            .tree;
+        merged.push(base.clone());
+    }
+
+    Ok(tree)
+}
+
+/// 对方的文件在我们这边哪个子目录下出现得最多，都不如根目录时不移动
+fn find_subtree_prefix(
+    ours: &HashMap<PathBuf, String>,
+    theirs: &HashMap<PathBuf, String>,
+) -> Option<PathBuf> {
+    let mut scores: HashMap<&Path, usize> = HashMap::new();
+    for path in ours.keys() {
+        for prefix in path.ancestors().skip(1) {
+            if let Ok(relative) = path.strip_prefix(prefix) {
+                if theirs.contains_key(relative) {
+                    *scores.entry(prefix).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    let root = scores.get(Path::new("")).copied().unwrap_or_default();
+    scores
+        .into_iter()
+        .filter(|(_, score)| *score > root)
+        .max_by(|(a, a_score), (b, b_score)| a_score.cmp(b_score).then(b.cmp(a)))
+        .map(|(prefix, _)| prefix.to_path_buf())
+}
@@ -0,0 +1,209 @@
+use std::str::FromStr;
+
+use crate::{
+    line_diff::{self, Edit, Hunk, LineKind},
+    regex::Regex,
+};
+
+/// `--word-diff` 的输出方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WordDiffMode {
+    /// 用 `[-删除-]` 和 `{+增加+}` 标出修改的词
+    #[default]
+    Plain,
+    /// 只用颜色标出修改的词
+    Color,
+    /// 每段一行，前缀同 unified diff，`~` 表示原来的换行
+    Porcelain,
+}
+
+impl FromStr for WordDiffMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(WordDiffMode::Plain),
+            "color" => Ok(WordDiffMode::Color),
+            "porcelain" => Ok(WordDiffMode::Porcelain),
+            _ => Err(format!("unknown word diff mode: {s}")),
+        }
+    }
+}
+
+/// 按词比较的选项
+#[derive(Clone, Debug, Default)]
+pub struct WordDiff {
+    pub mode: WordDiffMode,
+    /// 匹配的部分才算词，为 None 时连续的非空白字符是一个词
+    pub regex: Option<Regex>,
+}
+
+/// hunk 中按词比较后的一段内容
+enum Segment<'a> {
+    Common(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+impl WordDiff {
+    /// 一个 hunk 按词比较的结果，不含 hunk 头，词之间的内容用新内容的
+    pub fn hunk(&self, hunk: &Hunk) -> String {
+        let side = |skip: LineKind| {
+            hunk.lines
+                .iter()
+                .filter(|(kind, _)| *kind != skip)
+                .map(|(_, line)| line.as_str())
+                .collect::<String>()
+        };
+        let (old, new) = (side(LineKind::Added), side(LineKind::Removed));
+        let (old_words, new_words) = (self.split_words(&old), self.split_words(&new));
+
+        let old_tokens = old_words
+            .iter()
+            .map(|(s, e)| &old[*s..*e])
+            .collect::<Vec<_>>();
+        let new_tokens = new_words
+            .iter()
+            .map(|(s, e)| &new[*s..*e])
+            .collect::<Vec<_>>();
+        let edits = line_diff::myers(&old_tokens, &new_tokens);
+
+        //new_pos 之前的新内容已经输出，common_end 和 old_common_end 是最后一个相同的词在两边的结尾
+        let mut segments = vec![];
+        let (mut new_pos, mut common_end, mut old_common_end) = (0, 0, 0);
+        let mut i = 0;
+        while i < edits.len() {
+            if let Edit::Equal(o, n) = edits[i] {
+                common_end = new_words[n].1;
+                old_common_end = old_words[o].1;
+                i += 1;
+                continue;
+            }
+
+            let mut removed = None::<(usize, usize)>;
+            let mut added = None::<(usize, usize)>;
+            while let Some(edit) = edits.get(i) {
+                let (range, (start, end)) = match *edit {
+                    Edit::Delete(o) => (&mut removed, old_words[o]),
+                    Edit::Insert(n) => (&mut added, new_words[n]),
+                    Edit::Equal(..) => break,
+                };
+                let range = range.get_or_insert((start, end));
+                range.1 = end;
+                i += 1;
+            }
+
+            let common = added.map_or(common_end, |(start, _)| start);
+            segments.push(Segment::Common(&new[new_pos..common]));
+            new_pos = common;
+            if let Some((start, end)) = removed {
+                //只有删除时，删除的词前面的空白用旧内容的
+                if added.is_none() {
+                    segments.push(Segment::Common(&old[old_common_end..start]));
+                }
+                segments.push(Segment::Removed(&old[start..end]));
+            }
+            if let Some((start, end)) = added {
+                segments.push(Segment::Added(&new[start..end]));
+                new_pos = end;
+                common_end = end;
+            }
+        }
+        segments.push(Segment::Common(&new[new_pos..]));
+
+        self.render(&segments)
+    }
+
+    /// 每个词在文本中的字节范围
+    fn split_words(&self, text: &str) -> Vec<(usize, usize)> {
+        match &self.regex {
+            Some(regex) => regex
+                .find_iter(text)
+                .into_iter()
+                .filter(|(start, end)| start < end)
+                .collect(),
+            None => {
+                let mut words = vec![];
+                let mut start = None;
+                for (i, c) in text.char_indices() {
+                    match (c.is_whitespace(), start) {
+                        (true, Some(s)) => {
+                            words.push((s, i));
+                            start = None;
+                        }
+                        (false, None) => start = Some(i),
+                        _ => {}
+                    }
+                }
+                if let Some(s) = start {
+                    words.push((s, text.len()));
+                }
+                words
+            }
+        }
+    }
+
+    fn render(&self, segments: &[Segment]) -> String {
+        let mut output = String::new();
+        for segment in segments {
+            let (prefix, text) = match segment {
+                Segment::Common(text) => (' ', *text),
+                Segment::Removed(text) => ('-', *text),
+                Segment::Added(text) => ('+', *text),
+            };
+            if text.is_empty() {
+                continue;
+            }
+
+            match (self.mode, prefix) {
+                (WordDiffMode::Porcelain, _) => {
+                    for (i, piece) in text.split('\n').enumerate() {
+                        if i > 0 {
+                            output.push_str("~\n");
+                        }
+                        if !piece.is_empty() {
+                            output.push(prefix);
+                            output.push_str(piece);
+                            output.push('\n');
+                        }
+                    }
+                }
+                (_, ' ') => output.push_str(text),
+                (WordDiffMode::Plain, '-') => output.push_str(&format!("[-{text}-]")),
+                (WordDiffMode::Plain, _) => output.push_str(&format!("{{+{text}+}}")),
+                (WordDiffMode::Color, '-') => output.push_str(&format!("\x1b[31m{text}\x1b[m")),
+                (WordDiffMode::Color, _) => output.push_str(&format!("\x1b[32m{text}\x1b[m")),
+            }
+        }
+
+        if self.mode != WordDiffMode::Porcelain && !output.ends_with('\n') {
+            output.push('\n');
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word_diff(old: &str, new: &str) -> String {
+        let (old_lines, new_lines) = (line_diff::split_lines(old), line_diff::split_lines(new));
+        let edits = line_diff::myers(&old_lines, &new_lines);
+        let hunks = line_diff::make_hunks(&old_lines, &new_lines, &edits, 3);
+        WordDiff::default().hunk(&hunks[0])
+    }
+
+    #[test]
+    fn removed_words_keep_old_whitespace() {
+        assert_eq!(word_diff("a b c\n", "a c\n"), "a [-b-] c\n");
+        assert_eq!(word_diff("a b c d\n", "a d\n"), "a [-b c-] d\n");
+        assert_eq!(word_diff("b c\n", "c\n"), "[-b-]c\n");
+    }
+
+    #[test]
+    fn replaced_and_added_words() {
+        assert_eq!(word_diff("a b c\n", "a x c\n"), "a [-b-]{+x+} c\n");
+        assert_eq!(word_diff("a c\n", "a b c\n"), "a {+b+} c\n");
+    }
+}
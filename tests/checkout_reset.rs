@@ -0,0 +1,91 @@
+mod common;
+
+use common::Repo;
+
+/// master 上有两次提交，side 停在第一次
+fn two_commits() -> (Repo, String, String) {
+    let mut repo = Repo::new();
+    let one = repo.commit("one", &[("a", "a\n"), ("b", "b\n")]);
+    repo.rgit(&["branch", "side"]);
+    let two = repo.commit("two", &[("a", "a2\n")]);
+    (repo, one, two)
+}
+
+#[test]
+fn checkout_refuses_local_changes() {
+    let (repo, _, _) = two_commits();
+    repo.write("a", "local\n");
+
+    repo.rgit(&["checkout", "side"]);
+    assert_eq!(repo.read("a"), "local\n");
+    assert_eq!(repo.read_ref("HEAD").unwrap(), "ref: refs/heads/master");
+
+    //不冲突的改动跟着切过去
+    repo.write("a", "a2\n");
+    repo.write("b", "local\n");
+    repo.rgit(&["checkout", "side"]);
+    assert_eq!(repo.read("a"), "a\n");
+    assert_eq!(repo.read("b"), "local\n");
+    assert_eq!(repo.read_ref("HEAD").unwrap(), "ref: refs/heads/side");
+}
+
+#[test]
+fn checkout_force_discards_local_changes() {
+    let (repo, _, _) = two_commits();
+    repo.write("a", "local\n");
+
+    repo.rgit(&["checkout", "-f", "side"]);
+    assert_eq!(repo.read("a"), "a\n");
+    assert_eq!(repo.read_ref("HEAD").unwrap(), "ref: refs/heads/side");
+}
+
+#[test]
+fn reset_hard_needs_force() {
+    let (repo, one, two) = two_commits();
+    repo.write("b", "local\n");
+
+    repo.rgit(&["reset", "--hard", "HEAD~1"]);
+    assert_eq!(repo.rev("HEAD"), two);
+    assert_eq!(repo.read("a"), "a2\n");
+    assert_eq!(repo.read("b"), "local\n");
+
+    repo.rgit(&["reset", "--hard", "-f", "HEAD~1"]);
+    assert_eq!(repo.rev("HEAD"), one);
+    assert_eq!(repo.read("a"), "a\n");
+    assert_eq!(repo.read("b"), "b\n");
+    assert_eq!(repo.read_ref("ORIG_HEAD").unwrap(), two);
+}
+
+#[test]
+fn reset_keep_carries_local_changes() {
+    let (repo, one, two) = two_commits();
+    repo.write("b", "local\n");
+
+    repo.rgit(&["reset", "--keep", "HEAD~1"]);
+    assert_eq!(repo.rev("HEAD"), one);
+    assert_eq!(repo.read("a"), "a\n");
+    assert_eq!(repo.read("b"), "local\n");
+    assert_eq!(repo.read_ref("ORIG_HEAD").unwrap(), two);
+
+    //改动的文件在两边不一样时拒绝
+    repo.write("a", "local\n");
+    repo.rgit(&["reset", "--keep", &two]);
+    assert_eq!(repo.rev("HEAD"), one);
+    assert_eq!(repo.read("a"), "local\n");
+}
+
+#[test]
+fn reset_records_orig_head() {
+    let (repo, one, two) = two_commits();
+    assert_eq!(repo.read_ref("ORIG_HEAD"), None);
+
+    repo.rgit(&["reset", "--soft", &one]);
+    assert_eq!(repo.rev("HEAD"), one);
+    assert_eq!(repo.read_ref("ORIG_HEAD").unwrap(), two);
+    //软重置不碰工作区
+    assert_eq!(repo.read("a"), "a2\n");
+
+    repo.rgit(&["reset", "ORIG_HEAD"]);
+    assert_eq!(repo.rev("HEAD"), two);
+    assert_eq!(repo.read_ref("ORIG_HEAD").unwrap(), one);
+}
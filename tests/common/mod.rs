@@ -0,0 +1,76 @@
+#![allow(dead_code)]
+
+use std::{fs, process::Command};
+
+use tempfile::TempDir;
+
+/// 在临时目录里跑 rgit 的仓库
+pub struct Repo {
+    dir: TempDir,
+    time: i64,
+}
+
+impl Repo {
+    pub fn new() -> Self {
+        let repo = Repo {
+            dir: TempDir::new().unwrap(),
+            time: 1_700_000_000,
+        };
+        repo.rgit(&["init"]);
+        repo
+    }
+
+    /// 跑一条命令，返回标准输出，命令失败时直接 panic
+    pub fn rgit(&self, args: &[&str]) -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_rgit"))
+            .arg("--no-pager")
+            .args(args)
+            .current_dir(self.dir.path())
+            .env("RGIT_AUTHOR_NAME", "A U Thor")
+            .env("RGIT_AUTHOR_EMAIL", "author@example.com")
+            .env("RGIT_COMMITTER_NAME", "C O Mitter")
+            .env("RGIT_COMMITTER_EMAIL", "committer@example.com")
+            .env("RGIT_AUTHOR_DATE", format!("{} +0000", self.time))
+            .env("RGIT_COMMITTER_DATE", format!("{} +0000", self.time))
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "rgit {args:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    pub fn write(&self, path: &str, content: &str) {
+        fs::write(self.dir.path().join(path), content).unwrap();
+    }
+
+    pub fn read(&self, path: &str) -> String {
+        fs::read_to_string(self.dir.path().join(path)).unwrap()
+    }
+
+    /// 没有这个 ref 时返回 None
+    pub fn read_ref(&self, name: &str) -> Option<String> {
+        fs::read_to_string(self.dir.path().join(".rgit").join(name)).ok()
+    }
+
+    pub fn rev(&self, rev: &str) -> String {
+        self.rgit(&["rev-list", "-n", "1", rev]).trim().to_string()
+    }
+
+    /// 写入文件后全部提交，每次提交的时间比上一次晚一分钟
+    pub fn commit(&mut self, message: &str, files: &[(&str, &str)]) -> String {
+        self.commit_at(message, files, self.time + 60)
+    }
+
+    pub fn commit_at(&mut self, message: &str, files: &[(&str, &str)], time: i64) -> String {
+        for (path, content) in files {
+            self.write(path, content);
+        }
+        self.time = time;
+        self.rgit(&["add", "-A"]);
+        self.rgit(&["commit", "-m", message]);
+        self.rev("HEAD")
+    }
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common::Repo;
+
+/// 两个分支互相合并对方的第一次提交，最佳公共祖先有两个
+fn criss_cross() -> (Repo, String, String) {
+    let mut repo = Repo::new();
+    repo.commit("base", &[("f", "1\n2\n3\n4\n5\n")]);
+    repo.rgit(&["branch", "side"]);
+    let m1 = repo.commit("m1", &[("f", "one\n2\n3\n4\n5\n")]);
+    repo.rgit(&["checkout", "side"]);
+    let s1 = repo.commit("s1", &[("f", "1\n2\n3\n4\nfive\n")]);
+
+    repo.rgit(&["merge", &m1]);
+    repo.commit("s2", &[]);
+    repo.rgit(&["checkout", "master"]);
+    repo.rgit(&["merge", &s1]);
+    repo.commit("m2", &[]);
+    assert_eq!(repo.read("f"), "one\n2\n3\n4\nfive\n");
+    (repo, m1, s1)
+}
+
+#[test]
+fn criss_cross_merge_bases() {
+    let (repo, m1, s1) = criss_cross();
+
+    let mut bases = repo
+        .rgit(&["merge-base", "--all", "master", "side"])
+        .lines()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    bases.sort();
+    let mut expected = vec![m1, s1];
+    expected.sort();
+    assert_eq!(bases, expected);
+
+    let base = repo.rgit(&["merge-base", "master", "side"]);
+    assert!(expected.contains(&base.trim().to_string()));
+}
+
+#[test]
+fn criss_cross_recursive_merge() {
+    let (mut repo, _, _) = criss_cross();
+    let master = repo.commit("m3", &[("f", "one\ntwo\n3\n4\nfive\n")]);
+    repo.rgit(&["checkout", "side"]);
+    let side = repo.commit("s3", &[("f", "one\n2\n3\nFOUR\nfive\n")]);
+    repo.rgit(&["checkout", "master"]);
+
+    //只拿其中一个祖先做基准会冲突，两个祖先合出的虚拟基准能干净合并
+    repo.rgit(&["merge", "side"]);
+    assert_eq!(repo.read("f"), "one\ntwo\n3\nFOUR\nfive\n");
+    assert_eq!(repo.read_ref("MERGE_HEAD").unwrap(), side);
+    assert_eq!(repo.read_ref("ORIG_HEAD").unwrap(), master);
+
+    let merge = repo.commit("m4", &[]);
+    let parents = repo.rgit(&["log", "-n", "1", "--format=%P", &merge]);
+    assert_eq!(parents.trim(), format!("{master} {side}"));
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use common::Repo;
+
+/// A 之后分出 side，C 的提交时间比 A 还早，最后 master 合并 side 得到 M
+///
+///   A(100) - B(300) - D(500) - M(600)
+///     \                       /
+///      C(50) ---- E(400) ----
+fn branch_and_merge() -> Repo {
+    let mut repo = Repo::new();
+    repo.commit_at("A", &[("a", "A\n")], 100);
+    repo.rgit(&["branch", "side"]);
+    repo.commit_at("B", &[("b", "B\n")], 300);
+    repo.commit_at("D", &[("d", "D\n")], 500);
+    repo.rgit(&["checkout", "side"]);
+    repo.commit_at("C", &[("c", "C\n")], 50);
+    repo.commit_at("E", &[("e", "E\n")], 400);
+    repo.rgit(&["checkout", "master"]);
+    repo.rgit(&["merge", "side"]);
+    repo.commit_at("M", &[], 600);
+    repo
+}
+
+fn subjects(repo: &Repo, args: &[&str]) -> String {
+    let output = repo.rgit(&[&["log", "--format=%s"], args].concat());
+    output.lines().collect::<Vec<_>>().join(" ")
+}
+
+#[test]
+fn default_order_follows_dates() {
+    let repo = branch_and_merge();
+    //只按时间出队，时钟倒退的 C 排在了它的父提交 A 后面
+    assert_eq!(subjects(&repo, &[]), "M D E B A C");
+    assert_eq!(subjects(&repo, &["--reverse"]), "C A B E D M");
+}
+
+#[test]
+fn date_order_shows_children_first() {
+    let repo = branch_and_merge();
+    assert_eq!(subjects(&repo, &["--date-order"]), "M D E B C A");
+    assert_eq!(
+        subjects(&repo, &["--date-order", "--reverse"]),
+        "A C B E D M"
+    );
+}
+
+#[test]
+fn topo_order_keeps_lines_together() {
+    let repo = branch_and_merge();
+    assert_eq!(subjects(&repo, &["--topo-order"]), "M D B E C A");
+    assert_eq!(
+        subjects(&repo, &["--topo-order", "--reverse"]),
+        "A C E B D M"
+    );
+}
+
+#[test]
+fn first_parent() {
+    let repo = branch_and_merge();
+    assert_eq!(subjects(&repo, &["--first-parent"]), "M D B A");
+    assert_eq!(subjects(&repo, &["--first-parent", "--reverse"]), "A B D M");
+}
+
+#[test]
+fn ancestry_path() {
+    let repo = branch_and_merge();
+    assert_eq!(
+        subjects(&repo, &["--ancestry-path", "side~1..master"]),
+        "M E"
+    );
+    assert_eq!(
+        subjects(&repo, &["--ancestry-path", "master~1..master"]),
+        "M"
+    );
+    //不加 --ancestry-path 时 B、D 也在范围里
+    assert_eq!(subjects(&repo, &["side~1..master"]), "M D E B");
+}